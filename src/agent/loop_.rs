@@ -1,7 +1,7 @@
 use crate::config::Config;
 use crate::memory::{self, Memory, MemoryCategory};
-use crate::observability::{self, Observer, ObserverEvent};
-use crate::providers::{self, ChatMessage, Provider};
+use crate::observability::{self, spans, Observer, ObserverEvent};
+use crate::providers::{self, ChatMessage, Provider, RequestContext, RequestPriority};
 use crate::runtime;
 use crate::security::SecurityPolicy;
 use crate::tools::{self, Tool};
@@ -11,6 +11,8 @@ use std::fmt::Write;
 use std::io::Write as IoWrite;
 use std::sync::Arc;
 use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 /// Maximum agentic tool-use iterations per user message to prevent runaway loops.
 const MAX_TOOL_ITERATIONS: usize = 10;
@@ -177,9 +179,14 @@ async fn agent_turn(
     model: &str,
     temperature: f64,
 ) -> Result<String> {
-    for _iteration in 0..MAX_TOOL_ITERATIONS {
-        let response = provider
-            .chat_with_history(history, model, temperature)
+    for iteration in 0..MAX_TOOL_ITERATIONS {
+        // Turns that only feed tool results back yield to fresh user turns.
+        let mut ctx = RequestContext::current_or_new();
+        if iteration > 0 {
+            ctx.priority = ctx.priority.min(RequestPriority::ToolCall);
+        }
+        let response = ctx
+            .scope(provider.chat_with_history(history, model, temperature))
             .await?;
 
         let (text, tool_calls) = parse_tool_calls(&response);
@@ -201,8 +208,14 @@ async fn agent_turn(
         for call in &tool_calls {
             let start = Instant::now();
             let result = if let Some(tool) = find_tool(tools_registry, &call.name) {
-                match tool.execute(call.arguments.clone()).await {
+                let span = spans::tool_execute(&call.name);
+                match tool
+                    .execute(call.arguments.clone())
+                    .instrument(span.clone())
+                    .await
+                {
                     Ok(r) => {
+                        spans::record_success(&span, r.success);
                         observer.record_event(&ObserverEvent::ToolCall {
                             tool: call.name.clone(),
                             duration: start.elapsed(),
@@ -215,6 +228,7 @@ async fn agent_turn(
                         }
                     }
                     Err(e) => {
+                        spans::record_success(&span, false);
                         observer.record_event(&ObserverEvent::ToolCall {
                             tool: call.name.clone(),
                             duration: start.elapsed(),
@@ -268,13 +282,62 @@ fn build_tool_instructions(tools_registry: &[Box<dyn Tool>]) -> String {
     instructions
 }
 
-#[allow(clippy::too_many_lines)]
 pub async fn run(
     config: Config,
     message: Option<String>,
     provider_override: Option<String>,
     model_override: Option<String>,
     temperature: f64,
+) -> Result<()> {
+    let provider_name = provider_override
+        .as_deref()
+        .or(config.default_provider.as_deref())
+        .unwrap_or("openrouter");
+
+    let model_name = model_override
+        .as_deref()
+        .or(config.default_model.as_deref())
+        .unwrap_or("anthropic/claude-sonnet-4-20250514");
+
+    let provider: Box<dyn Provider> = providers::create_routed_provider(
+        provider_name,
+        config.api_key.as_deref(),
+        &config.reliability,
+        &config.model_routes,
+        &config.model_routing,
+        model_name,
+    )?;
+    let provider = providers::create_experiment_provider(
+        provider,
+        provider_name,
+        config.api_key.as_deref(),
+        &config.reliability,
+        &config.experiment,
+    )?;
+
+    let model_name = model_name.to_string();
+    let provider_name = provider_name.to_string();
+    Box::pin(run_with_provider(
+        config,
+        message,
+        provider.as_ref(),
+        &provider_name,
+        &model_name,
+        temperature,
+    ))
+    .await
+}
+
+/// Run the agent against an already-built `provider`, which callers share
+/// to keep its reliability state across runs.
+#[allow(clippy::too_many_lines)]
+pub async fn run_with_provider(
+    config: Config,
+    message: Option<String>,
+    provider: &dyn Provider,
+    provider_name: &str,
+    model_name: &str,
+    temperature: f64,
 ) -> Result<()> {
     // ── Wire up agnostic subsystems ──────────────────────────────
     let observer: Arc<dyn Observer> =
@@ -308,25 +371,6 @@ pub async fn run(
         &config.browser,
     );
 
-    // ── Resolve provider ─────────────────────────────────────────
-    let provider_name = provider_override
-        .as_deref()
-        .or(config.default_provider.as_deref())
-        .unwrap_or("openrouter");
-
-    let model_name = model_override
-        .as_deref()
-        .or(config.default_model.as_deref())
-        .unwrap_or("anthropic/claude-sonnet-4-20250514");
-
-    let provider: Box<dyn Provider> = providers::create_routed_provider(
-        provider_name,
-        config.api_key.as_deref(),
-        &config.reliability,
-        &config.model_routes,
-        model_name,
-    )?;
-
     observer.record_event(&ObserverEvent::AgentStart {
         provider: provider_name.to_string(),
         model: model_name.to_string(),
@@ -416,7 +460,7 @@ pub async fn run(
         ];
 
         let response = agent_turn(
-            provider.as_ref(),
+            provider,
             &mut history,
             &tools_registry,
             observer.as_ref(),
@@ -448,7 +492,17 @@ pub async fn run(
         // Persistent conversation history across turns
         let mut history = vec![ChatMessage::system(&system_prompt)];
 
-        while let Some(msg) = rx.recv().await {
+        // A message sent while a turn is running cancels that turn and is
+        // answered next.
+        let mut pending = None;
+        loop {
+            let Some(msg) = (match pending.take() {
+                Some(msg) => Some(msg),
+                None => rx.recv().await,
+            }) else {
+                break;
+            };
+
             // Auto-save conversation turns
             if config.memory.auto_save {
                 let _ = mem
@@ -466,17 +520,35 @@ pub async fn run(
 
             history.push(ChatMessage::user(&enriched));
 
-            let response = match agent_turn(
-                provider.as_ref(),
-                &mut history,
-                &tools_registry,
-                observer.as_ref(),
-                model_name,
-                temperature,
-            )
-            .await
-            {
+            let result = {
+                let cancel = CancellationToken::new();
+                let mut turn = Box::pin(
+                    RequestContext::current_or_new()
+                        .with_cancellation(cancel.clone())
+                        .scope(agent_turn(
+                            provider,
+                            &mut history,
+                            &tools_registry,
+                            observer.as_ref(),
+                            model_name,
+                            temperature,
+                        )),
+                );
+                tokio::select! {
+                    result = &mut turn => result,
+                    Some(next) = rx.recv() => {
+                        cancel.cancel();
+                        pending = Some(next);
+                        turn.await
+                    }
+                }
+            };
+            let response = match result {
                 Ok(resp) => resp,
+                Err(e) if pending.is_some() => {
+                    tracing::debug!("Turn canceled by a newer message: {e}");
+                    continue;
+                }
                 Err(e) => {
                     eprintln!("\nError: {e}\n");
                     continue;
@@ -643,12 +715,12 @@ After text."#;
 
         // System prompt preserved
         assert_eq!(history[0].role, "system");
-        assert_eq!(history[0].content, "system prompt");
+        assert_eq!(history[0].text(), "system prompt");
         // Trimmed to limit
         assert_eq!(history.len(), MAX_HISTORY_MESSAGES + 1); // +1 for system
                                                              // Most recent messages preserved
         let last = &history[history.len() - 1];
-        assert_eq!(last.content, format!("msg {}", MAX_HISTORY_MESSAGES + 19));
+        assert_eq!(last.text(), format!("msg {}", MAX_HISTORY_MESSAGES + 19));
     }
 
     #[test]
@@ -1,3 +1,3 @@
 pub mod loop_;
 
-pub use loop_::run;
+pub use loop_::{run, run_with_provider};
@@ -1,5 +1,6 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use anyhow::Context;
@@ -7,9 +8,11 @@ use async_trait::async_trait;
 use crabclaw::channels::traits::{Channel, ChannelMessage};
 use crabclaw::memory::sqlite::SqliteMemory;
 use crabclaw::memory::traits::{Memory, MemoryCategory};
+use crabclaw::providers::clock::MockClock;
 use crabclaw::providers::reliable::{ReliableProvider, ReliableProviderStats};
-use crabclaw::providers::traits::Provider;
-use crabclaw::tools::traits::{Tool, ToolResult};
+use crabclaw::providers::traits::{ChatMessage, Provider};
+use crabclaw::tools::process::output_streaming;
+use crabclaw::tools::traits::{Tool, ToolChunk, ToolErrorKind, ToolResult};
 use serde::Serialize;
 
 #[derive(Debug, Serialize)]
@@ -24,6 +27,53 @@ struct BenchmarkMetadata {
     timestamp_utc: String,
     iterations: usize,
     note: String,
+    git_commit: String,
+    hostname: String,
+    rustc_version: String,
+    cpu_count: String,
+    bench_mode: String,
+}
+
+const UNKNOWN: &str = "unknown";
+
+impl BenchmarkMetadata {
+    /// Metadata for this run, with provenance fields that could not be
+    /// determined recorded as `"unknown"`.
+    fn collect(iterations: usize, note: String, mode: BenchMode) -> Self {
+        Self {
+            timestamp_utc: chrono::Utc::now().to_rfc3339(),
+            iterations,
+            note,
+            git_commit: std::env::var("GIT_COMMIT")
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .or_else(|| command_stdout("git", &["rev-parse", "HEAD"]))
+                .unwrap_or_else(|| UNKNOWN.to_string()),
+            hostname: hostname::get()
+                .ok()
+                .map(|h| h.to_string_lossy().to_string())
+                .filter(|h| !h.is_empty())
+                .unwrap_or_else(|| UNKNOWN.to_string()),
+            rustc_version: command_stdout("rustc", &["--version"])
+                .unwrap_or_else(|| UNKNOWN.to_string()),
+            cpu_count: std::thread::available_parallelism()
+                .map_or_else(|_| UNKNOWN.to_string(), |n| n.get().to_string()),
+            bench_mode: mode.as_str().to_string(),
+        }
+    }
+}
+
+/// Trimmed stdout of a successful command, or `None` if it could not run.
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -39,6 +89,13 @@ impl BenchMode {
             _ => Self::Synthetic,
         }
     }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Synthetic => "synthetic",
+            Self::Real => "real",
+        }
+    }
 }
 
 struct SleepProvider {
@@ -59,11 +116,55 @@ impl Provider for SleepProvider {
     }
 }
 
+/// How `RealProvider` sends the API key (`CRABCLAW_PROVIDER_AUTH_STYLE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RealAuthStyle {
+    /// `Authorization: Bearer <key>`
+    Bearer,
+    /// `x-api-key: <key>`
+    XApiKey,
+}
+
+impl RealAuthStyle {
+    fn from_env() -> Self {
+        match std::env::var("CRABCLAW_PROVIDER_AUTH_STYLE") {
+            Ok(v) if v.eq_ignore_ascii_case("x-api-key") => Self::XApiKey,
+            _ => Self::Bearer,
+        }
+    }
+}
+
+/// Parse `Name=value` pairs separated by commas, e.g.
+/// `OpenAI-Organization=org-1,HTTP-Referer=https://example.com`.
+fn parse_headers(raw: &str) -> HashMap<String, String> {
+    raw.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+/// Header value safe to log: auth-looking headers are masked.
+fn redact_header<'a>(name: &str, value: &'a str) -> &'a str {
+    let lower = name.to_ascii_lowercase();
+    let sensitive = ["auth", "key", "token", "secret", "cookie"]
+        .iter()
+        .any(|needle| lower.contains(needle));
+    if sensitive {
+        "[REDACTED]"
+    } else {
+        value
+    }
+}
+
 struct RealProvider {
     client: reqwest::Client,
     base_url: String,
     api_key: String,
     model: String,
+    auth_style: RealAuthStyle,
+    /// Extra headers sent with every request (e.g. `OpenAI-Organization`).
+    headers: HashMap<String, String>,
 }
 
 #[async_trait]
@@ -80,7 +181,26 @@ impl Provider for RealProvider {
             messages.push(serde_json::json!({"role":"system","content":sys}));
         }
         messages.push(serde_json::json!({"role":"user","content":message}));
+        self.complete(&messages).await
+    }
+
+    /// `ChatMessage` already serializes to the OpenAI shape, including the
+    /// `content` array for image parts.
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        _model: &str,
+        _temperature: f64,
+    ) -> anyhow::Result<String> {
+        self.complete(messages).await
+    }
+}
 
+impl RealProvider {
+    async fn complete(
+        &self,
+        messages: &(impl Serialize + Sync + ?Sized),
+    ) -> anyhow::Result<String> {
         let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
         let body = serde_json::json!({
             "model": self.model,
@@ -89,13 +209,20 @@ impl Provider for RealProvider {
             "max_tokens": 16
         });
 
-        let res = self
-            .client
-            .post(url)
-            .bearer_auth(&self.api_key)
-            .json(&body)
-            .send()
-            .await?;
+        let mut req = self.client.post(url).json(&body);
+        req = match self.auth_style {
+            RealAuthStyle::Bearer => req.bearer_auth(&self.api_key),
+            RealAuthStyle::XApiKey => req.header("x-api-key", &self.api_key),
+        };
+        for (name, value) in &self.headers {
+            tracing::debug!(
+                header = name.as_str(),
+                value = redact_header(name, value),
+                "real provider extra header"
+            );
+            req = req.header(name, value);
+        }
+        let res = req.send().await?;
 
         if !res.status().is_success() {
             anyhow::bail!("real provider call failed: {}", res.status());
@@ -192,6 +319,7 @@ impl Tool for SleepTool {
             success: true,
             output: "ok".to_string(),
             error: None,
+            error_kind: None,
         })
     }
 }
@@ -215,14 +343,35 @@ impl Tool for RealCommandTool {
     }
 
     async fn execute(&self, _args: serde_json::Value) -> anyhow::Result<ToolResult> {
-        let out = tokio::process::Command::new("bash")
-            .arg("-lc")
-            .arg(&self.command)
+        let out = self
+            .command()
             .output()
             .await
             .context("run real benchmark tool command")?;
+        Ok(RealCommandTool::result(&out))
+    }
 
-        Ok(ToolResult {
+    async fn execute_streaming(
+        &self,
+        _args: serde_json::Value,
+        tx: tokio::sync::mpsc::Sender<ToolChunk>,
+    ) -> anyhow::Result<ToolResult> {
+        let out = output_streaming(self.command(), &tx)
+            .await
+            .context("run real benchmark tool command")?;
+        Ok(RealCommandTool::result(&out))
+    }
+}
+
+impl RealCommandTool {
+    fn command(&self) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new("bash");
+        cmd.arg("-lc").arg(&self.command);
+        cmd
+    }
+
+    fn result(out: &std::process::Output) -> ToolResult {
+        ToolResult {
             success: out.status.success(),
             output: String::from_utf8_lossy(&out.stdout).to_string(),
             error: if out.status.success() {
@@ -230,18 +379,28 @@ impl Tool for RealCommandTool {
             } else {
                 Some(String::from_utf8_lossy(&out.stderr).to_string())
             },
-        })
+            error_kind: ToolErrorKind::from_exit_status(out.status),
+        }
     }
 }
 
 fn percentile_ms(samples: &[f64], p: f64) -> f64 {
-    if samples.is_empty() {
-        return 0.0;
-    }
+    percentile_sorted(&sorted(samples), p)
+}
+
+fn sorted(samples: &[f64]) -> Vec<f64> {
     let mut v = samples.to_vec();
     v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-    let idx = ((v.len() as f64 - 1.0) * p).round() as usize;
-    v[idx.min(v.len() - 1)]
+    v
+}
+
+/// Nearest-rank percentile of already sorted samples; 0 when empty.
+fn percentile_sorted(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let idx = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+    sorted[idx.min(sorted.len() - 1)]
 }
 
 fn average(samples: &[f64]) -> f64 {
@@ -251,13 +410,44 @@ fn average(samples: &[f64]) -> f64 {
     samples.iter().sum::<f64>() / samples.len() as f64
 }
 
+/// Population standard deviation; 0 when empty.
+fn stddev(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let mean = average(samples);
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    variance.sqrt()
+}
+
 fn insert_latency_metrics(metrics: &mut BTreeMap<String, f64>, key_prefix: &str, samples: &[f64]) {
+    let sorted = sorted(samples);
     metrics.insert(
         format!("{key_prefix}.median_ms"),
-        percentile_ms(samples, 0.50),
+        percentile_sorted(&sorted, 0.50),
+    );
+    metrics.insert(
+        format!("{key_prefix}.p90_ms"),
+        percentile_sorted(&sorted, 0.90),
     );
-    metrics.insert(format!("{key_prefix}.p90_ms"), percentile_ms(samples, 0.90));
-    metrics.insert(format!("{key_prefix}.p95_ms"), percentile_ms(samples, 0.95));
+    metrics.insert(
+        format!("{key_prefix}.p95_ms"),
+        percentile_sorted(&sorted, 0.95),
+    );
+    metrics.insert(
+        format!("{key_prefix}.p99_ms"),
+        percentile_sorted(&sorted, 0.99),
+    );
+    metrics.insert(
+        format!("{key_prefix}.min_ms"),
+        sorted.first().copied().unwrap_or(0.0),
+    );
+    metrics.insert(
+        format!("{key_prefix}.max_ms"),
+        sorted.last().copied().unwrap_or(0.0),
+    );
+    metrics.insert(format!("{key_prefix}.mean_ms"), average(samples));
+    metrics.insert(format!("{key_prefix}.stddev_ms"), stddev(samples));
 }
 
 fn env_usize(key: &str, default: usize) -> usize {
@@ -335,32 +525,77 @@ async fn bench_tool(tool: &dyn Tool, iterations: usize) -> anyhow::Result<Vec<f6
     Ok(out)
 }
 
-async fn bench_memory_recall(iterations: usize) -> anyhow::Result<(Vec<f64>, f64, f64)> {
+/// Results requested per recall query, the `k` in hit@k and nDCG@k.
+const RECALL_K: usize = 10;
+
+/// Recall quality averaged over every benchmark query.
+#[derive(Debug, Default)]
+struct RecallQuality {
+    hit_at_k: f64,
+    precision_proxy: f64,
+    mrr: f64,
+    ndcg_at_k: f64,
+}
+
+/// Reciprocal rank of the first relevant result; 0 when none is relevant.
+fn reciprocal_rank(relevance: &[bool]) -> f64 {
+    relevance
+        .iter()
+        .position(|&relevant| relevant)
+        .map_or(0.0, |pos| 1.0 / (pos + 1) as f64)
+}
+
+/// Binary-relevance nDCG over the first `k` results, normalized by the ideal
+/// ranking of `total_relevant` relevant items. 0 when nothing is relevant.
+fn ndcg_at_k(relevance: &[bool], total_relevant: usize, k: usize) -> f64 {
+    let discount = |pos: usize| 1.0 / ((pos + 2) as f64).log2();
+    let dcg: f64 = relevance
+        .iter()
+        .take(k)
+        .enumerate()
+        .filter(|(_, &relevant)| relevant)
+        .map(|(pos, _)| discount(pos))
+        .sum();
+    let ideal: f64 = (0..total_relevant.min(k)).map(discount).sum();
+    if ideal == 0.0 {
+        0.0
+    } else {
+        dcg / ideal
+    }
+}
+
+async fn bench_memory_recall(iterations: usize) -> anyhow::Result<(Vec<f64>, RecallQuality)> {
     let mut dir = std::env::temp_dir();
     dir.push(format!("crabclaw-bench-{}", uuid::Uuid::new_v4()));
     std::fs::create_dir_all(&dir)?;
 
     let mem = SqliteMemory::new(&dir)?;
 
+    // Ground truth: the keys stored under each topic.
+    let mut relevant_keys: HashMap<&str, std::collections::HashSet<String>> = HashMap::new();
     for i in 0..200 {
         let topic = if i % 2 == 0 { "rust" } else { "python" };
+        let key = format!("bench-key-{i}");
         mem.store(
-            &format!("bench-key-{i}"),
+            &key,
             &format!(
                 "This is benchmark content number {i} about {topic} memory recall latency testing."
             ),
             MemoryCategory::Conversation,
         )
         .await?;
+        relevant_keys.entry(topic).or_default().insert(key);
     }
 
     let mut out = Vec::with_capacity(iterations);
     let mut hit = 0usize;
     let mut precision_sum = 0.0f64;
+    let mut mrr_sum = 0.0f64;
+    let mut ndcg_sum = 0.0f64;
     for i in 0..iterations {
         let topic = if i % 2 == 0 { "rust" } else { "python" };
         let t0 = Instant::now();
-        let rows = mem.recall(topic, 10).await?;
+        let rows = mem.recall(topic, RECALL_K).await?;
         out.push(t0.elapsed().as_secs_f64() * 1000.0);
 
         if rows
@@ -376,12 +611,24 @@ async fn bench_memory_recall(iterations: usize) -> anyhow::Result<(Vec<f64>, f64
                 .count();
             precision_sum += relevant as f64 / rows.len() as f64;
         }
+
+        let relevant = &relevant_keys[topic];
+        let relevance: Vec<bool> = rows.iter().map(|r| relevant.contains(&r.key)).collect();
+        mrr_sum += reciprocal_rank(&relevance);
+        ndcg_sum += ndcg_at_k(&relevance, relevant.len(), RECALL_K);
     }
 
     let _ = std::fs::remove_dir_all(&dir);
-    let hit_at_k = hit as f64 / iterations as f64;
-    let precision_proxy = precision_sum / iterations as f64;
-    Ok((out, hit_at_k, precision_proxy))
+    let n = iterations.max(1) as f64;
+    Ok((
+        out,
+        RecallQuality {
+            hit_at_k: hit as f64 / n,
+            precision_proxy: precision_sum / n,
+            mrr: mrr_sum / n,
+            ndcg_at_k: ndcg_sum / n,
+        },
+    ))
 }
 
 async fn probe_http_breakdown(base_url: &str) -> anyhow::Result<(f64, f64, f64)> {
@@ -442,6 +689,46 @@ async fn collect_reliability_observability_metrics() -> anyhow::Result<ReliableP
     Ok(provider.stats_snapshot())
 }
 
+/// Drive a provider through a full circuit-breaker cycle: trip it open with
+/// consecutive failures, get rejected while open, then recover through
+/// half-open to closed once the cooldown has passed.
+async fn collect_circuit_breaker_metrics() -> anyhow::Result<ReliableProviderStats> {
+    let threshold = ReliableProvider::circuit_breaker_failure_threshold_from_env();
+    let cooldown_ms = ReliableProvider::circuit_breaker_cooldown_ms_from_env();
+    let clock = Arc::new(MockClock::new());
+    let provider = ReliableProvider::new_with_clock(
+        vec![(
+            "flaky".to_string(),
+            Box::new(FlakyProvider {
+                attempts: std::sync::Mutex::new(0),
+                fail_for_attempts: threshold as usize,
+                timeout_error: false,
+            }),
+        )],
+        0,
+        1,
+        clock.clone(),
+    );
+
+    for _ in 0..threshold {
+        let _ = provider
+            .chat_with_system(Some("bench"), "circuit", "benchmark-model", 0.0)
+            .await;
+    }
+    // Rejected without reaching the provider while the circuit is open.
+    let _ = provider
+        .chat_with_system(Some("bench"), "circuit", "benchmark-model", 0.0)
+        .await;
+
+    clock.advance(Duration::from_millis(cooldown_ms));
+    provider
+        .chat_with_system(Some("bench"), "circuit", "benchmark-model", 0.0)
+        .await
+        .context("circuit breaker recovery call")?;
+
+    Ok(provider.stats_snapshot())
+}
+
 fn benchmark_cost_inputs() -> (f64, f64, f64, f64) {
     let input_tokens = env_f64("CRABCLAW_BENCH_INPUT_TOKENS", 1200.0);
     let output_tokens = env_f64("CRABCLAW_BENCH_OUTPUT_TOKENS", 500.0);
@@ -546,6 +833,10 @@ async fn main() -> anyhow::Result<()> {
                     base_url: url,
                     api_key: key,
                     model: provider_model,
+                    auth_style: RealAuthStyle::from_env(),
+                    headers: std::env::var("CRABCLAW_BENCH_REAL_PROVIDER_HEADERS")
+                        .map(|raw| parse_headers(&raw))
+                        .unwrap_or_default(),
                 };
                 provider_fast = bench_provider(&real_provider, iterations).await?;
                 provider_normal = provider_fast.clone();
@@ -603,8 +894,7 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    let (memory_recall, memory_hit_at_k, memory_precision_proxy) =
-        bench_memory_recall(iterations).await?;
+    let (memory_recall, recall_quality) = bench_memory_recall(iterations).await?;
 
     // TTFT proxy
     let ttft_p95 = percentile_ms(&provider_fast, 0.95);
@@ -617,10 +907,18 @@ async fn main() -> anyhow::Result<()> {
     insert_latency_metrics(&mut metrics, "memory.recall", &memory_recall);
 
     metrics.insert("memory.recall.avg_ms".to_string(), average(&memory_recall));
-    metrics.insert("memory.recall.hit_at_k".to_string(), memory_hit_at_k);
+    metrics.insert(
+        "memory.recall.hit_at_k".to_string(),
+        recall_quality.hit_at_k,
+    );
     metrics.insert(
         "memory.recall.precision_proxy".to_string(),
-        memory_precision_proxy,
+        recall_quality.precision_proxy,
+    );
+    metrics.insert("memory.recall.mrr".to_string(), recall_quality.mrr);
+    metrics.insert(
+        "memory.recall.ndcg_at_k".to_string(),
+        recall_quality.ndcg_at_k,
     );
     metrics.insert(
         "ttft.p90_ms".to_string(),
@@ -656,6 +954,7 @@ async fn main() -> anyhow::Result<()> {
     metrics.insert("bench.real_tool_used".to_string(), real_tool_used);
 
     let reliability_stats = collect_reliability_observability_metrics().await?;
+    let circuit_stats = collect_circuit_breaker_metrics().await?;
     metrics.insert(
         "provider.retry_count".to_string(),
         reliability_stats.retry_count as f64,
@@ -676,43 +975,64 @@ async fn main() -> anyhow::Result<()> {
         "provider.timeout_rate".to_string(),
         reliability_stats.timeout_rate(),
     );
+    metrics.insert(
+        "provider.connection_error_count".to_string(),
+        reliability_stats.connection_error_count as f64,
+    );
+    metrics.insert(
+        "provider.deadline_exceeded_count".to_string(),
+        reliability_stats.deadline_exceeded_count as f64,
+    );
+    metrics.insert(
+        "provider.semaphore_wait_count".to_string(),
+        reliability_stats.semaphore_wait_count as f64,
+    );
+    metrics.insert(
+        "provider.retry_budget_denied_count".to_string(),
+        reliability_stats.retry_budget_denied_count as f64,
+    );
+    metrics.insert(
+        "provider.validation_reject_count".to_string(),
+        reliability_stats.validation_reject_count as f64,
+    );
+    metrics.insert(
+        "provider.shadow_mismatch_count".to_string(),
+        reliability_stats.shadow_mismatch_count as f64,
+    );
     metrics.insert(
         "provider.circuit.reject_rate".to_string(),
         reliability_stats.circuit_reject_rate(),
     );
     metrics.insert(
         "circuitbreaker.open_count".to_string(),
-        reliability_stats.circuit_open_count as f64,
+        circuit_stats.circuit_open_count as f64,
     );
     metrics.insert(
         "circuitbreaker.reject_count".to_string(),
-        reliability_stats.circuit_reject_count as f64,
+        circuit_stats.circuit_reject_count as f64,
     );
     metrics.insert(
         "circuitbreaker.state".to_string(),
-        reliability_stats.circuit_state as f64,
+        circuit_stats.circuit_state as f64,
     );
     metrics.insert(
         "circuitbreaker.half_open_count".to_string(),
-        reliability_stats.circuit_half_open_count as f64,
+        circuit_stats.circuit_half_open_count as f64,
     );
     metrics.insert(
         "circuitbreaker.close_count".to_string(),
-        reliability_stats.circuit_close_count as f64,
+        circuit_stats.circuit_close_count as f64,
     );
     // Short aliases for dashboards.
     metrics.insert(
         "cb.open_count".to_string(),
-        reliability_stats.circuit_open_count as f64,
+        circuit_stats.circuit_open_count as f64,
     );
     metrics.insert(
         "cb.reject_count".to_string(),
-        reliability_stats.circuit_reject_count as f64,
-    );
-    metrics.insert(
-        "cb.state".to_string(),
-        reliability_stats.circuit_state as f64,
+        circuit_stats.circuit_reject_count as f64,
     );
+    metrics.insert("cb.state".to_string(), circuit_stats.circuit_state as f64);
     metrics.insert(
         "cache.response.hit_rate".to_string(),
         reliability_stats.cache_hit_rate(),
@@ -721,6 +1041,10 @@ async fn main() -> anyhow::Result<()> {
         "provider.cache.hit_rate".to_string(),
         reliability_stats.cache_hit_rate(),
     );
+    metrics.insert(
+        "provider.cache.bytes".to_string(),
+        reliability_stats.cache_bytes as f64,
+    );
 
     if matches!(mode, BenchMode::Real) {
         if let Ok(base_url) = std::env::var("CRABCLAW_BENCH_REAL_PROVIDER_URL") {
@@ -740,11 +1064,7 @@ async fn main() -> anyhow::Result<()> {
     raw_samples_ms.insert("memory.recall".to_string(), memory_recall.clone());
 
     let report = BenchmarkReport {
-        metadata: BenchmarkMetadata {
-            timestamp_utc: chrono::Utc::now().to_rfc3339(),
-            iterations,
-            note: note_parts.join("; "),
-        },
+        metadata: BenchmarkMetadata::collect(iterations, note_parts.join("; "), mode),
         metrics,
         raw_samples_ms,
     };
@@ -757,3 +1077,205 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crabclaw::providers::traits::ContentPart;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn reciprocal_rank_uses_first_relevant_position() {
+        assert_close(reciprocal_rank(&[true, false, true]), 1.0);
+        assert_close(reciprocal_rank(&[false, false, true, true]), 1.0 / 3.0);
+        assert_close(reciprocal_rank(&[false, false]), 0.0);
+        assert_close(reciprocal_rank(&[]), 0.0);
+    }
+
+    #[test]
+    fn ndcg_rewards_relevant_results_ranked_higher() {
+        // Ideal ordering scores 1 regardless of trailing irrelevant results.
+        assert_close(ndcg_at_k(&[true, true, false], 2, 3), 1.0);
+
+        // [rel, irrel, rel] with 2 relevant: DCG = 1 + 1/log2(4) = 1.5,
+        // IDCG = 1 + 1/log2(3).
+        let expected = 1.5 / (1.0 + 1.0 / 3f64.log2());
+        assert_close(ndcg_at_k(&[true, false, true], 2, 3), expected);
+
+        // Same hits ranked lower score worse.
+        assert!(ndcg_at_k(&[false, true, true], 2, 3) < expected);
+
+        // Only the first k results count; ideal is capped at k.
+        assert_close(ndcg_at_k(&[false, true], 5, 1), 0.0);
+        assert_close(ndcg_at_k(&[true, true], 100, 2), 1.0);
+        assert_close(ndcg_at_k(&[false, false], 0, 10), 0.0);
+    }
+
+    /// Accept one HTTP request, answer with a canned completion and return the
+    /// raw request, lowercased.
+    async fn capture_one_request(listener: tokio::net::TcpListener) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut raw = Vec::new();
+        let mut buf = [0u8; 4096];
+        let body_len = loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+            if let Some(end) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&raw[..end]).to_ascii_lowercase();
+                let content_length = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length:"))
+                    .and_then(|v| v.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                break end + 4 + content_length;
+            }
+        };
+        while raw.len() < body_len {
+            let n = socket.read(&mut buf).await.unwrap();
+            raw.extend_from_slice(&buf[..n]);
+        }
+        let body = r#"{"choices":[{"message":{"content":"pong"}}]}"#;
+        let response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        socket.write_all(response.as_bytes()).await.unwrap();
+        String::from_utf8_lossy(&raw).to_ascii_lowercase()
+    }
+
+    #[tokio::test]
+    async fn real_provider_sends_extra_headers_and_x_api_key() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(capture_one_request(listener));
+
+        let provider = RealProvider {
+            client: reqwest::Client::new(),
+            base_url: format!("http://{addr}"),
+            api_key: "sk-test".into(),
+            model: "m".into(),
+            auth_style: RealAuthStyle::XApiKey,
+            headers: parse_headers("OpenAI-Organization=org-42, HTTP-Referer=https://crabclaw.dev"),
+        };
+        let reply = provider.chat("ping", "m", 0.0).await.unwrap();
+        let head = server.await.unwrap();
+
+        assert_eq!(reply, "pong");
+        assert!(head.contains("x-api-key: sk-test"));
+        assert!(!head.contains("authorization:"));
+        assert!(head.contains("openai-organization: org-42"));
+        assert!(head.contains("http-referer: https://crabclaw.dev"));
+    }
+
+    #[test]
+    fn latency_metrics_include_tail_and_spread() {
+        let samples: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        let mut metrics = BTreeMap::new();
+        insert_latency_metrics(&mut metrics, "x", &samples);
+
+        assert_eq!(metrics["x.median_ms"], 51.0);
+        assert_eq!(metrics["x.p90_ms"], 90.0);
+        assert_eq!(metrics["x.p95_ms"], 95.0);
+        assert_eq!(metrics["x.p99_ms"], 99.0);
+        assert_eq!(metrics["x.min_ms"], 1.0);
+        assert_eq!(metrics["x.max_ms"], 100.0);
+        assert_eq!(metrics["x.mean_ms"], 50.5);
+        assert!((metrics["x.stddev_ms"] - 28.866_070).abs() < 1e-5);
+    }
+
+    #[test]
+    fn latency_metrics_are_zero_for_empty_samples() {
+        let mut metrics = BTreeMap::new();
+        insert_latency_metrics(&mut metrics, "x", &[]);
+
+        assert_eq!(metrics.len(), 8);
+        assert!(metrics.values().all(|v| *v == 0.0));
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker_scenario_opens_and_closes() {
+        let stats = collect_circuit_breaker_metrics().await.unwrap();
+
+        assert_eq!(stats.circuit_open_count, 1);
+        assert_eq!(stats.circuit_reject_count, 1);
+        assert_eq!(stats.circuit_half_open_count, 1);
+        assert_eq!(stats.circuit_close_count, 1);
+        assert_eq!(stats.circuit_state, 0);
+    }
+
+    #[test]
+    fn metadata_provenance_fields_are_populated() {
+        let meta = BenchmarkMetadata::collect(3, "n".into(), BenchMode::Real);
+
+        assert_eq!(meta.bench_mode, "real");
+        assert_eq!(meta.iterations, 3);
+        for field in [
+            &meta.git_commit,
+            &meta.hostname,
+            &meta.rustc_version,
+            &meta.cpu_count,
+        ] {
+            assert!(!field.is_empty());
+        }
+        assert!(meta.cpu_count == UNKNOWN || meta.cpu_count.parse::<usize>().unwrap() > 0);
+        assert!(meta.rustc_version == UNKNOWN || meta.rustc_version.starts_with("rustc"));
+    }
+
+    #[tokio::test]
+    async fn real_provider_sends_image_parts_as_content_array() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(capture_one_request(listener));
+
+        let provider = RealProvider {
+            client: reqwest::Client::new(),
+            base_url: format!("http://{addr}"),
+            api_key: "sk-test".into(),
+            model: "m".into(),
+            auth_style: RealAuthStyle::Bearer,
+            headers: HashMap::new(),
+        };
+        let messages = [
+            ChatMessage::system("describe images"),
+            ChatMessage::user_parts(vec![
+                ContentPart::Text("what is this?".into()),
+                ContentPart::ImageUrl {
+                    url: "https://example.com/cat.png".into(),
+                    detail: Some("low".into()),
+                },
+            ]),
+        ];
+        provider
+            .chat_with_history(&messages, "m", 0.0)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+
+        assert_eq!(
+            body["messages"],
+            serde_json::json!([
+                {"role": "system", "content": "describe images"},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "what is this?"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/cat.png", "detail": "low"}}
+                ]}
+            ])
+        );
+    }
+
+    #[test]
+    fn auth_headers_are_redacted_for_logging() {
+        assert_eq!(redact_header("Authorization", "Bearer sk"), "[REDACTED]");
+        assert_eq!(redact_header("x-api-key", "sk"), "[REDACTED]");
+        assert_eq!(redact_header("HTTP-Referer", "https://a"), "https://a");
+    }
+}
@@ -0,0 +1,235 @@
+use super::traits::{Channel, ChannelMessage, DeliveryReceipt};
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Ids forwarded recently, oldest first.
+#[derive(Debug, Default)]
+struct SeenIds {
+    order: VecDeque<String>,
+    seen_at: HashMap<String, Instant>,
+}
+
+impl SeenIds {
+    /// Remember `id` and return `true`, or `false` when it is still remembered.
+    fn insert(&mut self, id: &str, now: Instant, window: usize, ttl: Duration) -> bool {
+        while let Some(oldest) = self.order.front() {
+            let expired = self
+                .seen_at
+                .get(oldest)
+                .is_none_or(|seen_at| now.duration_since(*seen_at) >= ttl);
+            if !expired && self.order.len() < window {
+                break;
+            }
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen_at.remove(&oldest);
+            }
+        }
+        if self.seen_at.contains_key(id) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        self.seen_at.insert(id.to_string(), now);
+        true
+    }
+}
+
+/// Channel wrapper that drops redelivered inbound messages.
+///
+/// Platforms with at-least-once webhook delivery can hand `listen` the same
+/// message twice. Every message id forwarded is remembered for `ttl`, up to
+/// the `window` most recent ids; a message whose id is still remembered is
+/// dropped before it reaches the agent. Sends are passed through untouched.
+pub struct DedupChannel {
+    inner: Arc<dyn Channel>,
+    window: usize,
+    ttl: Duration,
+    seen: Mutex<SeenIds>,
+    duplicates_dropped: AtomicU64,
+}
+
+impl DedupChannel {
+    pub fn new(inner: Arc<dyn Channel>, window: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            window: window.max(1),
+            ttl,
+            seen: Mutex::new(SeenIds::default()),
+            duplicates_dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of inbound messages dropped as duplicates.
+    pub fn duplicates_dropped(&self) -> u64 {
+        self.duplicates_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Whether `msg` is the first delivery of its id within the window.
+    fn first_delivery(&self, msg: &ChannelMessage) -> bool {
+        self.seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(&msg.id, Instant::now(), self.window, self.ttl)
+    }
+
+    /// Forward messages from the inner listener to `tx`, skipping duplicates.
+    async fn forward(
+        &self,
+        mut rx: mpsc::Receiver<ChannelMessage>,
+        tx: mpsc::Sender<ChannelMessage>,
+    ) {
+        while let Some(msg) = rx.recv().await {
+            if !self.first_delivery(&msg) {
+                self.duplicates_dropped.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!(
+                    channel = self.inner.name(),
+                    message_id = msg.id,
+                    "Dropping duplicate inbound message"
+                );
+                continue;
+            }
+            if tx.send(msg).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Channel for DedupChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()> {
+        self.inner.send(message, recipient).await
+    }
+
+    async fn send_with_id(
+        &self,
+        message_id: &str,
+        message: &str,
+        recipient: &str,
+    ) -> anyhow::Result<DeliveryReceipt> {
+        self.inner
+            .send_with_id(message_id, message, recipient)
+            .await
+    }
+
+    async fn listen(&self, tx: mpsc::Sender<ChannelMessage>) -> anyhow::Result<()> {
+        let (inner_tx, inner_rx) = mpsc::channel(tx.max_capacity());
+        let (result, ()) = tokio::join!(self.inner.listen(inner_tx), self.forward(inner_rx, tx));
+        result
+    }
+
+    async fn listen_with_shutdown(
+        &self,
+        tx: mpsc::Sender<ChannelMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        let (inner_tx, inner_rx) = mpsc::channel(tx.max_capacity());
+        let (result, ()) = tokio::join!(
+            self.inner.listen_with_shutdown(inner_tx, shutdown),
+            self.forward(inner_rx, tx)
+        );
+        result
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delivers each configured id once, in order, then returns.
+    struct ReplayChannel {
+        ids: Vec<&'static str>,
+    }
+
+    #[async_trait]
+    impl Channel for ReplayChannel {
+        fn name(&self) -> &str {
+            "replay"
+        }
+
+        async fn send(&self, _message: &str, _recipient: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn listen(&self, tx: mpsc::Sender<ChannelMessage>) -> anyhow::Result<()> {
+            for id in &self.ids {
+                let msg = ChannelMessage {
+                    id: (*id).to_string(),
+                    sender: "user".into(),
+                    content: format!("message {id}"),
+                    channel: "replay".into(),
+                    timestamp: 0,
+                    in_reply_to: None,
+                };
+                if tx.send(msg).await.is_err() {
+                    return Ok(());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    async fn forwarded(channel: &DedupChannel) -> Vec<String> {
+        let (tx, mut rx) = mpsc::channel(16);
+        channel.listen(tx).await.unwrap();
+        let mut ids = Vec::new();
+        while let Some(msg) = rx.recv().await {
+            ids.push(msg.id);
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn redelivered_message_is_forwarded_once() {
+        let channel = DedupChannel::new(
+            Arc::new(ReplayChannel {
+                ids: vec!["a", "b", "a"],
+            }),
+            128,
+            Duration::from_secs(60),
+        );
+
+        assert_eq!(forwarded(&channel).await, vec!["a", "b"]);
+        assert_eq!(channel.duplicates_dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn ids_evicted_from_window_are_forwarded_again() {
+        let channel = DedupChannel::new(
+            Arc::new(ReplayChannel {
+                ids: vec!["a", "b", "c", "a"],
+            }),
+            2,
+            Duration::from_secs(60),
+        );
+
+        assert_eq!(forwarded(&channel).await, vec!["a", "b", "c", "a"]);
+        assert_eq!(channel.duplicates_dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn ids_older_than_ttl_are_forwarded_again() {
+        let channel = DedupChannel::new(
+            Arc::new(ReplayChannel { ids: vec!["a"] }),
+            128,
+            Duration::from_millis(20),
+        );
+
+        assert_eq!(forwarded(&channel).await, vec!["a"]);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(forwarded(&channel).await, vec!["a"]);
+        assert_eq!(channel.duplicates_dropped(), 0);
+    }
+}
@@ -328,7 +328,10 @@ impl Channel for DiscordChannel {
                     let channel_id = d.get("channel_id").and_then(|c| c.as_str()).unwrap_or("").to_string();
 
                     let channel_msg = ChannelMessage {
-                        id: Uuid::new_v4().to_string(),
+                        id: d
+                            .get("id")
+                            .and_then(serde_json::Value::as_str)
+                            .map_or_else(|| Uuid::new_v4().to_string(), |id| format!("discord_{id}")),
                         sender: channel_id,
                         content: content.to_string(),
                         channel: "discord".to_string(),
@@ -336,6 +339,7 @@ impl Channel for DiscordChannel {
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        in_reply_to: None,
                     };
 
                     if tx.send(channel_msg).await.is_err() {
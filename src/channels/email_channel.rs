@@ -416,6 +416,7 @@ impl Channel for EmailChannel {
                             content,
                             channel: "email".to_string(),
                             timestamp: ts,
+                            in_reply_to: None,
                         };
                         if tx.send(msg).await.is_err() {
                             return Ok(());
@@ -178,6 +178,7 @@ end tell"#
                                 .duration_since(std::time::UNIX_EPOCH)
                                 .unwrap_or_default()
                                 .as_secs(),
+                            in_reply_to: None,
                         };
 
                         if tx.send(msg).await.is_err() {
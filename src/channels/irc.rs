@@ -561,6 +561,7 @@ impl Channel for IrcChannel {
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        in_reply_to: None,
                     };
 
                     if tx.send(channel_msg).await.is_err() {
@@ -236,6 +236,7 @@ impl Channel for MatrixChannel {
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        in_reply_to: None,
                     };
 
                     if tx.send(msg).await.is_err() {
@@ -1,9 +1,12 @@
 pub mod cli;
+pub mod dedup;
 pub mod discord;
 pub mod email_channel;
 pub mod imessage;
 pub mod irc;
 pub mod matrix;
+pub mod rate_limited;
+pub mod reliable;
 pub mod slack;
 pub mod telegram;
 pub mod traits;
@@ -23,11 +26,13 @@ pub use whatsapp::WhatsAppChannel;
 use crate::config::Config;
 use crate::identity;
 use crate::memory::{self, Memory};
-use crate::providers::{self, Provider};
+use crate::observability::spans;
+use crate::providers::{self, Provider, RequestContext};
 use crate::util::truncate_with_ellipsis;
 use anyhow::Result;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tracing::Instrument;
 
 /// Maximum characters per injected workspace file (matches `OpenClaw` default).
 const BOOTSTRAP_MAX_CHARS: usize = 20_000;
@@ -76,6 +81,17 @@ fn spawn_supervised_listener(
     })
 }
 
+/// `Channel::send` inside a `channel.send` span.
+async fn send_traced(channel: &dyn Channel, message: &str, recipient: &str) -> Result<()> {
+    let span = spans::channel_send(channel.name());
+    let result = channel
+        .send(message, recipient)
+        .instrument(span.clone())
+        .await;
+    spans::record_success(&span, result.is_ok());
+    result
+}
+
 /// Load `OpenClaw` format bootstrap files into the prompt.
 fn load_openclaw_bootstrap_files(prompt: &mut String, workspace_dir: &std::path::Path) {
     prompt
@@ -475,17 +491,33 @@ pub async fn doctor_channels(config: Config) -> Result<()> {
 /// Start all configured channels and route messages to the agent
 #[allow(clippy::too_many_lines)]
 pub async fn start_channels(config: Config) -> Result<()> {
-    let provider: Arc<dyn Provider> = Arc::from(providers::create_resilient_provider(
-        config.default_provider.as_deref().unwrap_or("openrouter"),
+    let provider_name = config.default_provider.as_deref().unwrap_or("openrouter");
+    let provider: Arc<dyn Provider> = Arc::from(providers::create_experiment_provider(
+        providers::create_resilient_provider(
+            provider_name,
+            config.api_key.as_deref(),
+            &config.reliability,
+        )?,
+        provider_name,
         config.api_key.as_deref(),
         &config.reliability,
+        &config.experiment,
     )?);
 
+    // Spend tracker the provider factories attached; relays budget alerts.
+    let cost = crate::cost::tracker();
+
     // Warm up the provider connection pool (TLS handshake, DNS, HTTP/2 setup)
     // so the first real message doesn't hit a cold-start timeout.
     if let Err(e) = provider.warmup().await {
         tracing::warn!("Provider warmup failed (non-fatal): {e}");
     }
+    if config.reliability.keepalive_interval_secs > 0 {
+        providers::spawn_keepalive(
+            &provider,
+            Duration::from_secs(config.reliability.keepalive_interval_secs),
+        );
+    }
 
     let model = config
         .default_model
@@ -697,9 +729,17 @@ pub async fn start_channels(config: Config) -> Result<()> {
         println!("  ⏳ Processing message...");
         let started_at = Instant::now();
 
+        let ctx = RequestContext::new()
+            .with_session_id(format!("{}_{}", msg.channel, msg.sender))
+            .with_channel(&msg.channel);
         let llm_result = tokio::time::timeout(
             Duration::from_secs(CHANNEL_MESSAGE_TIMEOUT_SECS),
-            provider.chat_with_system(Some(&system_prompt), &msg.content, &model, temperature),
+            ctx.clone().scope(provider.chat_with_system(
+                Some(&system_prompt),
+                &msg.content,
+                &model,
+                temperature,
+            )),
         )
         .await;
 
@@ -713,9 +753,13 @@ pub async fn start_channels(config: Config) -> Result<()> {
                 // Find the channel that sent this message and reply
                 for ch in &channels {
                     if ch.name() == msg.channel {
-                        if let Err(e) = ch.send(&response, &msg.sender).await {
+                        if let Err(e) = send_traced(ch.as_ref(), &response, &msg.sender).await {
                             eprintln!("  ❌ Failed to reply on {}: {e}", ch.name());
                         }
+                        let alert = cost.as_ref().and_then(|t| t.take_alert(Some(&ctx)));
+                        if let Some(alert) = alert {
+                            let _ = send_traced(ch.as_ref(), &alert, &msg.sender).await;
+                        }
                         break;
                     }
                 }
@@ -727,7 +771,8 @@ pub async fn start_channels(config: Config) -> Result<()> {
                 );
                 for ch in &channels {
                     if ch.name() == msg.channel {
-                        let _ = ch.send(&format!("⚠️ Error: {e}"), &msg.sender).await;
+                        let _ =
+                            send_traced(ch.as_ref(), &format!("⚠️ Error: {e}"), &msg.sender).await;
                         break;
                     }
                 }
@@ -742,12 +787,12 @@ pub async fn start_channels(config: Config) -> Result<()> {
                 );
                 for ch in &channels {
                     if ch.name() == msg.channel {
-                        let _ = ch
-                            .send(
-                                "⚠️ Request timed out while waiting for the model. Please try again.",
-                                &msg.sender,
-                            )
-                            .await;
+                        let _ = send_traced(
+                            ch.as_ref(),
+                            "⚠️ Request timed out while waiting for the model. Please try again.",
+                            &msg.sender,
+                        )
+                        .await;
                         break;
                     }
                 }
@@ -0,0 +1,175 @@
+use super::traits::{Channel, ChannelMessage, DeliveryReceipt};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Channel wrapper that paces outbound sends with a token bucket.
+///
+/// The bucket holds up to `max_per_second` tokens and refills continuously, so
+/// short bursts go out immediately and sustained traffic is held to the rate.
+/// Sends that find the bucket empty wait for the next token. `listen` is
+/// passed through untouched.
+pub struct RateLimitedChannel {
+    inner: Arc<dyn Channel>,
+    max_per_second: f64,
+    bucket: Mutex<TokenBucket>,
+    delayed_sends: AtomicU64,
+}
+
+impl RateLimitedChannel {
+    pub fn new(inner: Arc<dyn Channel>, max_per_second: u32) -> Self {
+        let max_per_second = f64::from(max_per_second.max(1));
+        Self {
+            inner,
+            max_per_second,
+            bucket: Mutex::new(TokenBucket {
+                tokens: max_per_second,
+                last_refill: Instant::now(),
+            }),
+            delayed_sends: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of sends that had to wait for a token.
+    pub fn delayed_sends(&self) -> u64 {
+        self.delayed_sends.load(Ordering::Relaxed)
+    }
+
+    /// Take one token, sleeping until one is available. The bucket lock is held
+    /// while waiting so concurrent senders are released in arrival order.
+    async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        self.refill(&mut bucket);
+        if bucket.tokens < 1.0 {
+            self.delayed_sends.fetch_add(1, Ordering::Relaxed);
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / self.max_per_second);
+            tracing::debug!(
+                channel = self.inner.name(),
+                wait_ms = wait.as_millis(),
+                "Channel send rate-limited"
+            );
+            tokio::time::sleep(wait).await;
+            self.refill(&mut bucket);
+        }
+        bucket.tokens = (bucket.tokens - 1.0).max(0.0);
+    }
+
+    fn refill(&self, bucket: &mut TokenBucket) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.max_per_second).min(self.max_per_second);
+        bucket.last_refill = now;
+    }
+}
+
+#[async_trait]
+impl Channel for RateLimitedChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()> {
+        self.acquire().await;
+        self.inner.send(message, recipient).await
+    }
+
+    async fn send_with_id(
+        &self,
+        message_id: &str,
+        message: &str,
+        recipient: &str,
+    ) -> anyhow::Result<DeliveryReceipt> {
+        self.acquire().await;
+        self.inner
+            .send_with_id(message_id, message, recipient)
+            .await
+    }
+
+    async fn listen(&self, tx: tokio::sync::mpsc::Sender<ChannelMessage>) -> anyhow::Result<()> {
+        self.inner.listen(tx).await
+    }
+
+    async fn listen_with_shutdown(
+        &self,
+        tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        self.inner.listen_with_shutdown(tx, shutdown).await
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingChannel {
+        sends: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Channel for CountingChannel {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        async fn send(&self, _message: &str, _recipient: &str) -> anyhow::Result<()> {
+            self.sends.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn listen(
+            &self,
+            _tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn burst_beyond_capacity_is_paced_to_rate() {
+        let inner = Arc::new(CountingChannel {
+            sends: AtomicUsize::new(0),
+        });
+        let channel = RateLimitedChannel::new(inner.clone(), 20);
+
+        let started = Instant::now();
+        for i in 0..30 {
+            channel.send(&format!("msg {i}"), "bob").await.unwrap();
+        }
+        let elapsed = started.elapsed();
+
+        // 20 tokens cover the initial burst; the remaining 10 arrive at 20/s.
+        assert_eq!(inner.sends.load(Ordering::SeqCst), 30);
+        assert_eq!(channel.delayed_sends(), 10);
+        assert!(elapsed >= Duration::from_millis(450), "elapsed {elapsed:?}");
+        assert!(elapsed < Duration::from_secs(3), "elapsed {elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn sends_within_capacity_are_not_delayed() {
+        let inner = Arc::new(CountingChannel {
+            sends: AtomicUsize::new(0),
+        });
+        let channel = RateLimitedChannel::new(inner.clone(), 5);
+
+        for _ in 0..5 {
+            channel.send_with_receipt("hi", "bob").await.unwrap();
+        }
+        assert_eq!(inner.sends.load(Ordering::SeqCst), 5);
+        assert_eq!(channel.delayed_sends(), 0);
+    }
+}
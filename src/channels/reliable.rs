@@ -0,0 +1,230 @@
+use super::traits::{Channel, ChannelMessage, DeliveryReceipt};
+use crate::retry::RetryPolicy;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio_util::sync::CancellationToken;
+
+/// Channel wrapper that retries failed sends with exponential backoff.
+///
+/// Every attempt for one logical send reuses the same message id, so channels
+/// that deduplicate on id deliver a retried message at most once.
+pub struct ReliableChannel {
+    inner: Arc<dyn Channel>,
+    retry: RetryPolicy,
+}
+
+impl ReliableChannel {
+    pub fn new(inner: Arc<dyn Channel>, max_retries: u32, base_backoff_ms: u64) -> Self {
+        Self {
+            inner,
+            retry: RetryPolicy::new(max_retries, base_backoff_ms.max(50))
+                .with_classifier(is_retryable_send),
+        }
+    }
+}
+
+/// Sends the platform rejected outright (bad token, unknown chat, oversized
+/// message) fail the same way on every retry. Only the typed HTTP status is
+/// trusted: channel errors quote chat and message ids that look like codes.
+fn is_retryable_send(err: &anyhow::Error) -> bool {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<reqwest::Error>()?.status())
+        .is_none_or(|status| !status.is_client_error() || matches!(status.as_u16(), 408 | 429))
+}
+
+#[async_trait]
+impl Channel for ReliableChannel {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()> {
+        self.send_with_receipt(message, recipient).await.map(|_| ())
+    }
+
+    async fn send_with_id(
+        &self,
+        message_id: &str,
+        message: &str,
+        recipient: &str,
+    ) -> anyhow::Result<DeliveryReceipt> {
+        let max_attempts = self.retry.max_retries() + 1;
+        let attempts = AtomicU32::new(0);
+        let failures = Mutex::new(Vec::new());
+
+        let result = self
+            .retry
+            .execute(|| async {
+                let attempt = attempts.fetch_add(1, Ordering::Relaxed) + 1;
+                self.inner
+                    .send_with_id(message_id, message, recipient)
+                    .await
+                    .inspect_err(|e| {
+                        tracing::warn!(
+                            channel = self.inner.name(),
+                            message_id,
+                            attempt,
+                            max_attempts,
+                            "Channel send failed: {e}"
+                        );
+                        failures
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(format!("attempt {attempt}/{max_attempts}: {e}"));
+                    })
+            })
+            .await;
+
+        match result {
+            Ok(receipt) => {
+                let attempt = attempts.load(Ordering::Relaxed);
+                if attempt > 1 {
+                    tracing::info!(
+                        channel = self.inner.name(),
+                        message_id,
+                        attempt,
+                        "Channel send recovered after retries"
+                    );
+                }
+                Ok(receipt)
+            }
+            Err(_) => anyhow::bail!(
+                "Channel {} failed to send message {message_id}:\n{}",
+                self.inner.name(),
+                failures
+                    .into_inner()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .join("\n")
+            ),
+        }
+    }
+
+    async fn listen(&self, tx: tokio::sync::mpsc::Sender<ChannelMessage>) -> anyhow::Result<()> {
+        self.inner.listen(tx).await
+    }
+
+    async fn listen_with_shutdown(
+        &self,
+        tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        self.inner.listen_with_shutdown(tx, shutdown).await
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails the first `fail_first` sends and records every message id it sees.
+    struct FlakyChannel {
+        fail_first: usize,
+        seen_ids: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Channel for FlakyChannel {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn send(&self, _message: &str, _recipient: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn send_with_id(
+            &self,
+            message_id: &str,
+            message: &str,
+            recipient: &str,
+        ) -> anyhow::Result<DeliveryReceipt> {
+            let attempt = {
+                let mut seen = self.seen_ids.lock().unwrap();
+                seen.push(message_id.to_string());
+                seen.len()
+            };
+            if attempt <= self.fail_first {
+                anyhow::bail!("temporary failure");
+            }
+            self.send(message, recipient).await?;
+            Ok(DeliveryReceipt::new(message_id))
+        }
+
+        async fn listen(
+            &self,
+            _tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn flaky(fail_first: usize) -> Arc<FlakyChannel> {
+        Arc::new(FlakyChannel {
+            fail_first,
+            seen_ids: Mutex::new(Vec::new()),
+        })
+    }
+
+    #[tokio::test]
+    async fn default_receipt_generates_unique_uuid_ids() {
+        let channel = flaky(0);
+        let before = chrono::Utc::now();
+        let a = channel.send_with_receipt("hi", "bob").await.unwrap();
+        let b = channel.send_with_receipt("hi", "bob").await.unwrap();
+
+        assert!(uuid::Uuid::parse_str(&a.message_id).is_ok());
+        assert_ne!(a.message_id, b.message_id);
+        assert!(a.accepted_at >= before);
+    }
+
+    #[tokio::test]
+    async fn retries_reuse_the_same_message_id() {
+        let inner = flaky(2);
+        let channel = ReliableChannel::new(inner.clone(), 3, 1);
+
+        let receipt = channel.send_with_receipt("hi", "bob").await.unwrap();
+
+        let seen = inner.seen_ids.lock().unwrap().clone();
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|id| *id == receipt.message_id));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let inner = flaky(10);
+        let channel = ReliableChannel::new(inner.clone(), 1, 1);
+
+        let err = channel.send("hi", "bob").await.unwrap_err();
+
+        assert!(err.to_string().contains("attempt 2/2"));
+        assert_eq!(inner.seen_ids.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let status_error = |code: u16| {
+            let response = axum::http::Response::builder()
+                .status(code)
+                .body(String::new())
+                .unwrap();
+            anyhow::Error::new(
+                reqwest::Response::from(response)
+                    .error_for_status()
+                    .unwrap_err(),
+            )
+            .context("Telegram sendMessage failed")
+        };
+
+        assert!(!is_retryable_send(&status_error(403)));
+        assert!(is_retryable_send(&status_error(429)));
+        assert!(is_retryable_send(&status_error(502)));
+        assert!(is_retryable_send(&anyhow::anyhow!(
+            "chat 404 not reachable"
+        )));
+    }
+}
@@ -1,6 +1,5 @@
 use super::traits::{Channel, ChannelMessage};
 use async_trait::async_trait;
-use uuid::Uuid;
 
 /// Slack channel — polls conversations.history via Web API
 pub struct SlackChannel {
@@ -160,7 +159,7 @@ impl Channel for SlackChannel {
                     last_ts = ts.to_string();
 
                     let channel_msg = ChannelMessage {
-                        id: Uuid::new_v4().to_string(),
+                        id: format!("slack_{channel_id}_{ts}"),
                         sender: channel_id.clone(),
                         content: text.to_string(),
                         channel: "slack".to_string(),
@@ -168,6 +167,7 @@ impl Channel for SlackChannel {
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        in_reply_to: None,
                     };
 
                     if tx.send(channel_msg).await.is_err() {
@@ -508,8 +508,17 @@ Allowlist Telegram @username or numeric user ID, then run `crabclaw onboard --ch
                         .send()
                         .await; // Ignore errors for typing indicator
 
+                    // Telegram message ids are unique within a chat and survive redelivery.
+                    let id = message
+                        .get("message_id")
+                        .and_then(serde_json::Value::as_i64)
+                        .map_or_else(
+                            || Uuid::new_v4().to_string(),
+                            |message_id| format!("telegram_{chat_id}_{message_id}"),
+                        );
+
                     let msg = ChannelMessage {
-                        id: Uuid::new_v4().to_string(),
+                        id,
                         sender: chat_id,
                         content: text.to_string(),
                         channel: "telegram".to_string(),
@@ -517,6 +526,7 @@ Allowlist Telegram @username or numeric user ID, then run `crabclaw onboard --ch
                             .duration_since(std::time::UNIX_EPOCH)
                             .unwrap_or_default()
                             .as_secs(),
+                        in_reply_to: None,
                     };
 
                     if tx.send(msg).await.is_err() {
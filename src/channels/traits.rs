@@ -1,4 +1,6 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio_util::sync::CancellationToken;
 
 /// A message received from or sent to a channel
 #[derive(Debug, Clone)]
@@ -8,6 +10,25 @@ pub struct ChannelMessage {
     pub content: String,
     pub channel: String,
     pub timestamp: u64,
+    /// Id of the message this one replies to, when the platform reports it.
+    pub in_reply_to: Option<String>,
+}
+
+/// Acknowledgement that a channel accepted an outbound message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeliveryReceipt {
+    pub message_id: String,
+    pub accepted_at: DateTime<Utc>,
+}
+
+impl DeliveryReceipt {
+    /// Receipt for `message_id`, accepted now.
+    pub fn new(message_id: impl Into<String>) -> Self {
+        Self {
+            message_id: message_id.into(),
+            accepted_at: Utc::now(),
+        }
+    }
 }
 
 /// Core channel trait — implement for any messaging platform
@@ -19,11 +40,140 @@ pub trait Channel: Send + Sync {
     /// Send a message through this channel
     async fn send(&self, message: &str, recipient: &str) -> anyhow::Result<()>;
 
+    /// Send a message and return a receipt for later correlation.
+    /// Default implementation generates a UUID message id.
+    async fn send_with_receipt(
+        &self,
+        message: &str,
+        recipient: &str,
+    ) -> anyhow::Result<DeliveryReceipt> {
+        let message_id = uuid::Uuid::new_v4().to_string();
+        self.send_with_id(&message_id, message, recipient).await
+    }
+
+    /// Send a message under a caller-chosen id, so redelivery of the same id
+    /// can be deduplicated. Channels without native ids just call `send`.
+    async fn send_with_id(
+        &self,
+        message_id: &str,
+        message: &str,
+        recipient: &str,
+    ) -> anyhow::Result<DeliveryReceipt> {
+        self.send(message, recipient).await?;
+        Ok(DeliveryReceipt::new(message_id))
+    }
+
     /// Start listening for incoming messages (long-running)
     async fn listen(&self, tx: tokio::sync::mpsc::Sender<ChannelMessage>) -> anyhow::Result<()>;
 
+    /// Listen until `shutdown` is cancelled, then return `Ok(())`.
+    /// Default implementation drops the `listen` future on cancellation, which
+    /// releases its sender so the receiving side can drain and close.
+    async fn listen_with_shutdown(
+        &self,
+        tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        shutdown: CancellationToken,
+    ) -> anyhow::Result<()> {
+        tokio::select! {
+            result = self.listen(tx) => result,
+            () = shutdown.cancelled() => Ok(()),
+        }
+    }
+
     /// Check if channel is healthy
     async fn health_check(&self) -> bool {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Emits a message every few milliseconds and never returns on its own.
+    struct ChattyChannel;
+
+    #[async_trait]
+    impl Channel for ChattyChannel {
+        fn name(&self) -> &str {
+            "chatty"
+        }
+
+        async fn send(&self, _message: &str, _recipient: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn listen(
+            &self,
+            tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+        ) -> anyhow::Result<()> {
+            loop {
+                let msg = ChannelMessage {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    sender: "bot".into(),
+                    content: "ping".into(),
+                    channel: "chatty".into(),
+                    timestamp: 0,
+                    in_reply_to: None,
+                };
+                if tx.send(msg).await.is_err() {
+                    return Ok(());
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn listen_with_shutdown_returns_promptly_and_drops_sender() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(100);
+        let shutdown = CancellationToken::new();
+        let listener = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { ChattyChannel.listen_with_shutdown(tx, shutdown).await }
+        });
+
+        assert!(rx.recv().await.is_some());
+        shutdown.cancel();
+
+        let result = tokio::time::timeout(Duration::from_secs(1), listener)
+            .await
+            .expect("listener did not stop after cancellation")
+            .unwrap();
+        assert!(result.is_ok());
+
+        // The listener's sender is gone, so the bus drains and closes.
+        while rx.recv().await.is_some() {}
+    }
+
+    #[tokio::test]
+    async fn listen_with_shutdown_surfaces_listener_errors() {
+        struct FailingChannel;
+
+        #[async_trait]
+        impl Channel for FailingChannel {
+            fn name(&self) -> &str {
+                "failing"
+            }
+
+            async fn send(&self, _message: &str, _recipient: &str) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            async fn listen(
+                &self,
+                _tx: tokio::sync::mpsc::Sender<ChannelMessage>,
+            ) -> anyhow::Result<()> {
+                anyhow::bail!("connection refused")
+            }
+        }
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let err = FailingChannel
+            .listen_with_shutdown(tx, CancellationToken::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("connection refused"));
+    }
+}
@@ -118,11 +118,15 @@ impl WhatsAppChannel {
                         });
 
                     messages.push(ChannelMessage {
-                        id: Uuid::new_v4().to_string(),
+                        id: msg.get("id").and_then(|id| id.as_str()).map_or_else(
+                            || Uuid::new_v4().to_string(),
+                            |id| format!("whatsapp_{id}"),
+                        ),
                         sender: normalized_from,
                         content,
                         channel: "whatsapp".to_string(),
                         timestamp,
+                        in_reply_to: None,
                     });
                 }
             }
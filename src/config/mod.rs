@@ -1,8 +1,9 @@
 pub mod schema;
 
 pub use schema::{
-    AutonomyConfig, BrowserConfig, ChannelsConfig, ComposioConfig, Config, DiscordConfig,
-    DockerRuntimeConfig, GatewayConfig, HeartbeatConfig, IMessageConfig, IdentityConfig,
-    MatrixConfig, MemoryConfig, ModelRouteConfig, ObservabilityConfig, ReliabilityConfig,
-    RuntimeConfig, SecretsConfig, SlackConfig, TelegramConfig, TunnelConfig, WebhookConfig,
+    AutonomyConfig, BrowserConfig, ChannelsConfig, CompatibleProviderConfig, ComposioConfig,
+    Config, CostConfig, DiscordConfig, DockerRuntimeConfig, ExperimentConfig, GatewayConfig,
+    HeartbeatConfig, IMessageConfig, IdentityConfig, MatrixConfig, MemoryConfig, ModelPriceConfig,
+    ModelRouteConfig, ModelRoutingConfig, ObservabilityConfig, ReliabilityConfig, RuntimeConfig,
+    SecretsConfig, SlackConfig, TelegramConfig, TunnelConfig, WebhookConfig,
 };
@@ -2,6 +2,7 @@ use crate::security::AutonomyLevel;
 use anyhow::{Context, Result};
 use directories::UserDirs;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -37,6 +38,18 @@ pub struct Config {
     #[serde(default)]
     pub model_routes: Vec<ModelRouteConfig>,
 
+    /// Capability-based model selection for requests whose model is `auto`.
+    #[serde(default)]
+    pub model_routing: ModelRoutingConfig,
+
+    /// A/B split of traffic between the default model and an alternate one.
+    #[serde(default)]
+    pub experiment: ExperimentConfig,
+
+    /// Token pricing and daily spend budgets.
+    #[serde(default)]
+    pub cost: CostConfig,
+
     #[serde(default)]
     pub heartbeat: HeartbeatConfig,
 
@@ -266,6 +279,15 @@ pub struct MemoryConfig {
     /// Max tokens per chunk for document splitting
     #[serde(default = "default_chunk_size")]
     pub chunk_max_tokens: usize,
+    /// Largest content a single memory entry may hold, in bytes
+    #[serde(default = "default_max_content_bytes")]
+    pub max_content_bytes: usize,
+    /// Oversized content on store: "reject" | "truncate"
+    #[serde(default = "default_oversize_mode")]
+    pub oversize_mode: String,
+    /// Cap on total content bytes returned by a single recall
+    #[serde(default = "default_max_recall_bytes")]
+    pub max_recall_bytes: usize,
 }
 
 fn default_embedding_provider() -> String {
@@ -301,6 +323,15 @@ fn default_cache_size() -> usize {
 fn default_chunk_size() -> usize {
     512
 }
+fn default_max_content_bytes() -> usize {
+    64 * 1024
+}
+fn default_oversize_mode() -> String {
+    "reject".into()
+}
+fn default_max_recall_bytes() -> usize {
+    256 * 1024
+}
 
 impl Default for MemoryConfig {
     fn default() -> Self {
@@ -318,6 +349,9 @@ impl Default for MemoryConfig {
             keyword_weight: default_keyword_weight(),
             embedding_cache_size: default_cache_size(),
             chunk_max_tokens: default_chunk_size(),
+            max_content_bytes: default_max_content_bytes(),
+            oversize_mode: default_oversize_mode(),
+            max_recall_bytes: default_max_recall_bytes(),
         }
     }
 }
@@ -529,6 +563,64 @@ pub struct ReliabilityConfig {
     /// Max retries for cron job execution attempts.
     #[serde(default = "default_scheduler_retries")]
     pub scheduler_retries: u32,
+    /// Save open provider circuits in the daemon state file and restore them
+    /// on startup, so a restart does not hammer a provider that was failing.
+    #[serde(default)]
+    pub persist_circuit_state: bool,
+    /// Persisted failure counts of circuits that were still closed are
+    /// dropped on startup once their last failure is older than this.
+    #[serde(default = "default_circuit_state_max_age_secs")]
+    pub circuit_state_max_age_secs: u64,
+    /// Named OpenAI-compatible endpoints usable as the primary provider or in
+    /// `fallback_providers` by their `name`.
+    #[serde(default)]
+    pub compatible_providers: Vec<CompatibleProviderConfig>,
+    /// Per-provider cap (ms) on a single request, keyed by provider name. A
+    /// request that runs longer fails as a timeout and falls back like any
+    /// other.
+    ///
+    /// ```toml
+    /// [reliability.request_timeout_ms]
+    /// openai = 30000
+    /// ollama = 120000
+    /// ```
+    #[serde(default)]
+    pub request_timeout_ms: BTreeMap<String, u64>,
+    /// Re-warm provider connection pools this often (seconds) while channels
+    /// and the gateway run, so the first request after an idle period does
+    /// not pay for a cold connection; 0 disables.
+    #[serde(default)]
+    pub keepalive_interval_secs: u64,
+    /// Ollama model loaded at warmup and on every keepalive, so it stays in
+    /// memory between requests.
+    #[serde(default)]
+    pub keepalive_preload_model: Option<String>,
+}
+
+/// An OpenAI-compatible endpoint with its own URL, key and headers, such as a
+/// vLLM, LM Studio or llama.cpp server.
+///
+/// ```toml
+/// [[reliability.compatible_providers]]
+/// name = "lmstudio"
+/// base_url = "http://localhost:1234/v1"
+///
+/// [[reliability.compatible_providers]]
+/// name = "groq-eu"
+/// base_url = "https://api.groq.com/openai/v1"
+/// api_key = "gsk_..."
+/// headers = { "X-Region" = "eu" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompatibleProviderConfig {
+    pub name: String,
+    pub base_url: String,
+    /// Sent as a bearer token; local servers usually need none.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Extra headers sent with every request
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
 }
 
 fn default_provider_retries() -> u32 {
@@ -555,6 +647,10 @@ fn default_scheduler_retries() -> u32 {
     2
 }
 
+fn default_circuit_state_max_age_secs() -> u64 {
+    3600
+}
+
 impl Default for ReliabilityConfig {
     fn default() -> Self {
         Self {
@@ -565,6 +661,12 @@ impl Default for ReliabilityConfig {
             channel_max_backoff_secs: default_channel_backoff_max_secs(),
             scheduler_poll_secs: default_scheduler_poll_secs(),
             scheduler_retries: default_scheduler_retries(),
+            persist_circuit_state: false,
+            circuit_state_max_age_secs: default_circuit_state_max_age_secs(),
+            compatible_providers: Vec::new(),
+            request_timeout_ms: BTreeMap::new(),
+            keepalive_interval_secs: 0,
+            keepalive_preload_model: None,
         }
     }
 }
@@ -599,6 +701,143 @@ pub struct ModelRouteConfig {
     pub api_key: Option<String>,
 }
 
+/// Models to pick from when the model is `auto`, and how to pick: the policy
+/// chooses among the models whose context window fits the prompt and that
+/// offer vision and tool calling when the request needs them.
+///
+/// ```toml
+/// [model_routing]
+/// policy = "cheapest"  # or "fastest", or "pinned:<model>"
+///
+/// [[model_routing.models]]
+/// provider = "groq"
+/// model = "llama-3.3-70b-versatile"
+/// context_window = 131072
+/// tools = true
+/// cost_tier = 1
+/// speed_tier = 1
+///
+/// [[model_routing.models]]
+/// provider = "anthropic"
+/// model = "claude-sonnet-4-20250514"
+/// context_window = 200000
+/// vision = true
+/// tools = true
+/// cost_tier = 3
+/// speed_tier = 2
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelRoutingConfig {
+    /// `cheapest` (default), `fastest` or `pinned:<model>`
+    #[serde(default)]
+    pub policy: String,
+    #[serde(default)]
+    pub models: Vec<ModelCapabilityConfig>,
+}
+
+/// One model's declared capabilities for [`ModelRoutingConfig`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelCapabilityConfig {
+    pub provider: String,
+    pub model: String,
+    /// Context window in tokens
+    pub context_window: usize,
+    #[serde(default)]
+    pub vision: bool,
+    #[serde(default)]
+    pub tools: bool,
+    /// Relative price; lower is cheaper
+    #[serde(default)]
+    pub cost_tier: u8,
+    /// Relative latency; lower is faster
+    #[serde(default)]
+    pub speed_tier: u8,
+    /// Optional API key override for this model's provider
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+// ── Experiments ──────────────────────────────────────────────────
+
+/// Send a share of requests to an alternate provider or model and compare
+/// the two arms' latency, cost and feedback, e.g. to try a cheaper model on
+/// real traffic. Conversations stay on one arm throughout.
+///
+/// ```toml
+/// [experiment]
+/// name = "haiku-trial"
+/// treatment_percent = 10
+/// treatment_provider = "anthropic"
+/// treatment_model = "claude-3-5-haiku-latest"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExperimentConfig {
+    /// Label in logs and stats; also salts the traffic split
+    #[serde(default)]
+    pub name: String,
+    /// Share of requests (0-100) sent to the treatment arm; 0 disables the
+    /// experiment
+    #[serde(default)]
+    pub treatment_percent: u8,
+    /// Treatment provider; defaults to the primary provider
+    #[serde(default)]
+    pub treatment_provider: Option<String>,
+    /// Treatment model; defaults to the requested model
+    #[serde(default)]
+    pub treatment_model: Option<String>,
+    /// Optional API key override for the treatment provider
+    #[serde(default)]
+    pub treatment_api_key: Option<String>,
+}
+
+// ── Cost ─────────────────────────────────────────────────────────
+
+/// Prices usage that providers report and budgets the spend per UTC day.
+/// Crossing the soft limit warns on the channel that spent it; past the hard
+/// limit background work (heartbeat tasks, memory summaries) is refused while
+/// replies to users still go out.
+///
+/// ```toml
+/// [cost]
+/// enabled = true
+/// daily_soft_limit_usd = 2.0
+/// daily_hard_limit_usd = 5.0
+///
+/// [cost.prices."claude-sonnet-4"]
+/// input_per_million = 3.0
+/// output_per_million = 15.0
+/// cache_read_per_million = 0.3
+/// cache_write_per_million = 3.75
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CostConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// USD per million tokens, keyed by model name prefix (any `provider/`
+    /// prefix is ignored); the longest matching prefix wins
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPriceConfig>,
+    #[serde(default)]
+    pub daily_soft_limit_usd: Option<f64>,
+    #[serde(default)]
+    pub daily_hard_limit_usd: Option<f64>,
+}
+
+/// One model's token prices for [`CostConfig`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPriceConfig {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+    /// Prompt-cache reads; the input price when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_per_million: Option<f64>,
+    /// Prompt-cache writes; the input price when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_write_per_million: Option<f64>,
+}
+
 // ── Heartbeat ────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -825,6 +1064,9 @@ impl Default for Config {
             runtime: RuntimeConfig::default(),
             reliability: ReliabilityConfig::default(),
             model_routes: Vec::new(),
+            model_routing: ModelRoutingConfig::default(),
+            experiment: ExperimentConfig::default(),
+            cost: CostConfig::default(),
             heartbeat: HeartbeatConfig::default(),
             channels_config: ChannelsConfig::default(),
             memory: MemoryConfig::default(),
@@ -1120,6 +1362,9 @@ mod tests {
             },
             reliability: ReliabilityConfig::default(),
             model_routes: Vec::new(),
+            model_routing: ModelRoutingConfig::default(),
+            experiment: ExperimentConfig::default(),
+            cost: CostConfig::default(),
             heartbeat: HeartbeatConfig {
                 enabled: true,
                 interval_minutes: 15,
@@ -1208,6 +1453,9 @@ default_temperature = 0.7
             runtime: RuntimeConfig::default(),
             reliability: ReliabilityConfig::default(),
             model_routes: Vec::new(),
+            model_routing: ModelRoutingConfig::default(),
+            experiment: ExperimentConfig::default(),
+            cost: CostConfig::default(),
             heartbeat: HeartbeatConfig::default(),
             channels_config: ChannelsConfig::default(),
             memory: MemoryConfig::default(),
@@ -0,0 +1,391 @@
+//! Spend tracking: token usage that providers report is priced per model and
+//! summed per UTC day, session and channel against soft and hard daily
+//! budgets.
+//!
+//! Entry points [`install`] a tracker from [`CostConfig`] and the provider
+//! factories hand it to each `ReliableProvider`, which records every call
+//! that reports usage and refuses background requests once the hard budget
+//! is spent. Channels relay [`CostTracker::take_alert`] to the conversation
+//! that crossed a limit.
+
+use crate::config::{CostConfig, ModelPriceConfig};
+use crate::providers::{RequestContext, RequestPriority, Usage};
+use chrono::{NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+/// How much of the daily budget is spent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BudgetLevel {
+    #[default]
+    Within,
+    /// Past the soft limit: users are warned, work goes on.
+    Soft,
+    /// Past the hard limit: background work is refused until midnight UTC.
+    Hard,
+}
+
+/// Spend since midnight UTC, from [`CostTracker::summary`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CostSummary {
+    pub total_usd: f64,
+    pub by_session: BTreeMap<String, f64>,
+    pub by_channel: BTreeMap<String, f64>,
+    pub by_model: BTreeMap<String, f64>,
+    pub level: BudgetLevel,
+}
+
+/// A background request refused because the daily hard budget is spent.
+#[derive(Debug)]
+pub struct BudgetExhausted {
+    pub spent_usd: f64,
+    pub limit_usd: f64,
+}
+
+impl std::fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Daily budget exhausted (${:.2} of ${:.2}); background work is paused until midnight UTC",
+            self.spent_usd, self.limit_usd
+        )
+    }
+}
+
+impl std::error::Error for BudgetExhausted {}
+
+#[derive(Debug, Default)]
+struct Spend {
+    day: Option<NaiveDate>,
+    summary: CostSummary,
+    /// Warnings for limits crossed today, keyed by the tenant or session
+    /// whose request crossed them, until a channel takes them.
+    alerts: BTreeMap<String, String>,
+}
+
+/// Prices reported usage and keeps today's spend.
+pub struct CostTracker {
+    /// Lowercased model prefixes, longest first.
+    prices: Vec<(String, ModelPriceConfig)>,
+    soft_limit_usd: Option<f64>,
+    hard_limit_usd: Option<f64>,
+    spend: Mutex<Spend>,
+}
+
+impl CostTracker {
+    pub fn new(config: &CostConfig) -> Self {
+        let mut prices: Vec<(String, ModelPriceConfig)> = config
+            .prices
+            .iter()
+            .map(|(prefix, price)| (prefix.to_lowercase(), *price))
+            .collect();
+        prices.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+        Self {
+            prices,
+            soft_limit_usd: config.daily_soft_limit_usd,
+            hard_limit_usd: config.daily_hard_limit_usd,
+            spend: Mutex::new(Spend::default()),
+        }
+    }
+
+    /// Price of `model`, ignoring any `provider/` prefix; `None` when no
+    /// configured prefix matches.
+    pub fn price(&self, model: &str) -> Option<ModelPriceConfig> {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        self.prices
+            .iter()
+            .find(|(prefix, _)| name.starts_with(prefix.as_str()))
+            .map(|(_, price)| *price)
+    }
+
+    /// Cost of `usage` on `model` in USD; unpriced models cost nothing.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.price(model).map_or(0.0, |price| {
+            let cache_read = price
+                .cache_read_per_million
+                .unwrap_or(price.input_per_million);
+            let cache_write = price
+                .cache_write_per_million
+                .unwrap_or(price.input_per_million);
+            (usage.input_tokens as f64 * price.input_per_million
+                + usage.output_tokens as f64 * price.output_per_million
+                + usage.cache_read_tokens as f64 * cache_read
+                + usage.cache_write_tokens as f64 * cache_write)
+                / 1_000_000.0
+        })
+    }
+
+    /// Add `usage` on `model` to today's spend under the session and channel
+    /// of `ctx`, returning its cost.
+    pub fn record(&self, model: &str, usage: &Usage, ctx: Option<&RequestContext>) -> f64 {
+        self.record_on(Utc::now().date_naive(), model, usage, ctx)
+    }
+
+    /// [`CostTracker::record`] against the current request.
+    pub(crate) fn record_current(&self, model: &str, usage: &Usage) -> f64 {
+        self.record(model, usage, RequestContext::current().as_ref())
+    }
+
+    fn record_on(
+        &self,
+        day: NaiveDate,
+        model: &str,
+        usage: &Usage,
+        ctx: Option<&RequestContext>,
+    ) -> f64 {
+        let cost = self.cost(model, usage);
+        let mut spend = self.spend.lock().unwrap_or_else(PoisonError::into_inner);
+        if spend.day != Some(day) {
+            *spend = Spend {
+                day: Some(day),
+                ..Spend::default()
+            };
+        }
+        let summary = &mut spend.summary;
+        summary.total_usd += cost;
+        *summary.by_model.entry(model.to_string()).or_default() += cost;
+        if let Some(session) = ctx.and_then(|c| c.session_id.as_deref()) {
+            *summary.by_session.entry(session.to_string()).or_default() += cost;
+        }
+        if let Some(channel) = ctx.and_then(|c| c.channel.as_deref()) {
+            *summary.by_channel.entry(channel.to_string()).or_default() += cost;
+        }
+
+        let total = summary.total_usd;
+        let level = self.level_for(total);
+        if level > summary.level {
+            summary.level = level;
+            let message = match level {
+                BudgetLevel::Soft => format!(
+                    "💸 Spent ${total:.2} today, past the ${:.2} soft budget.",
+                    self.soft_limit_usd.unwrap_or_default()
+                ),
+                _ => format!(
+                    "🛑 Spent ${total:.2} today, past the ${:.2} hard budget; background work is paused until midnight UTC.",
+                    self.hard_limit_usd.unwrap_or_default()
+                ),
+            };
+            tracing::warn!(spent_usd = total, "{message}");
+            spend.alerts.insert(alert_key(ctx), message);
+        }
+        cost
+    }
+
+    fn level_for(&self, total: f64) -> BudgetLevel {
+        let past = |limit: Option<f64>| limit.is_some_and(|limit| total >= limit);
+        if past(self.hard_limit_usd) {
+            BudgetLevel::Hard
+        } else if past(self.soft_limit_usd) {
+            BudgetLevel::Soft
+        } else {
+            BudgetLevel::Within
+        }
+    }
+
+    /// Today's spend; empty once the day has rolled over.
+    pub fn summary(&self) -> CostSummary {
+        self.summary_on(Utc::now().date_naive())
+    }
+
+    fn summary_on(&self, day: NaiveDate) -> CostSummary {
+        let spend = self.spend.lock().unwrap_or_else(PoisonError::into_inner);
+        if spend.day == Some(day) {
+            spend.summary.clone()
+        } else {
+            CostSummary::default()
+        }
+    }
+
+    /// Refuse a request at `priority` when it is background work and the
+    /// hard budget is spent.
+    pub fn admit(&self, priority: RequestPriority) -> Result<(), BudgetExhausted> {
+        self.admit_on(Utc::now().date_naive(), priority)
+    }
+
+    /// [`CostTracker::admit`] at the current request's priority.
+    pub(crate) fn admit_current(&self) -> Result<(), BudgetExhausted> {
+        let priority =
+            RequestContext::current().map_or_else(RequestPriority::default, |c| c.priority);
+        self.admit(priority)
+    }
+
+    fn admit_on(&self, day: NaiveDate, priority: RequestPriority) -> Result<(), BudgetExhausted> {
+        let summary = self.summary_on(day);
+        match self.hard_limit_usd {
+            Some(limit_usd)
+                if priority == RequestPriority::Background
+                    && summary.level == BudgetLevel::Hard =>
+            {
+                Err(BudgetExhausted {
+                    spent_usd: summary.total_usd,
+                    limit_usd,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The warning for the latest limit crossed by a request of the same
+    /// tenant or session as `ctx`, once.
+    pub fn take_alert(&self, ctx: Option<&RequestContext>) -> Option<String> {
+        self.spend
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .alerts
+            .remove(&alert_key(ctx))
+    }
+}
+
+/// Who is told about a limit crossed by `ctx`'s request: its tenant, else its
+/// session.
+fn alert_key(ctx: Option<&RequestContext>) -> String {
+    ctx.and_then(|c| c.tenant_id.as_deref().or(c.session_id.as_deref()))
+        .unwrap_or_default()
+        .to_string()
+}
+
+static TRACKER: RwLock<Option<Arc<CostTracker>>> = RwLock::new(None);
+
+/// Track spend for providers built after this as `config` describes; a
+/// disabled config removes any installed tracker.
+pub fn install(config: &CostConfig) {
+    let tracker = config.enabled.then(|| Arc::new(CostTracker::new(config)));
+    *TRACKER.write().unwrap_or_else(PoisonError::into_inner) = tracker;
+}
+
+/// The installed tracker, if spend is being tracked.
+pub fn tracker() -> Option<Arc<CostTracker>> {
+    TRACKER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> CostTracker {
+        let mut prices = BTreeMap::new();
+        prices.insert(
+            "gpt-4o".to_string(),
+            ModelPriceConfig {
+                input_per_million: 2.5,
+                output_per_million: 10.0,
+                cache_read_per_million: Some(1.25),
+                cache_write_per_million: None,
+            },
+        );
+        prices.insert(
+            "gpt-4o-mini".to_string(),
+            ModelPriceConfig {
+                input_per_million: 0.15,
+                output_per_million: 0.6,
+                ..ModelPriceConfig::default()
+            },
+        );
+        CostTracker::new(&CostConfig {
+            enabled: true,
+            prices,
+            daily_soft_limit_usd: Some(1.0),
+            daily_hard_limit_usd: Some(2.0),
+        })
+    }
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> Usage {
+        Usage {
+            input_tokens,
+            output_tokens,
+            ..Usage::default()
+        }
+    }
+
+    #[test]
+    fn prices_by_longest_prefix_and_sums_per_session_and_channel() {
+        let tracker = tracker();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+
+        let telegram = RequestContext::new()
+            .with_session_id("telegram_alice")
+            .with_channel("telegram");
+        let cost = tracker.record_on(
+            day,
+            "openai/gpt-4o-2024-08-06",
+            &usage(100_000, 10_000),
+            Some(&telegram),
+        );
+        assert!((cost - 0.35).abs() < 1e-9);
+        let cli = RequestContext::new().with_session_id("cli");
+        let cost = tracker.record_on(day, "gpt-4o-mini", &usage(1_000_000, 0), Some(&cli));
+        assert!((cost - 0.15).abs() < 1e-9);
+        let unpriced = tracker.record_on(day, "llama3", &usage(1_000, 1_000), None);
+        assert!(unpriced.abs() < f64::EPSILON);
+
+        let cached = Usage {
+            cache_read_tokens: 1_000_000,
+            cache_write_tokens: 1_000_000,
+            ..Usage::default()
+        };
+        // Cache reads at their own price, writes at the input price.
+        assert!((tracker.cost("gpt-4o", &cached) - 3.75).abs() < 1e-9);
+
+        let summary = tracker.summary_on(day);
+        assert!((summary.total_usd - 0.5).abs() < 1e-9);
+        assert_eq!(summary.by_session.len(), 2);
+        assert!((summary.by_channel["telegram"] - 0.35).abs() < 1e-9);
+        assert_eq!(summary.by_model.len(), 3);
+        assert_eq!(summary.level, BudgetLevel::Within);
+    }
+
+    #[test]
+    fn limits_warn_once_then_refuse_background_work_until_the_next_day() {
+        let tracker = tracker();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let dollar = usage(400_000, 0);
+
+        tracker.record_on(day, "gpt-4o", &dollar, None);
+        assert!(tracker.take_alert(None).unwrap().contains("soft budget"));
+        assert!(tracker.take_alert(None).is_none());
+        assert!(tracker.admit_on(day, RequestPriority::Background).is_ok());
+
+        tracker.record_on(day, "gpt-4o", &dollar, None);
+        assert!(tracker.take_alert(None).unwrap().contains("hard budget"));
+        let refused = tracker
+            .admit_on(day, RequestPriority::Background)
+            .unwrap_err();
+        assert!((refused.limit_usd - 2.0).abs() < 1e-9);
+        assert!(tracker.admit_on(day, RequestPriority::Interactive).is_ok());
+
+        let next = day.succ_opt().unwrap();
+        assert!(tracker.admit_on(next, RequestPriority::Background).is_ok());
+        tracker.record_on(next, "gpt-4o", &usage(4_000, 0), None);
+        assert_eq!(tracker.summary_on(next).level, BudgetLevel::Within);
+        assert!(tracker.take_alert(None).is_none());
+    }
+    #[test]
+    fn alerts_go_to_the_tenant_or_session_that_crossed_the_limit() {
+        let tracker = tracker();
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let alice = RequestContext::new().with_session_id("telegram_alice");
+        let bob = RequestContext::new().with_session_id("telegram_bob");
+        let acme = RequestContext::new()
+            .with_tenant_id("acme")
+            .with_session_id("telegram_bob");
+
+        tracker.record_on(day, "gpt-4o", &usage(400_000, 0), Some(&alice));
+        assert!(tracker.take_alert(Some(&bob)).is_none());
+        assert!(tracker.take_alert(None).is_none());
+        assert!(tracker
+            .take_alert(Some(&alice))
+            .unwrap()
+            .contains("soft budget"));
+
+        tracker.record_on(day, "gpt-4o", &usage(400_000, 0), Some(&acme));
+        assert!(tracker.take_alert(Some(&bob)).is_none());
+        assert!(tracker
+            .take_alert(Some(&acme))
+            .unwrap()
+            .contains("hard budget"));
+    }
+}
@@ -1,8 +1,11 @@
 use crate::config::Config;
+use crate::providers::circuit_store::{self, PersistedCircuit};
+use crate::providers::reliable::ReliableProvider;
+use crate::providers::{self, RequestContext, RequestPriority};
 use anyhow::Result;
-use chrono::Utc;
 use std::future::Future;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 use tokio::time::Duration;
 
@@ -17,6 +20,11 @@ pub async fn run(config: Config, host: String, port: u16) -> Result<()> {
 
     crate::health::mark_component_ok("daemon");
 
+    if config.reliability.persist_circuit_state {
+        let max_age = Duration::from_secs(config.reliability.circuit_state_max_age_secs);
+        restore_circuit_state(&state_file_path(&config), max_age).await;
+    }
+
     if config.heartbeat.enabled {
         let _ =
             crate::heartbeat::engine::HeartbeatEngine::ensure_heartbeat_file(&config.workspace_dir)
@@ -66,7 +74,7 @@ pub async fn run(config: Config, host: String, port: u16) -> Result<()> {
             max_backoff,
             move || {
                 let cfg = heartbeat_cfg.clone();
-                async move { run_heartbeat_worker(cfg).await }
+                async move { Box::pin(run_heartbeat_worker(cfg)).await }
             },
         ));
     }
@@ -120,19 +128,50 @@ fn spawn_state_writer(config: Config) -> JoinHandle<()> {
         let mut interval = tokio::time::interval(Duration::from_secs(STATUS_FLUSH_SECONDS));
         loop {
             interval.tick().await;
-            let mut json = crate::health::snapshot_json();
-            if let Some(obj) = json.as_object_mut() {
-                obj.insert(
-                    "written_at".into(),
-                    serde_json::json!(Utc::now().to_rfc3339()),
-                );
-            }
-            let data = serde_json::to_vec_pretty(&json).unwrap_or_else(|_| b"{}".to_vec());
-            let _ = tokio::fs::write(&path, data).await;
+            let _ = write_state_file(&path).await;
         }
     })
 }
 
+/// Write the current health snapshot (whose `updated_at` the snapshot sets)
+/// to `path`, plus any provider circuits published for persistence.
+pub async fn write_state_file(path: &Path) -> Result<()> {
+    let mut json = crate::health::snapshot_json();
+    if let Some(obj) = json.as_object_mut() {
+        let circuits = circuit_store::snapshot();
+        if !circuits.is_empty() {
+            obj.insert("circuits".into(), serde_json::to_value(circuits)?);
+        }
+    }
+    let data = serde_json::to_vec_pretty(&json)?;
+    tokio::fs::write(path, data).await?;
+    Ok(())
+}
+
+/// Seed the provider circuit store from the `circuits` saved in the state
+/// file by the previous run, aging out those older than `max_age`. A missing
+/// or unreadable file is not an error.
+async fn restore_circuit_state(path: &Path, max_age: Duration) {
+    let Ok(data) = tokio::fs::read(path).await else {
+        return;
+    };
+    let circuits = serde_json::from_slice::<serde_json::Value>(&data)
+        .ok()
+        .and_then(|mut json| json.get_mut("circuits").map(serde_json::Value::take))
+        .map(serde_json::from_value::<Vec<PersistedCircuit>>);
+    match circuits {
+        Some(Ok(circuits)) => {
+            tracing::info!(
+                count = circuits.len(),
+                "Restoring persisted provider circuits"
+            );
+            circuit_store::seed(circuits, max_age);
+        }
+        Some(Err(e)) => tracing::warn!("Ignoring malformed circuit state: {e}"),
+        None => {}
+    }
+}
+
 fn spawn_component_supervisor<F, Fut>(
     name: &'static str,
     initial_backoff_secs: u64,
@@ -170,20 +209,54 @@ where
     })
 }
 
-async fn run_heartbeat_worker(config: Config) -> Result<()> {
-    let observer: std::sync::Arc<dyn crate::observability::Observer> =
-        std::sync::Arc::from(crate::observability::create_observer(&config.observability));
-    let engine = crate::heartbeat::engine::HeartbeatEngine::new(
+/// Heartbeat engine for the daemon, reporting the stats of `provider`.
+fn heartbeat_engine(
+    config: &Config,
+    provider: &Arc<ReliableProvider>,
+) -> crate::heartbeat::engine::HeartbeatEngine {
+    let observer: Arc<dyn crate::observability::Observer> =
+        Arc::from(crate::observability::create_observer(&config.observability));
+    let stats_provider = Arc::clone(provider);
+    let mut engine = crate::heartbeat::engine::HeartbeatEngine::new(
         config.heartbeat.clone(),
         config.workspace_dir.clone(),
         observer,
-    );
+    )
+    .with_provider_stats(Arc::new(move || stats_provider.stats_snapshot()))
+    .with_state_file(state_file_path(config));
+    match crate::memory::create_memory(
+        &config.memory,
+        &config.workspace_dir,
+        config.api_key.as_deref(),
+    ) {
+        Ok(memory) => engine = engine.with_memory(Arc::from(memory)),
+        Err(e) => tracing::warn!("Heartbeat memory pruning disabled: {e}"),
+    }
+    engine
+}
+
+async fn run_heartbeat_worker(config: Config) -> Result<()> {
+    let provider_name = config
+        .default_provider
+        .clone()
+        .unwrap_or_else(|| "openrouter".into());
+    let model_name = config
+        .default_model
+        .clone()
+        .unwrap_or_else(|| "anthropic/claude-sonnet-4-20250514".into());
+    let provider = Arc::new(providers::create_reliable_provider(
+        &provider_name,
+        config.api_key.as_deref(),
+        &config.reliability,
+    )?);
+    let engine = heartbeat_engine(&config, &provider);
 
     let interval_mins = config.heartbeat.interval_minutes.max(5);
     let mut interval = tokio::time::interval(Duration::from_secs(u64::from(interval_mins) * 60));
 
     loop {
         interval.tick().await;
+        engine.maintenance_tick().await;
 
         let tasks = engine.collect_tasks().await?;
         if tasks.is_empty() {
@@ -193,8 +266,16 @@ async fn run_heartbeat_worker(config: Config) -> Result<()> {
         for task in tasks {
             let prompt = format!("[Heartbeat Task] {task}");
             let temp = config.default_temperature;
-            if let Err(e) = crate::agent::run(config.clone(), Some(prompt), None, None, temp).await
-            {
+            let run = crate::agent::run_with_provider(
+                config.clone(),
+                Some(prompt),
+                provider.as_ref(),
+                &provider_name,
+                &model_name,
+                temp,
+            );
+            let ctx = RequestContext::new().with_priority(RequestPriority::Background);
+            if let Err(e) = ctx.scope(run).await {
                 crate::health::mark_component_error("heartbeat", e.to_string());
                 tracing::warn!("Heartbeat task failed: {e}");
             } else {
@@ -276,6 +357,44 @@ mod tests {
             .contains("component exited unexpectedly"));
     }
 
+    struct StubProvider;
+
+    #[async_trait::async_trait]
+    impl crate::providers::Provider for StubProvider {
+        async fn chat_with_system(
+            &self,
+            _system_prompt: Option<&str>,
+            _message: &str,
+            _model: &str,
+            _temperature: f64,
+        ) -> anyhow::Result<String> {
+            Ok("ok".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn heartbeat_summary_reports_the_daemon_provider_stats() {
+        use crate::providers::Provider;
+
+        let tmp = TempDir::new().unwrap();
+        let config = test_config(&tmp);
+        let provider = Arc::new(ReliableProvider::new(
+            vec![("stub".into(), Box::new(StubProvider))],
+            0,
+            1,
+        ));
+        let engine = heartbeat_engine(&config, &provider);
+
+        provider
+            .chat_with_system(None, "ping", "test-model", 0.0)
+            .await
+            .unwrap();
+        let summary = engine.maintenance_tick().await;
+
+        assert_eq!(summary.provider_stats, Some(provider.stats_snapshot()));
+        assert!(summary.line().contains("providers=[calls=1 retries=0"));
+    }
+
     #[test]
     fn detects_no_supervised_channels() {
         let config = Config::default();
@@ -78,9 +78,10 @@ pub fn run(config: &Config) -> Result<()> {
     checks.push(CheckResult {
         name: "daemon.state.fresh".into(),
         ok: daemon_age.is_some_and(|age| age <= 60),
-        detail: daemon_age
-            .map(|age| format!("state age {age}s"))
-            .unwrap_or_else(|| "state file missing/stale".into()),
+        detail: daemon_age.map_or_else(
+            || "state file missing/stale".into(),
+            |age| format!("state age {age}s"),
+        ),
     });
 
     checks.push(CheckResult {
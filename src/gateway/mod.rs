@@ -181,11 +181,24 @@ pub async fn run_gateway(host: &str, port: u16, config: Config) -> Result<()> {
     let actual_port = listener.local_addr()?.port();
     let display_addr = format!("{host}:{actual_port}");
 
-    let provider: Arc<dyn Provider> = Arc::from(providers::create_resilient_provider(
-        config.default_provider.as_deref().unwrap_or("openrouter"),
+    let provider_name = config.default_provider.as_deref().unwrap_or("openrouter");
+    let provider: Arc<dyn Provider> = Arc::from(providers::create_experiment_provider(
+        providers::create_resilient_provider(
+            provider_name,
+            config.api_key.as_deref(),
+            &config.reliability,
+        )?,
+        provider_name,
         config.api_key.as_deref(),
         &config.reliability,
+        &config.experiment,
     )?);
+    if config.reliability.keepalive_interval_secs > 0 {
+        providers::spawn_keepalive(
+            &provider,
+            Duration::from_secs(config.reliability.keepalive_interval_secs),
+        );
+    }
     let model = config
         .default_model
         .clone()
@@ -1,16 +1,38 @@
 use crate::config::HeartbeatConfig;
+use crate::memory::Memory;
 use crate::observability::{Observer, ObserverEvent};
+use crate::providers::clock::{Clock, SystemClock};
+use crate::providers::reliable::ReliableProviderStats;
 use anyhow::Result;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
 use tokio::time::{self, Duration};
 use tracing::{info, warn};
 
-/// Heartbeat engine — reads HEARTBEAT.md and executes tasks periodically
+/// Snapshots provider reliability counters for the heartbeat health summary
+pub type ProviderStatsSource = Arc<dyn Fn() -> ReliableProviderStats + Send + Sync>;
+
+/// What one maintenance pass did; `None` fields were not configured or failed.
+#[derive(Debug, Clone, Default)]
+pub struct HeartbeatSummary {
+    pub pruned: Option<usize>,
+    pub provider_stats: Option<ReliableProviderStats>,
+    pub state_written: bool,
+    pub since_last_tick: Option<Duration>,
+}
+
+/// Heartbeat engine — reads HEARTBEAT.md and executes tasks periodically,
+/// pruning memory and refreshing the daemon state file on every tick.
 pub struct HeartbeatEngine {
     config: HeartbeatConfig,
     workspace_dir: std::path::PathBuf,
     observer: Arc<dyn Observer>,
+    memory: Option<Arc<dyn Memory>>,
+    provider_stats: Option<ProviderStatsSource>,
+    state_file: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+    last_tick: Mutex<Option<Instant>>,
 }
 
 impl HeartbeatEngine {
@@ -23,9 +45,42 @@ impl HeartbeatEngine {
             config,
             workspace_dir,
             observer,
+            memory: None,
+            provider_stats: None,
+            state_file: None,
+            clock: Arc::new(SystemClock),
+            last_tick: Mutex::new(None),
         }
     }
 
+    /// Memory whose expired entries are pruned on each tick
+    #[must_use]
+    pub fn with_memory(mut self, memory: Arc<dyn Memory>) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Provider stats included in the per-tick health summary
+    #[must_use]
+    pub fn with_provider_stats(mut self, source: ProviderStatsSource) -> Self {
+        self.provider_stats = Some(source);
+        self
+    }
+
+    /// Daemon state file rewritten on each tick
+    #[must_use]
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(path);
+        self
+    }
+
+    /// Clock used to measure the time between ticks
+    #[must_use]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Start the heartbeat loop (runs until cancelled)
     pub async fn run(&self) -> Result<()> {
         if !self.config.enabled {
@@ -41,6 +96,7 @@ impl HeartbeatEngine {
         loop {
             interval.tick().await;
             self.observer.record_event(&ObserverEvent::HeartbeatTick);
+            self.maintenance_tick().await;
 
             match self.tick().await {
                 Ok(tasks) => {
@@ -59,6 +115,52 @@ impl HeartbeatEngine {
         }
     }
 
+    /// Prune memory, snapshot provider stats, refresh the daemon state file and
+    /// log a one-line health summary. Each step fails independently: errors
+    /// are logged and never abort the heartbeat loop.
+    pub async fn maintenance_tick(&self) -> HeartbeatSummary {
+        let now = self.clock.now();
+        let since_last_tick = self
+            .last_tick
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .replace(now)
+            .map(|last| now.saturating_duration_since(last));
+
+        let pruned = match &self.memory {
+            Some(memory) => match memory.prune_expired().await {
+                Ok(pruned) => Some(pruned),
+                Err(e) => {
+                    warn!("💓 Heartbeat memory pruning failed: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let provider_stats = self.provider_stats.as_ref().map(|source| source());
+
+        let state_written = match &self.state_file {
+            Some(path) => match crate::daemon::write_state_file(path).await {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("💓 Heartbeat failed to write {}: {e}", path.display());
+                    false
+                }
+            },
+            None => false,
+        };
+
+        let summary = HeartbeatSummary {
+            pruned,
+            provider_stats,
+            state_written,
+            since_last_tick,
+        };
+        info!("💓 Heartbeat health: {}", summary.line());
+        summary
+    }
+
     /// Single heartbeat tick — read HEARTBEAT.md and return task count
     async fn tick(&self) -> Result<usize> {
         Ok(self.collect_tasks().await?.len())
@@ -103,6 +205,31 @@ impl HeartbeatEngine {
     }
 }
 
+impl HeartbeatSummary {
+    /// Concise `key=value` health line for logs
+    pub fn line(&self) -> String {
+        let pruned = self
+            .pruned
+            .map_or_else(|| "n/a".to_string(), |n| n.to_string());
+        let providers = self.provider_stats.as_ref().map_or_else(
+            || "n/a".to_string(),
+            |stats| {
+                format!(
+                    "calls={} retries={} cache_hit_rate={:.2} circuit_open={}",
+                    stats.total_calls,
+                    stats.retry_count,
+                    stats.cache_hit_rate(),
+                    stats.circuit_state == 1,
+                )
+            },
+        );
+        format!(
+            "pruned={pruned} state_written={} providers=[{providers}]",
+            self.state_written
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -283,6 +410,124 @@ mod tests {
         let _ = tokio::fs::remove_dir_all(&dir).await;
     }
 
+    struct PruneCountingMemory {
+        prunes: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Memory for PruneCountingMemory {
+        fn name(&self) -> &str {
+            "prune-counting"
+        }
+
+        async fn store(
+            &self,
+            _key: &str,
+            _content: &str,
+            _category: crate::memory::MemoryCategory,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn recall(
+            &self,
+            _query: &str,
+            _limit: usize,
+        ) -> Result<Vec<crate::memory::MemoryEntry>> {
+            Ok(Vec::new())
+        }
+
+        async fn get(&self, _key: &str) -> Result<Option<crate::memory::MemoryEntry>> {
+            Ok(None)
+        }
+
+        async fn list(
+            &self,
+            _category: Option<&crate::memory::MemoryCategory>,
+        ) -> Result<Vec<crate::memory::MemoryEntry>> {
+            Ok(Vec::new())
+        }
+
+        async fn forget(&self, _key: &str) -> Result<bool> {
+            Ok(false)
+        }
+
+        async fn count(&self) -> Result<usize> {
+            Ok(0)
+        }
+
+        async fn health_check(&self) -> bool {
+            true
+        }
+
+        async fn prune_expired(&self) -> Result<usize> {
+            self.prunes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(2)
+        }
+    }
+
+    #[tokio::test]
+    async fn maintenance_tick_prunes_memory_and_writes_state_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let state_file = tmp.path().join("daemon_state.json");
+        let memory = Arc::new(PruneCountingMemory {
+            prunes: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let clock = Arc::new(crate::providers::clock::MockClock::new());
+        let observer: Arc<dyn Observer> = Arc::new(crate::observability::NoopObserver);
+        let engine = HeartbeatEngine::new(
+            HeartbeatConfig {
+                enabled: true,
+                interval_minutes: 30,
+            },
+            tmp.path().to_path_buf(),
+            observer,
+        )
+        .with_memory(memory.clone())
+        .with_provider_stats(Arc::new(|| ReliableProviderStats {
+            total_calls: 7,
+            ..ReliableProviderStats::default()
+        }))
+        .with_state_file(state_file.clone())
+        .with_clock(clock.clone());
+
+        let summary = engine.maintenance_tick().await;
+        assert_eq!(memory.prunes.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(summary.pruned, Some(2));
+        assert!(summary.state_written);
+        assert!(summary.since_last_tick.is_none());
+        assert!(summary.line().contains("calls=7"));
+
+        let state: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&state_file).unwrap()).unwrap();
+        assert!(state.get("updated_at").and_then(|v| v.as_str()).is_some());
+
+        clock.advance(Duration::from_secs(1800));
+        let summary = engine.maintenance_tick().await;
+        assert_eq!(summary.since_last_tick, Some(Duration::from_secs(1800)));
+        assert_eq!(memory.prunes.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn maintenance_tick_survives_unwritable_state_file() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let observer: Arc<dyn Observer> = Arc::new(crate::observability::NoopObserver);
+        let engine = HeartbeatEngine::new(
+            HeartbeatConfig {
+                enabled: true,
+                interval_minutes: 30,
+            },
+            tmp.path().to_path_buf(),
+            observer,
+        )
+        .with_state_file(tmp.path().join("missing").join("state.json"));
+
+        let summary = engine.maintenance_tick().await;
+        assert!(!summary.state_written);
+        assert!(summary.pruned.is_none());
+    }
+
     #[tokio::test]
     async fn run_returns_immediately_when_disabled() {
         let observer: Arc<dyn Observer> = Arc::new(crate::observability::NoopObserver);
@@ -17,6 +17,7 @@ use serde::{Deserialize, Serialize};
 pub mod agent;
 pub mod channels;
 pub mod config;
+pub mod cost;
 pub mod cron;
 pub mod daemon;
 pub mod doctor;
@@ -30,6 +31,7 @@ pub mod migration;
 pub mod observability;
 pub mod onboard;
 pub mod providers;
+pub mod retry;
 pub mod runtime;
 pub mod security;
 pub mod service;
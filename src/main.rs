@@ -16,6 +16,7 @@ use tracing_subscriber::FmtSubscriber;
 mod agent;
 mod channels;
 mod config;
+mod cost;
 mod cron;
 mod daemon;
 mod diagnose;
@@ -30,6 +31,7 @@ mod migration;
 mod observability;
 mod onboard;
 mod providers;
+mod retry;
 mod runtime;
 mod security;
 mod service;
@@ -274,6 +276,11 @@ async fn main() -> Result<()> {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .finish();
+    #[cfg(feature = "otel")]
+    let subscriber = {
+        use tracing_subscriber::layer::SubscriberExt;
+        subscriber.with(observability::spans::layer())
+    };
 
     tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
@@ -302,6 +309,7 @@ async fn main() -> Result<()> {
         };
         // Auto-start channels if user said yes during wizard
         if std::env::var("CRABCLAW_AUTOSTART_CHANNELS").as_deref() == Ok("1") {
+            cost::install(&config.cost);
             channels::start_channels(config).await?;
         }
         return Ok(());
@@ -309,6 +317,7 @@ async fn main() -> Result<()> {
 
     // All other commands need config loaded first
     let config = Config::load_or_init()?;
+    cost::install(&config.cost);
 
     match cli.command {
         Commands::Onboard { .. } => unreachable!(),
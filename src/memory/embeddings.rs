@@ -1,4 +1,6 @@
+use crate::providers::Provider;
 use async_trait::async_trait;
+use std::sync::Arc;
 
 /// Trait for embedding providers — convert text to vectors
 #[async_trait]
@@ -123,6 +125,42 @@ impl EmbeddingProvider for OpenAiEmbedding {
     }
 }
 
+// ── Chat provider embeddings ─────────────────────────────────
+
+/// Embeds through a chat [`Provider`], e.g. a `ReliableProvider` chain, so
+/// embedding calls share its retries, fallbacks and response cache.
+pub struct ProviderEmbedding {
+    provider: Arc<dyn Provider>,
+    model: String,
+    dims: usize,
+}
+
+impl ProviderEmbedding {
+    pub fn new(provider: Arc<dyn Provider>, model: &str, dims: usize) -> Self {
+        Self {
+            provider,
+            model: model.to_string(),
+            dims,
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for ProviderEmbedding {
+    fn name(&self) -> &str {
+        "provider"
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dims
+    }
+
+    async fn embed(&self, texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let texts: Vec<String> = texts.iter().map(ToString::to_string).collect();
+        self.provider.embed(&texts, &self.model).await
+    }
+}
+
 // ── Factory ──────────────────────────────────────────────────
 
 pub fn create_embedding_provider(
@@ -0,0 +1,125 @@
+// At-rest encryption for memory content.
+//
+// The key is derived from a passphrase (env `CRABCLAW_MEMORY_ENCRYPTION_KEY`)
+// and a random per-database salt with PBKDF2-HMAC-SHA256, and used with
+// ChaCha20-Poly1305. Every row gets a fresh random nonce, stored next to the
+// ciphertext.
+
+use anyhow::Result;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng};
+use chacha20poly1305::{AeadCore, ChaCha20Poly1305, Key, Nonce};
+use sha2::Sha256;
+
+/// Env var holding the memory encryption passphrase
+pub const ENCRYPTION_KEY_ENV: &str = "CRABCLAW_MEMORY_ENCRYPTION_KEY";
+
+/// Nonce length for ChaCha20-Poly1305 (96 bits)
+pub const NONCE_LEN: usize = 12;
+
+/// Length of the random salt generated for each database
+pub const SALT_LEN: usize = 16;
+
+const KDF_ROUNDS: u32 = 100_000;
+
+/// Encrypts and decrypts memory content with a passphrase-derived key.
+pub struct MemoryCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl MemoryCipher {
+    /// Cipher keyed by `passphrase` stretched with the database's `salt`.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Self {
+        let key = derive_key(passphrase.as_bytes(), salt);
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+
+    /// Passphrase from `CRABCLAW_MEMORY_ENCRYPTION_KEY`, or `None` when it is unset or empty.
+    pub fn passphrase_from_env() -> Option<String> {
+        std::env::var(ENCRYPTION_KEY_ENV)
+            .ok()
+            .filter(|v| !v.is_empty())
+    }
+
+    /// Encrypt `plaintext`, returning `(nonce, hex(ciphertext ‖ tag))`.
+    pub fn encrypt(&self, plaintext: &str) -> Result<(Vec<u8>, String)> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|e| anyhow::anyhow!("Memory encryption failed: {e}"))?;
+        Ok((nonce.to_vec(), hex::encode(ciphertext)))
+    }
+
+    /// Decrypt content produced by [`MemoryCipher::encrypt`].
+    pub fn decrypt(&self, nonce: &[u8], ciphertext_hex: &str) -> Result<String> {
+        if nonce.len() != NONCE_LEN {
+            anyhow::bail!("Invalid memory nonce length {}", nonce.len());
+        }
+        let ciphertext = hex::decode(ciphertext_hex)
+            .map_err(|e| anyhow::anyhow!("Memory ciphertext is not hex: {e}"))?;
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Decryption failed — wrong key or tampered data"))?;
+        String::from_utf8(plaintext)
+            .map_err(|e| anyhow::anyhow!("Decrypted memory is not valid UTF-8: {e}"))
+    }
+}
+
+/// Fresh random salt for a new database.
+pub fn generate_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+fn derive_key(passphrase: &[u8], salt: &[u8]) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase, salt, KDF_ROUNDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() {
+        let cipher = MemoryCipher::from_passphrase("correct horse", &generate_salt());
+        let (nonce, ciphertext) = cipher.encrypt("User prefers Rust").unwrap();
+
+        assert_eq!(nonce.len(), NONCE_LEN);
+        assert!(!ciphertext.contains("Rust"));
+        assert_eq!(
+            cipher.decrypt(&nonce, &ciphertext).unwrap(),
+            "User prefers Rust"
+        );
+    }
+
+    #[test]
+    fn wrong_key_fails_to_decrypt() {
+        let salt = generate_salt();
+        let (nonce, ciphertext) = MemoryCipher::from_passphrase("correct horse", &salt)
+            .encrypt("secret")
+            .unwrap();
+
+        let err = MemoryCipher::from_passphrase("battery staple", &salt)
+            .decrypt(&nonce, &ciphertext)
+            .unwrap_err();
+        assert!(err.to_string().contains("wrong key"));
+    }
+
+    #[test]
+    fn same_passphrase_with_another_salt_fails_to_decrypt() {
+        let (nonce, ciphertext) = MemoryCipher::from_passphrase("correct horse", &generate_salt())
+            .encrypt("secret")
+            .unwrap();
+
+        assert!(
+            MemoryCipher::from_passphrase("correct horse", &generate_salt())
+                .decrypt(&nonce, &ciphertext)
+                .is_err()
+        );
+    }
+}
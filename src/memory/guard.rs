@@ -0,0 +1,287 @@
+use super::traits::{Memory, MemoryCategory, MemoryEntry};
+use crate::config::MemoryConfig;
+use async_trait::async_trait;
+
+/// Appended to content that was cut down to `max_content_bytes`, so a stored
+/// entry records that it is incomplete.
+pub const TRUNCATION_MARKER: &str = "\n… [truncated]";
+
+/// What `store` does with content larger than `max_content_bytes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OversizeMode {
+    /// Fail the store with an error
+    Reject,
+    /// Keep the leading bytes and append `TRUNCATION_MARKER`
+    Truncate,
+}
+
+impl OversizeMode {
+    /// Parse the config value; anything but "truncate" rejects.
+    pub fn from_config(value: &str) -> Self {
+        if value.eq_ignore_ascii_case("truncate") {
+            Self::Truncate
+        } else {
+            Self::Reject
+        }
+    }
+}
+
+/// Whether `content` was truncated on store
+pub fn is_truncated(content: &str) -> bool {
+    content.ends_with(TRUNCATION_MARKER)
+}
+
+/// Memory wrapper that bounds how much data a single entry may hold and how
+/// much a single `recall` may return, whatever the backend.
+pub struct SizeGuardedMemory {
+    inner: Box<dyn Memory>,
+    max_content_bytes: usize,
+    oversize_mode: OversizeMode,
+    max_recall_bytes: usize,
+}
+
+impl SizeGuardedMemory {
+    pub fn new(
+        inner: Box<dyn Memory>,
+        max_content_bytes: usize,
+        oversize_mode: OversizeMode,
+        max_recall_bytes: usize,
+    ) -> Self {
+        Self {
+            inner,
+            max_content_bytes,
+            oversize_mode,
+            max_recall_bytes,
+        }
+    }
+
+    pub fn from_config(inner: Box<dyn Memory>, config: &MemoryConfig) -> Self {
+        Self::new(
+            inner,
+            config.max_content_bytes,
+            OversizeMode::from_config(&config.oversize_mode),
+            config.max_recall_bytes,
+        )
+    }
+
+    /// Content as it should be stored, or an error when it is rejected.
+    fn bounded_content<'c>(
+        &self,
+        key: &str,
+        content: &'c str,
+    ) -> anyhow::Result<std::borrow::Cow<'c, str>> {
+        if content.len() <= self.max_content_bytes {
+            return Ok(content.into());
+        }
+        match self.oversize_mode {
+            OversizeMode::Reject => anyhow::bail!(
+                "Memory content for '{key}' is {} bytes, over the {} byte limit",
+                content.len(),
+                self.max_content_bytes
+            ),
+            OversizeMode::Truncate => {
+                tracing::warn!(
+                    key,
+                    bytes = content.len(),
+                    limit = self.max_content_bytes,
+                    "Truncating oversized memory content"
+                );
+                Ok(truncate(content, self.max_content_bytes).into())
+            }
+        }
+    }
+
+    /// Keep results in rank order until the recall byte budget is spent. A
+    /// top result larger than the whole budget is truncated to fit.
+    fn cap_recall(&self, mut entries: Vec<MemoryEntry>) -> Vec<MemoryEntry> {
+        let mut total = 0usize;
+        let within_budget = entries
+            .iter()
+            .take_while(|entry| {
+                total += entry.content.len();
+                total <= self.max_recall_bytes
+            })
+            .count();
+        if within_budget == 0 {
+            entries.truncate(1);
+            if let Some(first) = entries.first_mut() {
+                first.content = truncate(&first.content, self.max_recall_bytes);
+            }
+        } else {
+            entries.truncate(within_budget);
+        }
+        entries
+    }
+}
+
+/// `content` cut to at most `max_bytes` including `TRUNCATION_MARKER`; a
+/// limit too small for the marker keeps only the leading bytes.
+fn truncate(content: &str, max_bytes: usize) -> String {
+    let Some(keep) = max_bytes.checked_sub(TRUNCATION_MARKER.len()) else {
+        return content[..content.floor_char_boundary(max_bytes)].to_string();
+    };
+    let mut truncated = content[..content.floor_char_boundary(keep)].to_string();
+    truncated.push_str(TRUNCATION_MARKER);
+    truncated
+}
+
+#[async_trait]
+impl Memory for SizeGuardedMemory {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn store(
+        &self,
+        key: &str,
+        content: &str,
+        category: MemoryCategory,
+    ) -> anyhow::Result<()> {
+        let content = self.bounded_content(key, content)?;
+        self.inner.store(key, &content, category).await
+    }
+
+    async fn store_tagged(
+        &self,
+        key: &str,
+        content: &str,
+        category: MemoryCategory,
+        tags: &[String],
+    ) -> anyhow::Result<()> {
+        let content = self.bounded_content(key, content)?;
+        self.inner.store_tagged(key, &content, category, tags).await
+    }
+
+    async fn recall(&self, query: &str, limit: usize) -> anyhow::Result<Vec<MemoryEntry>> {
+        let entries = self.inner.recall(query, limit).await?;
+        Ok(self.cap_recall(entries))
+    }
+
+    async fn recall_with_tags(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        let entries = self.inner.recall_with_tags(query, tags, limit).await?;
+        Ok(self.cap_recall(entries))
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<MemoryEntry>> {
+        self.inner.get(key).await
+    }
+
+    async fn list(&self, category: Option<&MemoryCategory>) -> anyhow::Result<Vec<MemoryEntry>> {
+        self.inner.list(category).await
+    }
+
+    async fn forget(&self, key: &str) -> anyhow::Result<bool> {
+        self.inner.forget(key).await
+    }
+
+    async fn count(&self) -> anyhow::Result<usize> {
+        self.inner.count().await
+    }
+
+    async fn prune_expired(&self) -> anyhow::Result<usize> {
+        self.inner.prune_expired().await
+    }
+
+    async fn health_check(&self) -> bool {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MarkdownMemory;
+    use tempfile::TempDir;
+
+    fn guarded(tmp: &TempDir, mode: OversizeMode, max_recall_bytes: usize) -> SizeGuardedMemory {
+        SizeGuardedMemory::new(
+            Box::new(MarkdownMemory::new(tmp.path())),
+            64,
+            mode,
+            max_recall_bytes,
+        )
+    }
+
+    #[tokio::test]
+    async fn oversized_content_is_rejected() {
+        let tmp = TempDir::new().unwrap();
+        let mem = guarded(&tmp, OversizeMode::Reject, 4096);
+
+        let err = mem
+            .store("blob", &"x".repeat(65), MemoryCategory::Core)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("over the 64 byte limit"));
+        assert!(mem.get("blob").await.unwrap().is_none());
+
+        mem.store("fits", &"x".repeat(64), MemoryCategory::Core)
+            .await
+            .unwrap();
+        assert!(mem.get("fits").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn oversized_content_is_truncated_and_flagged() {
+        let tmp = TempDir::new().unwrap();
+        let mem = guarded(&tmp, OversizeMode::Truncate, 4096);
+
+        mem.store("blob", &"é".repeat(100), MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        let entry = mem.get("blob").await.unwrap().unwrap();
+        assert!(entry.content.len() <= 64);
+        assert!(is_truncated(&entry.content));
+        assert!(entry.content.starts_with('é'));
+    }
+
+    #[tokio::test]
+    async fn recall_stops_at_byte_budget() {
+        let tmp = TempDir::new().unwrap();
+        let mem = guarded(&tmp, OversizeMode::Reject, 100);
+        for i in 0..5 {
+            let content = format!("shared topic {}", "y".repeat(30));
+            mem.store(&format!("k{i}"), &content, MemoryCategory::Core)
+                .await
+                .unwrap();
+        }
+
+        let results = mem.recall("shared topic", 10).await.unwrap();
+        let total: usize = results.iter().map(|e| e.content.len()).sum();
+        assert_eq!(results.len(), 2);
+        assert!(total <= 100);
+    }
+    #[tokio::test]
+    async fn recall_truncates_a_top_result_larger_than_the_budget() {
+        let tmp = TempDir::new().unwrap();
+        let mem = guarded(&tmp, OversizeMode::Reject, 40);
+        mem.store(
+            "big",
+            &format!("shared topic {}", "y".repeat(50)),
+            MemoryCategory::Core,
+        )
+        .await
+        .unwrap();
+
+        let results = mem.recall("shared topic", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].content.len() <= 40);
+        assert!(results[0].content.starts_with("shared topic"));
+        assert!(is_truncated(&results[0].content));
+    }
+
+    #[test]
+    fn truncation_never_exceeds_the_limit() {
+        let content = "é".repeat(100);
+        for max_bytes in [0, 1, 5, TRUNCATION_MARKER.len(), 64] {
+            let truncated = truncate(&content, max_bytes);
+            assert!(truncated.len() <= max_bytes, "{max_bytes}: {truncated:?}");
+        }
+        assert!(is_truncated(&truncate(&content, 64)));
+    }
+}
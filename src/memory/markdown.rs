@@ -3,16 +3,93 @@ use async_trait::async_trait;
 use chrono::Local;
 use std::path::{Path, PathBuf};
 use tokio::fs;
+use uuid::Uuid;
+
+/// Longest encoded key used verbatim as a filename before falling back to a hash suffix.
+const MAX_ENCODED_KEY_LEN: usize = 120;
 
 /// Markdown-based memory — plain files as source of truth
 ///
 /// Layout:
-///   workspace/MEMORY.md          — curated long-term memory (core)
-///   workspace/memory/YYYY-MM-DD.md — daily logs (append-only)
+///   workspace/memory/entries/<key>.md — one file per memory, front matter
+///                                        carries key, category and timestamps
+///   workspace/MEMORY.md               — legacy curated long-term memory (read-only)
+///   workspace/memory/YYYY-MM-DD.md    — legacy daily logs (read-only)
 pub struct MarkdownMemory {
     workspace_dir: PathBuf,
 }
 
+/// Front matter + body of a single entry file.
+struct EntryFile {
+    id: String,
+    key: String,
+    category: MemoryCategory,
+    created_at: String,
+    updated_at: String,
+    content: String,
+}
+
+impl EntryFile {
+    fn render(&self) -> String {
+        // Values are JSON-quoted so keys/categories with newlines or colons round-trip.
+        let quote = |v: &str| serde_json::to_string(v).unwrap_or_else(|_| "\"\"".into());
+        format!(
+            "---\nid: {}\nkey: {}\ncategory: {}\ncreated_at: {}\nupdated_at: {}\n---\n{}\n",
+            quote(&self.id),
+            quote(&self.key),
+            quote(&self.category.to_string()),
+            quote(&self.created_at),
+            quote(&self.updated_at),
+            self.content,
+        )
+    }
+
+    fn parse(raw: &str) -> Option<Self> {
+        let rest = raw.strip_prefix("---\n")?;
+        let (header, body) = rest.split_once("\n---\n")?;
+
+        let mut entry = Self {
+            id: String::new(),
+            key: String::new(),
+            category: MemoryCategory::Core,
+            created_at: String::new(),
+            updated_at: String::new(),
+            content: body.strip_suffix('\n').unwrap_or(body).to_string(),
+        };
+        for line in header.lines() {
+            let Some((name, value)) = line.split_once(": ") else {
+                continue;
+            };
+            let value: String = serde_json::from_str(value).ok()?;
+            match name {
+                "id" => entry.id = value,
+                "key" => entry.key = value,
+                "category" => entry.category = MarkdownMemory::str_to_category(&value),
+                "created_at" => entry.created_at = value,
+                "updated_at" => entry.updated_at = value,
+                _ => {}
+            }
+        }
+
+        if entry.key.is_empty() {
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn into_memory_entry(self) -> MemoryEntry {
+        MemoryEntry {
+            id: self.id,
+            key: self.key,
+            content: self.content,
+            category: self.category,
+            timestamp: self.created_at,
+            session_id: None,
+            score: None,
+        }
+    }
+}
+
 impl MarkdownMemory {
     pub fn new(workspace_dir: &Path) -> Self {
         Self {
@@ -24,46 +101,91 @@ impl MarkdownMemory {
         self.workspace_dir.join("memory")
     }
 
+    fn entries_dir(&self) -> PathBuf {
+        self.memory_dir().join("entries")
+    }
+
     fn core_path(&self) -> PathBuf {
         self.workspace_dir.join("MEMORY.md")
     }
 
-    fn daily_path(&self) -> PathBuf {
-        let date = Local::now().format("%Y-%m-%d").to_string();
-        self.memory_dir().join(format!("{date}.md"))
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.entries_dir()
+            .join(format!("{}.md", Self::encode_key(key)))
     }
 
-    async fn ensure_dirs(&self) -> anyhow::Result<()> {
-        fs::create_dir_all(self.memory_dir()).await?;
-        Ok(())
+    fn str_to_category(s: &str) -> MemoryCategory {
+        match s {
+            "core" => MemoryCategory::Core,
+            "daily" => MemoryCategory::Daily,
+            "conversation" => MemoryCategory::Conversation,
+            other => MemoryCategory::Custom(other.to_string()),
+        }
     }
 
-    async fn append_to_file(&self, path: &Path, content: &str) -> anyhow::Result<()> {
-        self.ensure_dirs().await?;
+    /// Map a key to a filesystem-safe file stem.
+    ///
+    /// Lower-case unreserved ASCII passes through, everything else is
+    /// percent-encoded. Upper-case letters are encoded too, so keys differing
+    /// only in case get distinct files on case-insensitive filesystems.
+    /// Overlong stems are truncated and suffixed with a SHA-256 of the key so
+    /// distinct keys never share a file.
+    fn encode_key(key: &str) -> String {
+        use sha2::{Digest, Sha256};
+        use std::fmt::Write;
+
+        let mut encoded = String::with_capacity(key.len());
+        for (i, byte) in key.bytes().enumerate() {
+            let passthrough = byte.is_ascii_lowercase()
+                || byte.is_ascii_digit()
+                || matches!(byte, b'-' | b'_')
+                || (byte == b'.' && i > 0);
+            if passthrough {
+                encoded.push(char::from(byte));
+            } else {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
 
-        let existing = if path.exists() {
-            fs::read_to_string(path).await.unwrap_or_default()
-        } else {
-            String::new()
-        };
+        if encoded.len() <= MAX_ENCODED_KEY_LEN {
+            return encoded;
+        }
 
-        let updated = if existing.is_empty() {
-            let header = if path == self.core_path() {
-                "# Long-Term Memory\n\n"
-            } else {
-                let date = Local::now().format("%Y-%m-%d").to_string();
-                &format!("# Daily Log — {date}\n\n")
-            };
-            format!("{header}{content}\n")
-        } else {
-            format!("{existing}\n{content}\n")
-        };
+        let digest = hex::encode(Sha256::digest(key.as_bytes()));
+        let mut end = MAX_ENCODED_KEY_LEN - 17;
+        while !encoded.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}~{}", &encoded[..end], &digest[..16])
+    }
 
-        fs::write(path, updated).await?;
+    async fn read_entry_file(path: &Path) -> anyhow::Result<Option<EntryFile>> {
+        match fs::read_to_string(path).await {
+            Ok(raw) => Ok(EntryFile::parse(&raw)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write `contents` to `path` atomically: temp file in the same directory, then rename.
+    async fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
+        let dir = path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("entry path has no parent: {}", path.display()))?;
+        fs::create_dir_all(dir).await?;
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("entry");
+        let tmp_path = dir.join(format!(".{file_name}.{}.tmp", Uuid::new_v4()));
+
+        fs::write(&tmp_path, contents).await?;
+        if let Err(e) = fs::rename(&tmp_path, path).await {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(e.into());
+        }
         Ok(())
     }
 
-    fn parse_entries_from_file(
+    fn parse_legacy_entries(
         path: &Path,
         content: &str,
         category: &MemoryCategory,
@@ -96,29 +218,28 @@ impl MarkdownMemory {
             .collect()
     }
 
-    async fn read_all_entries(&self) -> anyhow::Result<Vec<MemoryEntry>> {
+    /// Entries from the pre-front-matter layout (`MEMORY.md` + daily logs).
+    async fn read_legacy_entries(&self) -> anyhow::Result<Vec<MemoryEntry>> {
         let mut entries = Vec::new();
 
-        // Read MEMORY.md (core)
         let core_path = self.core_path();
         if core_path.exists() {
             let content = fs::read_to_string(&core_path).await?;
-            entries.extend(Self::parse_entries_from_file(
+            entries.extend(Self::parse_legacy_entries(
                 &core_path,
                 &content,
                 &MemoryCategory::Core,
             ));
         }
 
-        // Read daily logs
         let mem_dir = self.memory_dir();
         if mem_dir.exists() {
             let mut dir = fs::read_dir(&mem_dir).await?;
             while let Some(entry) = dir.next_entry().await? {
                 let path = entry.path();
-                if path.extension().and_then(|e| e.to_str()) == Some("md") {
+                if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("md") {
                     let content = fs::read_to_string(&path).await?;
-                    entries.extend(Self::parse_entries_from_file(
+                    entries.extend(Self::parse_legacy_entries(
                         &path,
                         &content,
                         &MemoryCategory::Daily,
@@ -127,6 +248,29 @@ impl MarkdownMemory {
             }
         }
 
+        Ok(entries)
+    }
+
+    async fn read_all_entries(&self) -> anyhow::Result<Vec<MemoryEntry>> {
+        let mut entries = Vec::new();
+
+        let entries_dir = self.entries_dir();
+        if entries_dir.exists() {
+            let mut dir = fs::read_dir(&entries_dir).await?;
+            while let Some(entry) = dir.next_entry().await? {
+                let path = entry.path();
+                let is_entry = path.extension().and_then(|e| e.to_str()) == Some("md")
+                    && !entry.file_name().to_string_lossy().starts_with('.');
+                if !is_entry {
+                    continue;
+                }
+                if let Some(file) = Self::read_entry_file(&path).await? {
+                    entries.push(file.into_memory_entry());
+                }
+            }
+        }
+
+        entries.extend(self.read_legacy_entries().await?);
         entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
         Ok(entries)
     }
@@ -144,35 +288,56 @@ impl Memory for MarkdownMemory {
         content: &str,
         category: MemoryCategory,
     ) -> anyhow::Result<()> {
-        let entry = format!("- **{key}**: {content}");
-        let path = match category {
-            MemoryCategory::Core => self.core_path(),
-            _ => self.daily_path(),
+        let path = self.entry_path(key);
+        let now = Local::now().to_rfc3339();
+
+        // Upsert: keep identity and creation time of an existing entry.
+        let (id, created_at) = match Self::read_entry_file(&path).await? {
+            Some(existing) if existing.key == key => (existing.id, existing.created_at),
+            _ => (Uuid::new_v4().to_string(), now.clone()),
+        };
+
+        let file = EntryFile {
+            id,
+            key: key.to_string(),
+            category,
+            created_at,
+            updated_at: now,
+            content: content.to_string(),
         };
-        self.append_to_file(&path, &entry).await
+        Self::write_atomic(&path, &file.render()).await
     }
 
     async fn recall(&self, query: &str, limit: usize) -> anyhow::Result<Vec<MemoryEntry>> {
-        let all = self.read_all_entries().await?;
         let query_lower = query.to_lowercase();
         let keywords: Vec<&str> = query_lower.split_whitespace().collect();
+        if keywords.is_empty() {
+            return Ok(Vec::new());
+        }
 
+        let all = self.read_all_entries().await?;
         let mut scored: Vec<MemoryEntry> = all
             .into_iter()
             .filter_map(|mut entry| {
-                let content_lower = entry.content.to_lowercase();
-                let matched = keywords
-                    .iter()
-                    .filter(|kw| content_lower.contains(**kw))
-                    .count();
-                if matched > 0 {
-                    #[allow(clippy::cast_precision_loss)]
-                    let score = matched as f64 / keywords.len() as f64;
-                    entry.score = Some(score);
-                    Some(entry)
-                } else {
-                    None
+                let haystack = format!("{} {}", entry.key, entry.content).to_lowercase();
+                let mut matched = 0_usize;
+                let mut frequency = 0_usize;
+                for kw in &keywords {
+                    let hits = haystack.matches(kw).count();
+                    if hits > 0 {
+                        matched += 1;
+                        frequency += hits;
+                    }
                 }
+                if matched == 0 {
+                    return None;
+                }
+                // Keyword coverage dominates; raw term frequency breaks ties.
+                #[allow(clippy::cast_precision_loss)]
+                let score =
+                    matched as f64 / keywords.len() as f64 + (frequency as f64).ln_1p() / 100.0;
+                entry.score = Some(score);
+                Some(entry)
             })
             .collect();
 
@@ -186,10 +351,13 @@ impl Memory for MarkdownMemory {
     }
 
     async fn get(&self, key: &str) -> anyhow::Result<Option<MemoryEntry>> {
-        let all = self.read_all_entries().await?;
-        Ok(all
-            .into_iter()
-            .find(|e| e.key == key || e.content.contains(key)))
+        if let Some(file) = Self::read_entry_file(&self.entry_path(key)).await? {
+            if file.key == key {
+                return Ok(Some(file.into_memory_entry()));
+            }
+        }
+        let legacy = self.read_legacy_entries().await?;
+        Ok(legacy.into_iter().find(|e| e.key == key))
     }
 
     async fn list(&self, category: Option<&MemoryCategory>) -> anyhow::Result<Vec<MemoryEntry>> {
@@ -200,10 +368,17 @@ impl Memory for MarkdownMemory {
         }
     }
 
-    async fn forget(&self, _key: &str) -> anyhow::Result<bool> {
-        // Markdown memory is append-only by design (audit trail)
-        // Return false to indicate the entry wasn't removed
-        Ok(false)
+    async fn forget(&self, key: &str) -> anyhow::Result<bool> {
+        let path = self.entry_path(key);
+        match Self::read_entry_file(&path).await? {
+            Some(file) if file.key == key => {}
+            _ => return Ok(false),
+        }
+        match fs::remove_file(&path).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
     }
 
     async fn count(&self) -> anyhow::Result<usize> {
@@ -220,6 +395,7 @@ impl Memory for MarkdownMemory {
 mod tests {
     use super::*;
     use std::fs as sync_fs;
+    use std::sync::Arc;
     use tempfile::TempDir;
 
     fn temp_workspace() -> (TempDir, MarkdownMemory) {
@@ -241,12 +417,16 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn markdown_store_core() {
+    async fn markdown_store_writes_front_matter() {
         let (_tmp, mem) = temp_workspace();
         mem.store("pref", "User likes Rust", MemoryCategory::Core)
             .await
             .unwrap();
-        let content = sync_fs::read_to_string(mem.core_path()).unwrap();
+        let content = sync_fs::read_to_string(mem.entry_path("pref")).unwrap();
+        assert!(content.starts_with("---\n"));
+        assert!(content.contains("key: \"pref\""));
+        assert!(content.contains("category: \"core\""));
+        assert!(content.contains("created_at: "));
         assert!(content.contains("User likes Rust"));
     }
 
@@ -256,9 +436,114 @@ mod tests {
         mem.store("note", "Finished tests", MemoryCategory::Daily)
             .await
             .unwrap();
-        let path = mem.daily_path();
-        let content = sync_fs::read_to_string(path).unwrap();
-        assert!(content.contains("Finished tests"));
+        let entry = mem.get("note").await.unwrap().unwrap();
+        assert_eq!(entry.content, "Finished tests");
+        assert_eq!(entry.category, MemoryCategory::Daily);
+    }
+
+    #[tokio::test]
+    async fn markdown_store_upserts_by_key() {
+        let (_tmp, mem) = temp_workspace();
+        mem.store("pref", "likes Rust", MemoryCategory::Core)
+            .await
+            .unwrap();
+        let first = mem.get("pref").await.unwrap().unwrap();
+        mem.store("pref", "loves Rust", MemoryCategory::Core)
+            .await
+            .unwrap();
+        let second = mem.get("pref").await.unwrap().unwrap();
+
+        assert_eq!(mem.count().await.unwrap(), 1);
+        assert_eq!(second.content, "loves Rust");
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.timestamp, first.timestamp);
+    }
+
+    #[tokio::test]
+    async fn markdown_keys_with_special_chars_round_trip() {
+        let (_tmp, mem) = temp_workspace();
+        let keys = ["a/b", "../escape", "with space", "multi\nline", ".hidden"];
+        for key in keys {
+            mem.store(key, "content", MemoryCategory::Core)
+                .await
+                .unwrap();
+        }
+        for key in keys {
+            let entry = mem.get(key).await.unwrap().unwrap();
+            assert_eq!(entry.key, key);
+        }
+        assert_eq!(mem.count().await.unwrap(), keys.len());
+        assert!(sync_fs::read_dir(mem.entries_dir())
+            .unwrap()
+            .all(|e| e.unwrap().path().parent() == Some(mem.entries_dir().as_path())));
+    }
+
+    #[tokio::test]
+    async fn markdown_keys_differing_in_case_get_separate_files() {
+        let (_tmp, mem) = temp_workspace();
+        mem.store("Foo", "upper", MemoryCategory::Core)
+            .await
+            .unwrap();
+        mem.store("foo", "lower", MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        assert_eq!(mem.get("Foo").await.unwrap().unwrap().content, "upper");
+        assert_eq!(mem.get("foo").await.unwrap().unwrap().content, "lower");
+        assert_eq!(mem.count().await.unwrap(), 2);
+        // No two file names may collide once case is folded.
+        let mut names: Vec<String> = sync_fs::read_dir(mem.entries_dir())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_lowercase())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn markdown_long_keys_do_not_collide() {
+        let (_tmp, mem) = temp_workspace();
+        let a = format!("{}a", "k".repeat(300));
+        let b = format!("{}b", "k".repeat(300));
+        mem.store(&a, "first", MemoryCategory::Core).await.unwrap();
+        mem.store(&b, "second", MemoryCategory::Core).await.unwrap();
+        assert_eq!(mem.get(&a).await.unwrap().unwrap().content, "first");
+        assert_eq!(mem.get(&b).await.unwrap().unwrap().content, "second");
+    }
+
+    #[tokio::test]
+    async fn markdown_concurrent_stores_do_not_corrupt() {
+        let tmp = TempDir::new().unwrap();
+        let mem = Arc::new(MarkdownMemory::new(tmp.path()));
+
+        let mut handles = Vec::new();
+        for i in 0..32 {
+            let mem = Arc::clone(&mem);
+            handles.push(tokio::spawn(async move {
+                mem.store("shared", &format!("value {i}"), MemoryCategory::Core)
+                    .await
+                    .unwrap();
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let entry = mem.get("shared").await.unwrap().unwrap();
+        assert!(entry.content.starts_with("value "));
+        assert_eq!(mem.count().await.unwrap(), 1);
+        let leftovers = sync_fs::read_dir(mem.entries_dir())
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(".tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
     }
 
     #[tokio::test]
@@ -275,12 +560,53 @@ mod tests {
             .unwrap();
 
         let results = mem.recall("Rust", 10).await.unwrap();
-        assert!(results.len() >= 2);
+        assert_eq!(results.len(), 2);
         assert!(results
             .iter()
             .all(|r| r.content.to_lowercase().contains("rust")));
     }
 
+    #[tokio::test]
+    async fn markdown_recall_multi_tops_up_a_low_ranking_category() {
+        let (_tmp, mem) = temp_workspace();
+        for i in 0..10 {
+            mem.store(&format!("fact_{i}"), "rust rust rust", MemoryCategory::Core)
+                .await
+                .unwrap();
+        }
+        mem.store("log", "rust once", MemoryCategory::Daily)
+            .await
+            .unwrap();
+
+        // Two results wanted, so the first search stops at 8 candidates, all
+        // of them core facts outranking the daily log.
+        let limits = [(MemoryCategory::Core, 1), (MemoryCategory::Daily, 1)];
+        let results = mem.recall_multi("rust", &limits).await.unwrap();
+
+        assert_eq!(results[&MemoryCategory::Core].len(), 1);
+        let daily = &results[&MemoryCategory::Daily];
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].key, "log");
+    }
+
+    #[tokio::test]
+    async fn markdown_recall_ranks_by_term_frequency() {
+        let (_tmp, mem) = temp_workspace();
+        mem.store("once", "rust once", MemoryCategory::Core)
+            .await
+            .unwrap();
+        mem.store("many", "rust rust rust", MemoryCategory::Core)
+            .await
+            .unwrap();
+        mem.store("both", "rust and tokio", MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        let results = mem.recall("rust tokio", 10).await.unwrap();
+        let keys: Vec<&str> = results.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(keys, ["both", "many", "once"]);
+    }
+
     #[tokio::test]
     async fn markdown_recall_no_match() {
         let (_tmp, mem) = temp_workspace();
@@ -298,8 +624,7 @@ mod tests {
         mem.store("b", "second", MemoryCategory::Core)
             .await
             .unwrap();
-        let count = mem.count().await.unwrap();
-        assert!(count >= 2);
+        assert_eq!(mem.count().await.unwrap(), 2);
     }
 
     #[tokio::test]
@@ -311,22 +636,52 @@ mod tests {
         mem.store("b", "daily note", MemoryCategory::Daily)
             .await
             .unwrap();
+        mem.store("c", "custom note", MemoryCategory::Custom("project".into()))
+            .await
+            .unwrap();
 
         let core = mem.list(Some(&MemoryCategory::Core)).await.unwrap();
+        assert_eq!(core.len(), 1);
         assert!(core.iter().all(|e| e.category == MemoryCategory::Core));
 
         let daily = mem.list(Some(&MemoryCategory::Daily)).await.unwrap();
+        assert_eq!(daily.len(), 1);
         assert!(daily.iter().all(|e| e.category == MemoryCategory::Daily));
+
+        let custom = mem
+            .list(Some(&MemoryCategory::Custom("project".into())))
+            .await
+            .unwrap();
+        assert_eq!(custom.len(), 1);
     }
 
     #[tokio::test]
-    async fn markdown_forget_is_noop() {
+    async fn markdown_forget_removes_entry() {
         let (_tmp, mem) = temp_workspace();
-        mem.store("a", "permanent", MemoryCategory::Core)
+        mem.store("a", "temporary", MemoryCategory::Core)
             .await
             .unwrap();
-        let removed = mem.forget("a").await.unwrap();
-        assert!(!removed, "Markdown memory is append-only");
+        assert!(mem.forget("a").await.unwrap());
+        assert!(mem.get("a").await.unwrap().is_none());
+        assert_eq!(mem.count().await.unwrap(), 0);
+        assert!(!mem.forget("a").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn markdown_reads_legacy_layout() {
+        let (tmp, mem) = temp_workspace();
+        sync_fs::write(
+            tmp.path().join("MEMORY.md"),
+            "# Long-Term Memory\n\n- **pref**: User likes Rust\n",
+        )
+        .unwrap();
+
+        let results = mem.recall("rust", 10).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].category, MemoryCategory::Core);
+        assert!(mem.get("MEMORY:0").await.unwrap().is_some());
+        // Legacy files are read-only.
+        assert!(!mem.forget("MEMORY:0").await.unwrap());
     }
 
     #[tokio::test]
@@ -1,12 +1,19 @@
 pub mod chunker;
 pub mod embeddings;
+pub mod encryption;
+pub mod guard;
 pub mod hygiene;
 pub mod markdown;
+pub mod pool;
 pub mod sqlite;
 pub mod traits;
 pub mod vector;
 
+#[allow(unused_imports)]
+pub use guard::SizeGuardedMemory;
 pub use markdown::MarkdownMemory;
+#[allow(unused_imports)]
+pub use sqlite::CompactionReport;
 pub use sqlite::SqliteMemory;
 pub use traits::Memory;
 #[allow(unused_imports)]
@@ -27,7 +34,7 @@ pub fn create_memory(
         tracing::warn!("memory hygiene skipped: {e}");
     }
 
-    match config.backend.as_str() {
+    let backend: Box<dyn Memory> = match config.backend.as_str() {
         "sqlite" => {
             let embedder: Arc<dyn embeddings::EmbeddingProvider> =
                 Arc::from(embeddings::create_embedding_provider(
@@ -44,15 +51,17 @@ pub fn create_memory(
                 config.vector_weight as f32,
                 config.keyword_weight as f32,
                 config.embedding_cache_size,
-            )?;
-            Ok(Box::new(mem))
+            )?
+            .with_conversation_retention_days(config.conversation_retention_days);
+            Box::new(mem)
         }
-        "markdown" | "none" => Ok(Box::new(MarkdownMemory::new(workspace_dir))),
+        "markdown" | "none" => Box::new(MarkdownMemory::new(workspace_dir)),
         other => {
             tracing::warn!("Unknown memory backend '{other}', falling back to markdown");
-            Ok(Box::new(MarkdownMemory::new(workspace_dir)))
+            Box::new(MarkdownMemory::new(workspace_dir))
         }
-    }
+    };
+    Ok(Box::new(SizeGuardedMemory::from_config(backend, config)))
 }
 
 #[cfg(test)]
@@ -0,0 +1,160 @@
+use rusqlite::Connection;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// How long a connection waits on a locked database before failing with
+/// `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fixed-size pool of connections to one `SQLite` database in WAL mode, so
+/// readers proceed while a writer holds the write lock.
+///
+/// `lock` blocks until a connection is free, like the single `Mutex<Connection>`
+/// it replaces.
+pub struct ConnectionPool {
+    idle: Mutex<Vec<Connection>>,
+    available: Condvar,
+    size: usize,
+}
+
+impl ConnectionPool {
+    /// Open `size` connections (at least one) to `path`. `init` runs once, on
+    /// the first connection, before the others are opened.
+    pub fn open(
+        path: &Path,
+        size: usize,
+        init: impl FnOnce(&Connection) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Self> {
+        let size = size.max(1);
+        let first = Self::open_connection(path)?;
+        init(&first)?;
+
+        let mut idle = Vec::with_capacity(size);
+        idle.push(first);
+        for _ in 1..size {
+            idle.push(Self::open_connection(path)?);
+        }
+
+        Ok(Self {
+            idle: Mutex::new(idle),
+            available: Condvar::new(),
+            size,
+        })
+    }
+
+    fn open_connection(path: &Path) -> anyhow::Result<Connection> {
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // `journal_mode` reports the resulting mode as a row.
+        conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+            row.get::<_, String>(0)
+        })?;
+        conn.execute_batch("PRAGMA synchronous = NORMAL;")?;
+        Ok(conn)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Take a connection, waiting for one to be returned if all are in use.
+    pub fn lock(&self) -> anyhow::Result<PooledConnection<'_>> {
+        let mut idle = self
+            .idle
+            .lock()
+            .map_err(|_| anyhow::anyhow!("connection pool poisoned"))?;
+        loop {
+            if let Some(conn) = idle.pop() {
+                return Ok(PooledConnection {
+                    pool: self,
+                    conn: Some(conn),
+                });
+            }
+            idle = self
+                .available
+                .wait(idle)
+                .map_err(|_| anyhow::anyhow!("connection pool poisoned"))?;
+        }
+    }
+
+    fn release(&self, conn: Connection) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push(conn);
+            self.available.notify_one();
+        }
+    }
+}
+
+/// A connection borrowed from a [`ConnectionPool`]; returned on drop.
+pub struct PooledConnection<'a> {
+    pool: &'a ConnectionPool,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection present until drop")
+    }
+}
+
+impl DerefMut for PooledConnection<'_> {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection present until drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.release(conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn opens_in_wal_mode_and_runs_init_once() {
+        let tmp = TempDir::new().unwrap();
+        let mut inits = 0;
+        let pool = ConnectionPool::open(&tmp.path().join("t.db"), 3, |conn| {
+            inits += 1;
+            conn.execute_batch("CREATE TABLE t (v INTEGER);")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(inits, 1);
+        assert_eq!(pool.size(), 3);
+
+        let conn = pool.lock().unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn lock_waits_for_a_returned_connection() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("t.db");
+        let pool = Arc::new(ConnectionPool::open(&path, 1, |_| Ok(())).unwrap());
+
+        let held = pool.lock().unwrap();
+        let waiter = {
+            let pool = Arc::clone(&pool);
+            std::thread::spawn(move || pool.lock().map(|_| ()).is_ok())
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        drop(held);
+        assert!(waiter.join().unwrap());
+    }
+}
@@ -1,14 +1,30 @@
 use super::embeddings::EmbeddingProvider;
+use super::encryption::{self, MemoryCipher};
+use super::pool::ConnectionPool;
 use super::traits::{Memory, MemoryCategory, MemoryEntry};
 use super::vector;
+use crate::providers::{Provider, RequestContext, RequestPriority};
 use async_trait::async_trait;
 use chrono::Local;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use tokio::sync::Semaphore;
 use uuid::Uuid;
 
+const COMPACTION_PROMPT: &str = "Consolidate the following memory entries into one concise \
+summary. Keep durable facts, preferences and decisions; drop chit-chat and duplicates. \
+Reply with the summary only.";
+
+/// Outcome of [`SqliteMemory::compact`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Number of original rows replaced by the summary
+    pub rows_compacted: usize,
+    /// Key of the new summary entry (`None` when nothing was old enough)
+    pub summary_key: Option<String>,
+}
+
 /// SQLite-backed persistent memory — the brain
 ///
 /// Full-stack search engine:
@@ -17,8 +33,16 @@ use uuid::Uuid;
 /// - **Hybrid Merge**: weighted fusion of vector + keyword results
 /// - **Embedding Cache**: LRU-evicted cache to avoid redundant API calls
 /// - **Safe Reindex**: temp DB → seed → sync → atomic swap → rollback
+/// - **At-rest Encryption** (optional): content sealed with ChaCha20-Poly1305
+///   when `CRABCLAW_MEMORY_ENCRYPTION_KEY` is set
+/// - **Concurrency**: WAL journaling and a small connection pool
+///   (`CRABCLAW_MEMORY_POOL_SIZE`, default 4) so recalls run during writes
+///
+/// With encryption on, FTS5 and embeddings only ever see ciphertext, so both
+/// are disabled: recall decrypts every candidate row and matches keywords
+/// client-side, and no embeddings are computed. Keys stay in plaintext.
 pub struct SqliteMemory {
-    conn: Arc<Mutex<Connection>>,
+    conn: Arc<ConnectionPool>,
     db_path: PathBuf,
     embedder: Arc<dyn EmbeddingProvider>,
     vector_weight: f32,
@@ -27,6 +51,12 @@ pub struct SqliteMemory {
     max_embed_chunks_per_ingest: usize,
     embed_chunk_tokens: usize,
     embedding_workers: Arc<Semaphore>,
+    cipher: Option<Arc<MemoryCipher>>,
+    /// This database's salt for deriving the encryption key
+    kdf_salt: Vec<u8>,
+    conversation_retention_days: u32,
+    /// Age at which a row's recency boost halves; `None` ranks by relevance only.
+    recency_half_life_days: Option<f64>,
 }
 
 impl SqliteMemory {
@@ -53,13 +83,17 @@ impl SqliteMemory {
             std::fs::create_dir_all(parent)?;
         }
 
-        let conn = Arc::new(Mutex::new(Connection::open(&db_path)?));
-        {
-            let conn_guard = conn
-                .lock()
-                .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
-            Self::init_schema(&conn_guard)?;
-        }
+        let pool_size = std::env::var("CRABCLAW_MEMORY_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(4);
+        let conn = Arc::new(ConnectionPool::open(
+            &db_path,
+            pool_size,
+            Self::init_schema,
+        )?);
+        let kdf_salt = Self::load_kdf_salt(&*conn.lock()?)?;
 
         let max_embed_chunks_per_ingest = std::env::var("CRABCLAW_MEMORY_MAX_EMBED_CHUNKS")
             .ok()
@@ -76,6 +110,10 @@ impl SqliteMemory {
             .and_then(|v| v.parse::<usize>().ok())
             .filter(|v| *v > 0)
             .unwrap_or(2);
+        let recency_half_life_days = std::env::var("CRABCLAW_MEMORY_RECENCY_HALFLIFE_DAYS")
+            .ok()
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|v| v.is_finite() && *v > 0.0);
 
         Ok(Self {
             conn,
@@ -87,9 +125,123 @@ impl SqliteMemory {
             max_embed_chunks_per_ingest,
             embed_chunk_tokens,
             embedding_workers: Arc::new(Semaphore::new(worker_limit)),
+            cipher: MemoryCipher::passphrase_from_env()
+                .map(|passphrase| Arc::new(MemoryCipher::from_passphrase(&passphrase, &kdf_salt))),
+            kdf_salt,
+            conversation_retention_days: 0,
+            recency_half_life_days,
         })
     }
 
+    /// Boost recall scores of newer rows, overriding
+    /// `CRABCLAW_MEMORY_RECENCY_HALFLIFE_DAYS`. A score is multiplied by
+    /// `1 + 0.5^(age_days / half_life_days)`, so a brand-new row counts double
+    /// and very old rows keep their plain relevance. 0 disables the boost.
+    #[must_use]
+    pub fn with_recency_half_life_days(mut self, half_life_days: f64) -> Self {
+        self.recency_half_life_days = Some(half_life_days).filter(|v| v.is_finite() && *v > 0.0);
+        self
+    }
+
+    /// Apply the recency boost to `results` and re-rank them; a no-op when
+    /// recency weighting is off. Equal scores keep their relative order.
+    fn apply_recency(&self, results: &mut [MemoryEntry]) {
+        let Some(half_life) = self.recency_half_life_days else {
+            return;
+        };
+        let now = Local::now();
+        for entry in results.iter_mut() {
+            let Ok(created) = chrono::DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                continue;
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let age_days =
+                (now.signed_duration_since(created).num_seconds().max(0) as f64) / 86_400.0;
+            let boost = 1.0 + 0.5f64.powf(age_days / half_life);
+            entry.score = entry.score.map(|score| score * boost);
+        }
+        results.sort_by(|a, b| b.score.unwrap_or(0.0).total_cmp(&a.score.unwrap_or(0.0)));
+    }
+
+    /// Let `prune_expired` delete conversation rows not updated for `days`
+    /// days; 0 keeps them forever.
+    #[must_use]
+    pub fn with_conversation_retention_days(mut self, days: u32) -> Self {
+        self.conversation_retention_days = days;
+        self
+    }
+
+    /// Encrypt content at rest with a key derived from `passphrase`,
+    /// overriding `CRABCLAW_MEMORY_ENCRYPTION_KEY`.
+    #[must_use]
+    pub fn with_encryption_passphrase(mut self, passphrase: &str) -> Self {
+        self.cipher = Some(Arc::new(MemoryCipher::from_passphrase(
+            passphrase,
+            &self.kdf_salt,
+        )));
+        self
+    }
+
+    /// Whether content is encrypted at rest
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Content as written to the `content` column, plus its nonce when encrypted
+    fn seal(&self, content: &str) -> anyhow::Result<(String, Option<Vec<u8>>)> {
+        match &self.cipher {
+            Some(cipher) => {
+                let (nonce, ciphertext) = cipher.encrypt(content)?;
+                Ok((ciphertext, Some(nonce)))
+            }
+            None => Ok((content.to_string(), None)),
+        }
+    }
+
+    /// Plaintext for a stored row, or `None` (with a warning) when the row is
+    /// encrypted and cannot be decrypted with the current key.
+    fn open_content(&self, key: &str, content: String, nonce: Option<Vec<u8>>) -> Option<String> {
+        let Some(nonce) = nonce else {
+            return Some(content);
+        };
+        let Some(cipher) = &self.cipher else {
+            tracing::warn!(
+                key,
+                "Skipping encrypted memory: no encryption key configured"
+            );
+            return None;
+        };
+        match cipher.decrypt(&nonce, &content) {
+            Ok(plaintext) => Some(plaintext),
+            Err(e) => {
+                tracing::warn!(key, "Skipping memory that failed to decrypt: {e}");
+                None
+            }
+        }
+    }
+
+    /// Build an entry from a `id, key, content, category, created_at, nonce`
+    /// row, decrypting the content when needed.
+    fn entry_from_row(
+        &self,
+        row: &rusqlite::Row,
+        score: Option<f64>,
+    ) -> rusqlite::Result<Option<MemoryEntry>> {
+        let key: String = row.get(1)?;
+        let Some(content) = self.open_content(&key, row.get(2)?, row.get(5)?) else {
+            return Ok(None);
+        };
+        Ok(Some(MemoryEntry {
+            id: row.get(0)?,
+            key,
+            content,
+            category: Self::str_to_category(&row.get::<_, String>(3)?),
+            timestamp: row.get(4)?,
+            session_id: None,
+            score,
+        }))
+    }
+
     /// Initialize all tables: memories, FTS5, `embedding_cache`
     fn init_schema(conn: &Connection) -> anyhow::Result<()> {
         conn.execute_batch(
@@ -134,11 +286,59 @@ impl SqliteMemory {
                 created_at   TEXT NOT NULL,
                 accessed_at  TEXT NOT NULL
             );
-            CREATE INDEX IF NOT EXISTS idx_cache_accessed ON embedding_cache(accessed_at);",
+            CREATE INDEX IF NOT EXISTS idx_cache_accessed ON embedding_cache(accessed_at);
+
+            -- Free-form tags, one row per (memory, tag)
+            CREATE TABLE IF NOT EXISTS memory_tags (
+                memory_key  TEXT NOT NULL,
+                tag         TEXT NOT NULL,
+                PRIMARY KEY (memory_key, tag)
+            );
+            CREATE INDEX IF NOT EXISTS idx_memory_tags_tag ON memory_tags(tag, memory_key);
+            CREATE TRIGGER IF NOT EXISTS memories_tags_ad AFTER DELETE ON memories BEGIN
+                DELETE FROM memory_tags WHERE memory_key = old.key;
+            END;
+
+            -- Per-database settings, such as the encryption key salt
+            CREATE TABLE IF NOT EXISTS memory_meta (
+                key    TEXT PRIMARY KEY,
+                value  BLOB NOT NULL
+            );",
         )?;
+
+        // Per-row nonce for encrypted content (NULL for plaintext rows)
+        let has_nonce = conn
+            .prepare("SELECT 1 FROM pragma_table_info('memories') WHERE name = 'nonce'")?
+            .exists([])?;
+        if !has_nonce {
+            conn.execute_batch("ALTER TABLE memories ADD COLUMN nonce BLOB;")?;
+        }
         Ok(())
     }
 
+    /// The salt stored in `memory_meta`, generated on first open.
+    fn load_kdf_salt(conn: &Connection) -> anyhow::Result<Vec<u8>> {
+        let stored = |conn: &Connection| {
+            conn.query_row(
+                "SELECT value FROM memory_meta WHERE key = 'kdf_salt'",
+                [],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()
+        };
+        if let Some(salt) = stored(conn)? {
+            return Ok(salt);
+        }
+
+        let salt = encryption::generate_salt().to_vec();
+        // Another process opening the same database may have won the race.
+        conn.execute(
+            "INSERT OR IGNORE INTO memory_meta (key, value) VALUES ('kdf_salt', ?1)",
+            params![salt],
+        )?;
+        Ok(stored(conn)?.unwrap_or(salt))
+    }
+
     fn category_to_str(cat: &MemoryCategory) -> String {
         match cat {
             MemoryCategory::Core => "core".into(),
@@ -203,6 +403,7 @@ impl SqliteMemory {
                 out[i] += *val;
             }
         }
+        #[allow(clippy::cast_precision_loss)]
         let denom = vectors.len() as f32;
         for item in &mut out {
             *item /= denom;
@@ -210,6 +411,91 @@ impl SqliteMemory {
         Some(out)
     }
 
+    /// Compute (or reuse a cached) embedding for `content` and attach it to `key`.
+    ///
+    /// Runs off the write path; failures are swallowed so a missing embedding
+    /// only degrades recall to keyword search.
+    async fn embed_and_attach(
+        conn: &ConnectionPool,
+        embedder: &dyn EmbeddingProvider,
+        key: &str,
+        content: &str,
+        cache_max: usize,
+        max_chunks: usize,
+        chunk_tokens: usize,
+    ) {
+        let chunks = super::chunker::chunk_markdown(content, chunk_tokens);
+        let chunked: Vec<String> = if chunks.is_empty() {
+            vec![content.to_string()]
+        } else {
+            chunks
+                .into_iter()
+                .take(max_chunks)
+                .map(|c| c.content)
+                .collect()
+        };
+        let text_for_hash = chunked.join("\n\n---\n\n");
+        let hash = SqliteMemory::content_hash(&text_for_hash);
+        let now = Local::now().to_rfc3339();
+
+        let cached: Option<Vec<u8>> = {
+            let Ok(guard) = conn.lock() else {
+                return;
+            };
+            let Ok(mut stmt) =
+                guard.prepare("SELECT embedding FROM embedding_cache WHERE content_hash = ?1")
+            else {
+                return;
+            };
+            stmt.query_row(params![hash.clone()], |row| row.get(0)).ok()
+        };
+
+        let emb_bytes = if let Some(bytes) = cached {
+            let Ok(guard) = conn.lock() else {
+                return;
+            };
+            let _ = guard.execute(
+                "UPDATE embedding_cache SET accessed_at = ?1 WHERE content_hash = ?2",
+                params![now.clone(), hash.clone()],
+            );
+            bytes
+        } else {
+            let refs: Vec<&str> = chunked.iter().map(String::as_str).collect();
+            let Ok(embs) = embedder.embed(&refs).await else {
+                return;
+            };
+            let Some(emb) = SqliteMemory::average_embeddings(&embs) else {
+                return;
+            };
+            let bytes = vector::vec_to_bytes(&emb);
+            let Ok(guard) = conn.lock() else {
+                return;
+            };
+            let _ = guard.execute(
+                "INSERT OR REPLACE INTO embedding_cache (content_hash, embedding, created_at, accessed_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![hash.clone(), bytes.clone(), now.clone(), now.clone()],
+            );
+            let max = i64::try_from(cache_max).unwrap_or(i64::MAX);
+            let _ = guard.execute(
+                "DELETE FROM embedding_cache WHERE content_hash IN (
+                    SELECT content_hash FROM embedding_cache
+                    ORDER BY accessed_at ASC
+                    LIMIT MAX(0, (SELECT COUNT(*) FROM embedding_cache) - ?1)
+                )",
+                params![max],
+            );
+            bytes
+        };
+
+        if let Ok(guard) = conn.lock() {
+            let _ = guard.execute(
+                "UPDATE memories SET embedding = ?1, updated_at = ?2 WHERE key = ?3",
+                params![emb_bytes, now, key],
+            );
+        }
+    }
+
     /// Get embedding from cache, or compute + cache it
     async fn get_or_compute_embedding(&self, text: &str) -> anyhow::Result<Option<Vec<f32>>> {
         if self.embedder.dimensions() == 0 {
@@ -272,10 +558,193 @@ impl SqliteMemory {
         Ok(Some(embedding))
     }
 
+    /// Hybrid recall restricted to memories carrying every tag in `tags`
+    /// (deduplicated by the caller); an empty slice applies no filter.
+    #[allow(clippy::too_many_lines)]
+    async fn recall_filtered(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.cipher.is_some() {
+            return self.recall_decrypted(query, tags, limit);
+        }
+
+        // Compute query embedding (async, before lock)
+        let query_embedding = self.get_or_compute_embedding(query).await?;
+
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+
+        // FTS5 BM25 keyword search
+        let keyword_results = Self::fts5_search(&conn, query, tags, limit * 2).unwrap_or_default();
+
+        // Vector similarity search (if embeddings available)
+        let vector_results = if let Some(ref qe) = query_embedding {
+            Self::vector_search(&conn, qe, tags, limit * 2).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        // Hybrid merge
+        let merged = if vector_results.is_empty() {
+            // No embeddings — use keyword results only
+            keyword_results
+                .iter()
+                .map(|(id, score)| vector::ScoredResult {
+                    id: id.clone(),
+                    vector_score: None,
+                    keyword_score: Some(*score),
+                    final_score: *score,
+                })
+                .collect::<Vec<_>>()
+        } else {
+            vector::hybrid_merge(
+                &vector_results,
+                &keyword_results,
+                self.vector_weight,
+                self.keyword_weight,
+                limit,
+            )
+        };
+
+        // Fetch full entries for merged results
+        let mut results = Vec::new();
+        for scored in &merged {
+            let mut stmt = conn.prepare(
+                "SELECT id, key, content, category, created_at, nonce FROM memories WHERE id = ?1",
+            )?;
+            if let Ok(Some(entry)) = stmt.query_row(params![scored.id], |row| {
+                self.entry_from_row(row, Some(f64::from(scored.final_score)))
+            }) {
+                results.push(entry);
+            }
+        }
+
+        // If hybrid returned nothing, fall back to LIKE search
+        if results.is_empty() {
+            let keywords: Vec<String> =
+                query.split_whitespace().map(|w| format!("%{w}%")).collect();
+            if !keywords.is_empty() {
+                let conditions: Vec<String> = keywords
+                    .iter()
+                    .enumerate()
+                    .map(|(i, _)| {
+                        format!("(content LIKE ?{} OR key LIKE ?{})", i * 2 + 1, i * 2 + 2)
+                    })
+                    .collect();
+                let where_clause = conditions.join(" OR ");
+                let sql = format!(
+                    "SELECT id, key, content, category, created_at, nonce FROM memories
+                     WHERE ({where_clause}){}
+                     ORDER BY updated_at DESC
+                     LIMIT ?{}",
+                    Self::tag_filter_sql("key", keywords.len() * 2 + 2, tags.len()),
+                    keywords.len() * 2 + 1
+                );
+                let mut stmt = conn.prepare(&sql)?;
+                let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+                for kw in &keywords {
+                    param_values.push(Box::new(kw.clone()));
+                    param_values.push(Box::new(kw.clone()));
+                }
+                #[allow(clippy::cast_possible_wrap)]
+                param_values.push(Box::new(limit as i64));
+                for tag in tags {
+                    param_values.push(Box::new(tag.clone()));
+                }
+                let params_ref: Vec<&dyn rusqlite::types::ToSql> =
+                    param_values.iter().map(AsRef::as_ref).collect();
+                let rows = stmt.query_map(params_ref.as_slice(), |row| {
+                    self.entry_from_row(row, Some(1.0))
+                })?;
+                for row in rows {
+                    results.extend(row?);
+                }
+            }
+        }
+
+        self.apply_recency(&mut results);
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// Client-side keyword recall over encrypted rows: decrypt every candidate
+    /// and rank by the fraction of query words found in its key or content.
+    fn recall_decrypted(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+
+        let sql = format!(
+            "SELECT id, key, content, category, created_at, nonce FROM memories
+             WHERE 1 = 1{}
+             ORDER BY updated_at DESC",
+            Self::tag_filter_sql("key", 1, tags.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(tags), |row| {
+            self.entry_from_row(row, None)
+        })?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let Some(mut entry) = row? else { continue };
+            let key = entry.key.to_lowercase();
+            let content = entry.content.to_lowercase();
+            let hits = words
+                .iter()
+                .filter(|w| key.contains(w.as_str()) || content.contains(w.as_str()))
+                .count();
+            if hits > 0 {
+                #[allow(clippy::cast_precision_loss)]
+                let score = hits as f64 / words.len() as f64;
+                entry.score = Some(score);
+                results.push(entry);
+            }
+        }
+        // Stable sort keeps most recently updated first among equal scores
+        results.sort_by(|a, b| b.score.unwrap_or(0.0).total_cmp(&a.score.unwrap_or(0.0)));
+        self.apply_recency(&mut results);
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// `AND <key_column> IN (...)` clause keeping rows tagged with all
+    /// `tag_count` tags, bound to parameters `?first_param..`; empty when there
+    /// are no tags.
+    fn tag_filter_sql(key_column: &str, first_param: usize, tag_count: usize) -> String {
+        if tag_count == 0 {
+            return String::new();
+        }
+        let placeholders: Vec<String> = (first_param..first_param + tag_count)
+            .map(|i| format!("?{i}"))
+            .collect();
+        format!(
+            " AND {key_column} IN (SELECT memory_key FROM memory_tags WHERE tag IN ({})
+                          GROUP BY memory_key HAVING COUNT(*) = {tag_count})",
+            placeholders.join(", ")
+        )
+    }
+
     /// FTS5 BM25 keyword search
     fn fts5_search(
         conn: &Connection,
         query: &str,
+        tags: &[String],
         limit: usize,
     ) -> anyhow::Result<Vec<(String, f32)>> {
         // Escape FTS5 special chars and build query
@@ -289,18 +758,23 @@ impl SqliteMemory {
             return Ok(Vec::new());
         }
 
-        let sql = "SELECT m.id, bm25(memories_fts) as score
-                   FROM memories_fts f
-                   JOIN memories m ON m.rowid = f.rowid
-                   WHERE memories_fts MATCH ?1
-                   ORDER BY score
-                   LIMIT ?2";
+        let sql = format!(
+            "SELECT m.id, bm25(memories_fts) as score
+             FROM memories_fts f
+             JOIN memories m ON m.rowid = f.rowid
+             WHERE memories_fts MATCH ?1{}
+             ORDER BY score
+             LIMIT ?2",
+            Self::tag_filter_sql("m.key", 3, tags.len())
+        );
 
-        let mut stmt = conn.prepare(sql)?;
+        let mut stmt = conn.prepare(&sql)?;
         #[allow(clippy::cast_possible_wrap)]
         let limit_i64 = limit as i64;
+        let mut param_values: Vec<&dyn rusqlite::types::ToSql> = vec![&fts_query, &limit_i64];
+        param_values.extend(tags.iter().map(|t| t as &dyn rusqlite::types::ToSql));
 
-        let rows = stmt.query_map(params![fts_query, limit_i64], |row| {
+        let rows = stmt.query_map(param_values.as_slice(), |row| {
             let id: String = row.get(0)?;
             let score: f64 = row.get(1)?;
             // BM25 returns negative scores (lower = better), negate for ranking
@@ -319,12 +793,16 @@ impl SqliteMemory {
     fn vector_search(
         conn: &Connection,
         query_embedding: &[f32],
+        tags: &[String],
         limit: usize,
     ) -> anyhow::Result<Vec<(String, f32)>> {
-        let mut stmt =
-            conn.prepare("SELECT id, embedding FROM memories WHERE embedding IS NOT NULL")?;
+        let sql = format!(
+            "SELECT id, embedding FROM memories WHERE embedding IS NOT NULL{}",
+            Self::tag_filter_sql("key", 1, tags.len())
+        );
+        let mut stmt = conn.prepare(&sql)?;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(rusqlite::params_from_iter(tags), |row| {
             let id: String = row.get(0)?;
             let blob: Vec<u8> = row.get(1)?;
             Ok((id, blob))
@@ -358,8 +836,9 @@ impl SqliteMemory {
             conn.execute_batch("INSERT INTO memories_fts(memories_fts) VALUES('rebuild');")?;
         }
 
-        // Step 2: Re-embed all memories that lack embeddings
-        if self.embedder.dimensions() == 0 {
+        // Step 2: Re-embed all memories that lack embeddings (never for
+        // encrypted content, which must not be sent to the embedder)
+        if self.embedder.dimensions() == 0 || self.cipher.is_some() {
             return Ok(0);
         }
 
@@ -395,251 +874,217 @@ impl SqliteMemory {
 
         Ok(count)
     }
-}
 
-#[async_trait]
-impl Memory for SqliteMemory {
-    fn name(&self) -> &str {
-        "sqlite"
+    /// Summarize every `category` memory not updated within `older_than` into
+    /// a single `Core` entry, then delete the originals.
+    ///
+    /// The summary is written and the originals removed in one transaction; a
+    /// failed summarizer call leaves the store untouched.
+    pub async fn compact(
+        &self,
+        category: MemoryCategory,
+        older_than: std::time::Duration,
+        summarizer: &dyn Provider,
+        model: &str,
+    ) -> anyhow::Result<CompactionReport> {
+        let cutoff = (Local::now() - chrono::Duration::from_std(older_than)?).to_rfc3339();
+        let cat = Self::category_to_str(&category);
+
+        let entries: Vec<MemoryEntry> = {
+            let conn = self
+                .conn
+                .lock()
+                .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+            let mut stmt = conn.prepare(
+                "SELECT id, key, content, category, created_at, nonce FROM memories
+                 WHERE category = ?1 AND updated_at < ?2
+                 ORDER BY updated_at ASC",
+            )?;
+            let rows =
+                stmt.query_map(params![cat, cutoff], |row| self.entry_from_row(row, None))?;
+            let mut entries = Vec::new();
+            for row in rows {
+                entries.extend(row?);
+            }
+            entries
+        };
+
+        if entries.is_empty() {
+            return Ok(CompactionReport {
+                rows_compacted: 0,
+                summary_key: None,
+            });
+        }
+
+        let combined = entries
+            .iter()
+            .map(|e| format!("- [{}] {}: {}", e.timestamp, e.key, e.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary = RequestContext::current_or_new()
+            .with_priority(RequestPriority::Background)
+            .scope(summarizer.chat_with_system(Some(COMPACTION_PROMPT), &combined, model, 0.2))
+            .await?;
+        if summary.trim().is_empty() {
+            anyhow::bail!("Summarizer returned an empty compaction summary");
+        }
+
+        let now = Local::now().to_rfc3339();
+        let summary_key = format!("compacted_{cat}_{}", Local::now().format("%Y%m%dT%H%M%S"));
+        let (stored, nonce) = self.seal(summary.trim())?;
+
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+        let tx = conn.transaction()?;
+        for entry in &entries {
+            tx.execute("DELETE FROM memories WHERE id = ?1", params![entry.id])?;
+        }
+        tx.execute(
+            "INSERT INTO memories (id, key, content, category, embedding, created_at, updated_at, nonce)
+             VALUES (?1, ?2, ?3, 'core', NULL, ?4, ?4, ?5)
+             ON CONFLICT(key) DO UPDATE SET
+                content = excluded.content,
+                embedding = NULL,
+                updated_at = excluded.updated_at,
+                nonce = excluded.nonce",
+            params![Uuid::new_v4().to_string(), summary_key, stored, now, nonce],
+        )?;
+        tx.commit()?;
+
+        Ok(CompactionReport {
+            rows_compacted: entries.len(),
+            summary_key: Some(summary_key),
+        })
     }
 
-    async fn store(
+    /// Insert or update the row for `key`, clearing its embedding.
+    fn upsert_row(
         &self,
+        conn: &Connection,
         key: &str,
         content: &str,
-        category: MemoryCategory,
+        category: &MemoryCategory,
     ) -> anyhow::Result<()> {
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
         let now = Local::now().to_rfc3339();
-        let cat = Self::category_to_str(&category);
+        let cat = Self::category_to_str(category);
         let id = Uuid::new_v4().to_string();
+        let (stored, nonce) = self.seal(content)?;
 
         // Fast path: write immediately, embedding computed asynchronously.
         conn.execute(
-            "INSERT INTO memories (id, key, content, category, embedding, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6)
+            "INSERT INTO memories (id, key, content, category, embedding, created_at, updated_at, nonce)
+             VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?7)
              ON CONFLICT(key) DO UPDATE SET
                 content = excluded.content,
                 category = excluded.category,
                 embedding = NULL,
-                updated_at = excluded.updated_at",
-            params![id, key, content, cat, now, now],
+                updated_at = excluded.updated_at,
+                nonce = excluded.nonce",
+            params![id, key, stored, cat, now, now, nonce],
         )?;
-        drop(conn);
-
-        if self.embedder.dimensions() > 0 {
-            let key_owned = key.to_string();
-            let content_owned = content.to_string();
-            let conn = Arc::clone(&self.conn);
-            let embedder = Arc::clone(&self.embedder);
-            let permit_pool = Arc::clone(&self.embedding_workers);
-            let cache_max = self.cache_max;
-            let max_chunks = self.max_embed_chunks_per_ingest;
-            let chunk_tokens = self.embed_chunk_tokens;
-
-            tokio::spawn(async move {
-                let _permit = permit_pool.acquire_owned().await.ok();
-
-                let chunks = super::chunker::chunk_markdown(&content_owned, chunk_tokens);
-                let chunked: Vec<String> = if chunks.is_empty() {
-                    vec![content_owned.clone()]
-                } else {
-                    chunks
-                        .into_iter()
-                        .take(max_chunks)
-                        .map(|c| c.content)
-                        .collect()
-                };
-                let text_for_hash = chunked.join("\n\n---\n\n");
-                let hash = SqliteMemory::content_hash(&text_for_hash);
-                let now = Local::now().to_rfc3339();
-
-                let cached: Option<Vec<u8>> = {
-                    let guard = match conn.lock() {
-                        Ok(g) => g,
-                        Err(_) => return,
-                    };
-                    let mut stmt = match guard
-                        .prepare("SELECT embedding FROM embedding_cache WHERE content_hash = ?1")
-                    {
-                        Ok(s) => s,
-                        Err(_) => return,
-                    };
-                    stmt.query_row(params![hash.clone()], |row| row.get(0)).ok()
-                };
-
-                let emb_bytes = if let Some(bytes) = cached {
-                    let guard = match conn.lock() {
-                        Ok(g) => g,
-                        Err(_) => return,
-                    };
-                    let _ = guard.execute(
-                        "UPDATE embedding_cache SET accessed_at = ?1 WHERE content_hash = ?2",
-                        params![now.clone(), hash.clone()],
-                    );
-                    bytes
-                } else {
-                    let refs: Vec<&str> = chunked.iter().map(String::as_str).collect();
-                    let embs = match embedder.embed(&refs).await {
-                        Ok(v) => v,
-                        Err(_) => return,
-                    };
-                    let emb = match SqliteMemory::average_embeddings(&embs) {
-                        Some(v) => v,
-                        None => return,
-                    };
-                    let bytes = vector::vec_to_bytes(&emb);
-                    let guard = match conn.lock() {
-                        Ok(g) => g,
-                        Err(_) => return,
-                    };
-                    let _ = guard.execute(
-                        "INSERT OR REPLACE INTO embedding_cache (content_hash, embedding, created_at, accessed_at)
-                         VALUES (?1, ?2, ?3, ?4)",
-                        params![hash.clone(), bytes.clone(), now.clone(), now.clone()],
-                    );
-                    let max = cache_max as i64;
-                    let _ = guard.execute(
-                        "DELETE FROM embedding_cache WHERE content_hash IN (
-                            SELECT content_hash FROM embedding_cache
-                            ORDER BY accessed_at ASC
-                            LIMIT MAX(0, (SELECT COUNT(*) FROM embedding_cache) - ?1)
-                        )",
-                        params![max],
-                    );
-                    bytes
-                };
-
-                if let Ok(guard) = conn.lock() {
-                    let _ = guard.execute(
-                        "UPDATE memories SET embedding = ?1, updated_at = ?2 WHERE key = ?3",
-                        params![emb_bytes, now, key_owned],
-                    );
-                }
-            });
-        }
-
         Ok(())
     }
 
-    async fn recall(&self, query: &str, limit: usize) -> anyhow::Result<Vec<MemoryEntry>> {
-        if query.trim().is_empty() {
-            return Ok(Vec::new());
+    /// Compute and attach the embedding for `key` in the background.
+    fn spawn_embedding(&self, key: &str, content: &str) {
+        // Embeddings of plaintext would leak content, so skip them when encrypted
+        if self.embedder.dimensions() == 0 || self.cipher.is_some() {
+            return;
         }
-
-        // Compute query embedding (async, before lock)
-        let query_embedding = self.get_or_compute_embedding(query).await?;
-
-        let conn = self
-            .conn
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
-
-        // FTS5 BM25 keyword search
-        let keyword_results = Self::fts5_search(&conn, query, limit * 2).unwrap_or_default();
-
-        // Vector similarity search (if embeddings available)
-        let vector_results = if let Some(ref qe) = query_embedding {
-            Self::vector_search(&conn, qe, limit * 2).unwrap_or_default()
-        } else {
-            Vec::new()
-        };
-
-        // Hybrid merge
-        let merged = if vector_results.is_empty() {
-            // No embeddings — use keyword results only
-            keyword_results
-                .iter()
-                .map(|(id, score)| vector::ScoredResult {
-                    id: id.clone(),
-                    vector_score: None,
-                    keyword_score: Some(*score),
-                    final_score: *score,
-                })
-                .collect::<Vec<_>>()
-        } else {
-            vector::hybrid_merge(
-                &vector_results,
-                &keyword_results,
-                self.vector_weight,
-                self.keyword_weight,
-                limit,
+        let key_owned = key.to_string();
+        let content_owned = content.to_string();
+        let conn = Arc::clone(&self.conn);
+        let embedder = Arc::clone(&self.embedder);
+        let permit_pool = Arc::clone(&self.embedding_workers);
+        let cache_max = self.cache_max;
+        let max_chunks = self.max_embed_chunks_per_ingest;
+        let chunk_tokens = self.embed_chunk_tokens;
+
+        tokio::spawn(async move {
+            let _permit = permit_pool.acquire_owned().await.ok();
+            SqliteMemory::embed_and_attach(
+                &conn,
+                embedder.as_ref(),
+                &key_owned,
+                &content_owned,
+                cache_max,
+                max_chunks,
+                chunk_tokens,
             )
-        };
+            .await;
+        });
+    }
+}
 
-        // Fetch full entries for merged results
-        let mut results = Vec::new();
-        for scored in &merged {
-            let mut stmt = conn.prepare(
-                "SELECT id, key, content, category, created_at FROM memories WHERE id = ?1",
-            )?;
-            if let Ok(entry) = stmt.query_row(params![scored.id], |row| {
-                Ok(MemoryEntry {
-                    id: row.get(0)?,
-                    key: row.get(1)?,
-                    content: row.get(2)?,
-                    category: Self::str_to_category(&row.get::<_, String>(3)?),
-                    timestamp: row.get(4)?,
-                    session_id: None,
-                    score: Some(f64::from(scored.final_score)),
-                })
-            }) {
-                results.push(entry);
-            }
-        }
+#[async_trait]
+impl Memory for SqliteMemory {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
 
-        // If hybrid returned nothing, fall back to LIKE search
-        if results.is_empty() {
-            let keywords: Vec<String> =
-                query.split_whitespace().map(|w| format!("%{w}%")).collect();
-            if !keywords.is_empty() {
-                let conditions: Vec<String> = keywords
-                    .iter()
-                    .enumerate()
-                    .map(|(i, _)| {
-                        format!("(content LIKE ?{} OR key LIKE ?{})", i * 2 + 1, i * 2 + 2)
-                    })
-                    .collect();
-                let where_clause = conditions.join(" OR ");
-                let sql = format!(
-                    "SELECT id, key, content, category, created_at FROM memories
-                     WHERE {where_clause}
-                     ORDER BY updated_at DESC
-                     LIMIT ?{}",
-                    keywords.len() * 2 + 1
-                );
-                let mut stmt = conn.prepare(&sql)?;
-                let mut param_values: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-                for kw in &keywords {
-                    param_values.push(Box::new(kw.clone()));
-                    param_values.push(Box::new(kw.clone()));
-                }
-                #[allow(clippy::cast_possible_wrap)]
-                param_values.push(Box::new(limit as i64));
-                let params_ref: Vec<&dyn rusqlite::types::ToSql> =
-                    param_values.iter().map(AsRef::as_ref).collect();
-                let rows = stmt.query_map(params_ref.as_slice(), |row| {
-                    Ok(MemoryEntry {
-                        id: row.get(0)?,
-                        key: row.get(1)?,
-                        content: row.get(2)?,
-                        category: Self::str_to_category(&row.get::<_, String>(3)?),
-                        timestamp: row.get(4)?,
-                        session_id: None,
-                        score: Some(1.0),
-                    })
-                })?;
-                for row in rows {
-                    results.push(row?);
-                }
-            }
+    async fn store(
+        &self,
+        key: &str,
+        content: &str,
+        category: MemoryCategory,
+    ) -> anyhow::Result<()> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+        self.upsert_row(&conn, key, content, &category)?;
+        drop(conn);
+
+        self.spawn_embedding(key, content);
+        Ok(())
+    }
+
+    async fn recall(&self, query: &str, limit: usize) -> anyhow::Result<Vec<MemoryEntry>> {
+        self.recall_filtered(query, &[], limit).await
+    }
+
+    async fn store_tagged(
+        &self,
+        key: &str,
+        content: &str,
+        category: MemoryCategory,
+        tags: &[String],
+    ) -> anyhow::Result<()> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+        let tx = conn.transaction()?;
+        self.upsert_row(&tx, key, content, &category)?;
+        tx.execute(
+            "DELETE FROM memory_tags WHERE memory_key = ?1",
+            params![key],
+        )?;
+        for tag in tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO memory_tags (memory_key, tag) VALUES (?1, ?2)",
+                params![key, tag],
+            )?;
         }
+        tx.commit()?;
+        drop(conn);
 
-        results.truncate(limit);
-        Ok(results)
+        self.spawn_embedding(key, content);
+        Ok(())
+    }
+
+    async fn recall_with_tags(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        let mut tags = tags.to_vec();
+        tags.sort();
+        tags.dedup();
+        self.recall_filtered(query, &tags, limit).await
     }
 
     async fn get(&self, key: &str) -> anyhow::Result<Option<MemoryEntry>> {
@@ -649,24 +1094,14 @@ impl Memory for SqliteMemory {
             .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
 
         let mut stmt = conn.prepare(
-            "SELECT id, key, content, category, created_at FROM memories WHERE key = ?1",
+            "SELECT id, key, content, category, created_at, nonce FROM memories WHERE key = ?1",
         )?;
 
-        let mut rows = stmt.query_map(params![key], |row| {
-            Ok(MemoryEntry {
-                id: row.get(0)?,
-                key: row.get(1)?,
-                content: row.get(2)?,
-                category: Self::str_to_category(&row.get::<_, String>(3)?),
-                timestamp: row.get(4)?,
-                session_id: None,
-                score: None,
-            })
-        })?;
+        let mut rows = stmt.query_map(params![key], |row| self.entry_from_row(row, None))?;
 
         match rows.next() {
-            Some(Ok(entry)) => Ok(Some(entry)),
-            _ => Ok(None),
+            Some(row) => Ok(row?),
+            None => Ok(None),
         }
     }
 
@@ -678,36 +1113,26 @@ impl Memory for SqliteMemory {
 
         let mut results = Vec::new();
 
-        let row_mapper = |row: &rusqlite::Row| -> rusqlite::Result<MemoryEntry> {
-            Ok(MemoryEntry {
-                id: row.get(0)?,
-                key: row.get(1)?,
-                content: row.get(2)?,
-                category: Self::str_to_category(&row.get::<_, String>(3)?),
-                timestamp: row.get(4)?,
-                session_id: None,
-                score: None,
-            })
-        };
+        let row_mapper = |row: &rusqlite::Row| self.entry_from_row(row, None);
 
         if let Some(cat) = category {
             let cat_str = Self::category_to_str(cat);
             let mut stmt = conn.prepare(
-                "SELECT id, key, content, category, created_at FROM memories
+                "SELECT id, key, content, category, created_at, nonce FROM memories
                  WHERE category = ?1 ORDER BY updated_at DESC",
             )?;
             let rows = stmt.query_map(params![cat_str], row_mapper)?;
             for row in rows {
-                results.push(row?);
+                results.extend(row?);
             }
         } else {
             let mut stmt = conn.prepare(
-                "SELECT id, key, content, category, created_at FROM memories
+                "SELECT id, key, content, category, created_at, nonce FROM memories
                  ORDER BY updated_at DESC",
             )?;
             let rows = stmt.query_map([], row_mapper)?;
             for row in rows {
-                results.push(row?);
+                results.extend(row?);
             }
         }
 
@@ -723,6 +1148,25 @@ impl Memory for SqliteMemory {
         Ok(affected > 0)
     }
 
+    async fn prune_expired(&self) -> anyhow::Result<usize> {
+        if self.conversation_retention_days == 0 {
+            return Ok(0);
+        }
+        let cutoff = (Local::now()
+            - chrono::Duration::days(i64::from(self.conversation_retention_days)))
+        .to_rfc3339();
+
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {e}"))?;
+        let pruned = conn.execute(
+            "DELETE FROM memories WHERE category = 'conversation' AND updated_at < ?1",
+            params![cutoff],
+        )?;
+        Ok(pruned)
+    }
+
     async fn count(&self) -> anyhow::Result<usize> {
         let conn = self
             .conn
@@ -744,6 +1188,7 @@ impl Memory for SqliteMemory {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::Mutex;
     use tempfile::TempDir;
 
     fn temp_sqlite() -> (TempDir, SqliteMemory) {
@@ -779,6 +1224,32 @@ mod tests {
         assert_eq!(entry.category, MemoryCategory::Core);
     }
 
+    #[tokio::test]
+    async fn get_is_exact_key_lookup_without_ranking() {
+        let (_tmp, mem) = temp_sqlite();
+        mem.store("project_notes", "Rust rust rust", MemoryCategory::Daily)
+            .await
+            .unwrap();
+        mem.store(
+            "favourite",
+            "project_notes",
+            MemoryCategory::Custom("misc".into()),
+        )
+        .await
+        .unwrap();
+
+        let entry = mem.get("project_notes").await.unwrap().unwrap();
+        assert_eq!(entry.content, "Rust rust rust");
+        assert_eq!(entry.category, MemoryCategory::Daily);
+        assert!(entry.score.is_none());
+
+        let custom = mem.get("favourite").await.unwrap().unwrap();
+        assert_eq!(custom.category, MemoryCategory::Custom("misc".into()));
+
+        assert!(mem.get("project").await.unwrap().is_none());
+        assert!(mem.get("Rust").await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn sqlite_store_upsert() {
         let (_tmp, mem) = temp_sqlite();
@@ -1189,6 +1660,49 @@ mod tests {
         assert!(results.len() <= 5);
     }
 
+    #[tokio::test]
+    async fn recall_multi_honors_each_category_limit() {
+        let (_tmp, mem) = temp_sqlite();
+        for i in 0..5 {
+            mem.store(&format!("fact_{i}"), "rust fact", MemoryCategory::Core)
+                .await
+                .unwrap();
+        }
+        for i in 0..7 {
+            mem.store(
+                &format!("chat_{i}"),
+                "we talked about rust",
+                MemoryCategory::Conversation,
+            )
+            .await
+            .unwrap();
+        }
+        mem.store("log", "rust daily log", MemoryCategory::Daily)
+            .await
+            .unwrap();
+
+        let results = mem
+            .recall_multi(
+                "rust",
+                &[
+                    (MemoryCategory::Core, 3),
+                    (MemoryCategory::Conversation, 5),
+                    (MemoryCategory::Custom("notes".into()), 2),
+                ],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[&MemoryCategory::Core].len(), 3);
+        assert_eq!(results[&MemoryCategory::Conversation].len(), 5);
+        assert!(results[&MemoryCategory::Custom("notes".into())].is_empty());
+        assert!(!results.contains_key(&MemoryCategory::Daily));
+        assert!(results[&MemoryCategory::Core]
+            .iter()
+            .all(|e| e.category == MemoryCategory::Core));
+    }
+
     // ── Score presence test ──────────────────────────────────────
 
     #[tokio::test]
@@ -1425,6 +1939,128 @@ mod tests {
         assert_eq!(mem.count().await.unwrap(), 1);
     }
 
+    // ── Tags ─────────────────────────────────────────────────────
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(ToString::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn recall_with_tags_requires_every_tag() {
+        let (_tmp, mem) = temp_sqlite();
+        mem.store_tagged(
+            "a",
+            "deploy notes alpha",
+            MemoryCategory::Core,
+            &tags(&["project:crabclaw", "source:slack"]),
+        )
+        .await
+        .unwrap();
+        mem.store_tagged(
+            "b",
+            "deploy notes beta",
+            MemoryCategory::Core,
+            &tags(&["project:crabclaw"]),
+        )
+        .await
+        .unwrap();
+        mem.store_tagged(
+            "c",
+            "deploy notes gamma",
+            MemoryCategory::Core,
+            &tags(&["source:slack"]),
+        )
+        .await
+        .unwrap();
+        mem.store("d", "deploy notes delta", MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        let keys = |entries: Vec<MemoryEntry>| {
+            let mut keys: Vec<String> = entries.into_iter().map(|e| e.key).collect();
+            keys.sort();
+            keys
+        };
+
+        let both = mem
+            .recall_with_tags("deploy", &tags(&["project:crabclaw", "source:slack"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(keys(both), vec!["a"]);
+
+        let project = mem
+            .recall_with_tags("deploy", &tags(&["project:crabclaw"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(keys(project), vec!["a", "b"]);
+
+        // Duplicated filter tags do not change the AND semantics.
+        let slack = mem
+            .recall_with_tags("deploy", &tags(&["source:slack", "source:slack"]), 10)
+            .await
+            .unwrap();
+        assert_eq!(keys(slack), vec!["a", "c"]);
+
+        let none = mem
+            .recall_with_tags("deploy", &tags(&["project:other"]), 10)
+            .await
+            .unwrap();
+        assert!(none.is_empty());
+
+        let unfiltered = mem.recall_with_tags("deploy", &[], 10).await.unwrap();
+        assert_eq!(keys(unfiltered), vec!["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn tags_are_replaced_on_restore_and_dropped_on_forget() {
+        let (_tmp, mem) = temp_sqlite();
+        mem.store_tagged("k", "tagged fact", MemoryCategory::Core, &tags(&["old"]))
+            .await
+            .unwrap();
+        mem.store_tagged("k", "tagged fact", MemoryCategory::Core, &tags(&["new"]))
+            .await
+            .unwrap();
+
+        assert!(mem
+            .recall_with_tags("tagged", &tags(&["old"]), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            mem.recall_with_tags("tagged", &tags(&["new"]), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        mem.forget("k").await.unwrap();
+        mem.store("k", "tagged fact", MemoryCategory::Core)
+            .await
+            .unwrap();
+        assert!(mem
+            .recall_with_tags("tagged", &tags(&["new"]), 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn store_tagged_rolls_back_the_row_when_tagging_fails() {
+        let (_tmp, mem) = temp_sqlite();
+        mem.conn
+            .lock()
+            .unwrap()
+            .execute_batch("DROP TABLE memory_tags")
+            .unwrap();
+
+        assert!(mem
+            .store_tagged("k", "tagged", MemoryCategory::Core, &tags(&["a"]))
+            .await
+            .is_err());
+        assert!(mem.get("k").await.unwrap().is_none());
+    }
+
     // ── Edge cases: reindex ──────────────────────────────────────
 
     #[tokio::test]
@@ -1521,4 +2157,268 @@ mod tests {
         let all = mem.list(None).await.unwrap();
         assert!(all.is_empty());
     }
+
+    // ── At-rest encryption ───────────────────────────────────────
+
+    #[tokio::test]
+    async fn encrypted_store_roundtrips_and_hides_plaintext() {
+        let tmp = TempDir::new().unwrap();
+        let mem = SqliteMemory::new(tmp.path())
+            .unwrap()
+            .with_encryption_passphrase("correct horse");
+        mem.store("lang", "User prefers Rust", MemoryCategory::Core)
+            .await
+            .unwrap();
+
+        let raw: String = mem
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT content FROM memories WHERE key = 'lang'", [], |r| {
+                r.get(0)
+            })
+            .unwrap();
+        assert!(!raw.contains("Rust"));
+
+        let entry = mem.get("lang").await.unwrap().unwrap();
+        assert_eq!(entry.content, "User prefers Rust");
+        let results = mem.recall("rust", 5).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].content, "User prefers Rust");
+        assert_eq!(mem.list(None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn wrong_key_rows_are_skipped() {
+        let tmp = TempDir::new().unwrap();
+        {
+            let mem = SqliteMemory::new(tmp.path())
+                .unwrap()
+                .with_encryption_passphrase("correct horse");
+            mem.store("lang", "User prefers Rust", MemoryCategory::Core)
+                .await
+                .unwrap();
+        }
+
+        let mem = SqliteMemory::new(tmp.path())
+            .unwrap()
+            .with_encryption_passphrase("battery staple");
+        assert!(mem.get("lang").await.unwrap().is_none());
+        assert!(mem.recall("rust", 5).await.unwrap().is_empty());
+        assert!(mem.list(None).await.unwrap().is_empty());
+        assert_eq!(mem.count().await.unwrap(), 1);
+    }
+
+    #[test]
+    fn each_database_gets_its_own_kdf_salt() {
+        let (a, b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let salt_a = SqliteMemory::new(a.path()).unwrap().kdf_salt;
+        let salt_b = SqliteMemory::new(b.path()).unwrap().kdf_salt;
+
+        assert_eq!(salt_a.len(), encryption::SALT_LEN);
+        assert_ne!(salt_a, salt_b);
+        assert_eq!(SqliteMemory::new(a.path()).unwrap().kdf_salt, salt_a);
+    }
+
+    // ── Recency weighting ────────────────────────────────────────
+
+    #[tokio::test]
+    async fn recency_weighting_ranks_newer_of_equally_relevant_rows_first() {
+        let (_tmp, mem) = temp_sqlite();
+        mem.store(
+            "old_lang",
+            "favourite language is Rust",
+            MemoryCategory::Core,
+        )
+        .await
+        .unwrap();
+        mem.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE memories SET created_at = '2000-01-01T00:00:00+00:00' WHERE key = 'old_lang'",
+                [],
+            )
+            .unwrap();
+        mem.store(
+            "new_lang",
+            "favourite language is Rust",
+            MemoryCategory::Core,
+        )
+        .await
+        .unwrap();
+
+        let plain = mem.recall("favourite language", 10).await.unwrap();
+        assert_eq!(plain.len(), 2);
+        assert_eq!(plain[0].score, plain[1].score);
+
+        let mem = mem.with_recency_half_life_days(30.0);
+        let weighted = mem.recall("favourite language", 10).await.unwrap();
+        assert_eq!(weighted.len(), 2);
+        assert_eq!(weighted[0].key, "new_lang");
+        assert!(weighted[0].score > weighted[1].score);
+
+        let mem = mem.with_recency_half_life_days(0.0);
+        let off = mem.recall("favourite language", 10).await.unwrap();
+        assert_eq!(
+            off.iter().map(|e| &e.key).collect::<Vec<_>>(),
+            plain.iter().map(|e| &e.key).collect::<Vec<_>>()
+        );
+    }
+
+    // ── Retention ────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn prune_expired_drops_only_stale_conversation_rows() {
+        let (_tmp, mem) = temp_sqlite();
+        assert_eq!(mem.prune_expired().await.unwrap(), 0);
+
+        let mem = mem.with_conversation_retention_days(30);
+        mem.store("old_chat", "stale", MemoryCategory::Conversation)
+            .await
+            .unwrap();
+        mem.store("old_pref", "likes tea", MemoryCategory::Core)
+            .await
+            .unwrap();
+        mem.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE memories SET updated_at = '2000-01-01T00:00:00+00:00'",
+                [],
+            )
+            .unwrap();
+        mem.store("new_chat", "fresh", MemoryCategory::Conversation)
+            .await
+            .unwrap();
+
+        assert_eq!(mem.prune_expired().await.unwrap(), 1);
+        assert!(mem.get("old_chat").await.unwrap().is_none());
+        assert!(mem.get("old_pref").await.unwrap().is_some());
+        assert!(mem.get("new_chat").await.unwrap().is_some());
+    }
+
+    // ── Compaction ───────────────────────────────────────────────
+
+    struct SummarizingProvider {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Provider for SummarizingProvider {
+        async fn chat_with_system(
+            &self,
+            _system_prompt: Option<&str>,
+            message: &str,
+            _model: &str,
+            _temperature: f64,
+        ) -> anyhow::Result<String> {
+            self.prompts.lock().unwrap().push(message.to_string());
+            Ok("User is migrating the billing service to Rust".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn compact_replaces_old_rows_with_recallable_summary() {
+        let (_tmp, mem) = temp_sqlite();
+        for (key, content) in [("c1", "talked about billing"), ("c2", "Rust port started")] {
+            mem.store(key, content, MemoryCategory::Conversation)
+                .await
+                .unwrap();
+        }
+        mem.store("pref", "likes tea", MemoryCategory::Core)
+            .await
+            .unwrap();
+        mem.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE memories SET updated_at = '2000-01-01T00:00:00+00:00'",
+                [],
+            )
+            .unwrap();
+        mem.store("c3", "fresh chat", MemoryCategory::Conversation)
+            .await
+            .unwrap();
+
+        let provider = SummarizingProvider {
+            prompts: Mutex::new(Vec::new()),
+        };
+        let report = mem
+            .compact(
+                MemoryCategory::Conversation,
+                std::time::Duration::from_secs(3600),
+                &provider,
+                "test-model",
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.rows_compacted, 2);
+        let prompt = provider.prompts.lock().unwrap()[0].clone();
+        assert!(prompt.contains("talked about billing") && prompt.contains("Rust port started"));
+        assert!(mem.get("c1").await.unwrap().is_none());
+        assert!(mem.get("c2").await.unwrap().is_none());
+        assert!(mem.get("c3").await.unwrap().is_some());
+        assert!(mem.get("pref").await.unwrap().is_some());
+
+        let summary = mem
+            .get(report.summary_key.as_deref().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.category, MemoryCategory::Core);
+        let recalled = mem.recall("billing", 5).await.unwrap();
+        assert_eq!(recalled.len(), 1);
+        assert_eq!(recalled[0].key, report.summary_key.unwrap());
+    }
+
+    // ── Connection pool ──────────────────────────────────────────
+
+    #[test]
+    fn opens_database_in_wal_mode() {
+        let (_tmp, mem) = temp_sqlite();
+        let conn = mem.conn.lock().unwrap();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+        assert!(mem.conn.size() > 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_stores_and_recalls_do_not_lock() {
+        let (_tmp, mem) = temp_sqlite();
+        let mem = Arc::new(mem);
+
+        let writers = (0..8).map(|w| {
+            let mem = Arc::clone(&mem);
+            tokio::spawn(async move {
+                for i in 0..25 {
+                    mem.store(
+                        &format!("w{w}_k{i}"),
+                        &format!("concurrent note {w} {i}"),
+                        MemoryCategory::Daily,
+                    )
+                    .await?;
+                }
+                anyhow::Ok(())
+            })
+        });
+        let readers = (0..8).map(|_| {
+            let mem = Arc::clone(&mem);
+            tokio::spawn(async move {
+                for _ in 0..25 {
+                    mem.recall("concurrent note", 5).await?;
+                }
+                anyhow::Ok(())
+            })
+        });
+        let handles: Vec<_> = writers.chain(readers).collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        assert_eq!(mem.count().await.unwrap(), 200);
+    }
 }
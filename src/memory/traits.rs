@@ -1,5 +1,10 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How many ranked candidates `recall_multi` and `recall_in_category`
+/// consider per requested result before widening the search.
+const RECALL_MULTI_OVERFETCH: usize = 4;
 
 /// A single memory entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,7 +19,7 @@ pub struct MemoryEntry {
 }
 
 /// Memory categories for organization
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum MemoryCategory {
     /// Long-term facts, preferences, decisions
@@ -51,7 +56,123 @@ pub trait Memory: Send + Sync {
     /// Recall memories matching a query (keyword search)
     async fn recall(&self, query: &str, limit: usize) -> anyhow::Result<Vec<MemoryEntry>>;
 
-    /// Get a specific memory by key
+    /// Store a memory entry with free-form tags (e.g. `project:crabclaw`),
+    /// replacing any tags previously stored under `key`. Plain `store` leaves
+    /// existing tags untouched.
+    async fn store_tagged(
+        &self,
+        key: &str,
+        content: &str,
+        category: MemoryCategory,
+        tags: &[String],
+    ) -> anyhow::Result<()> {
+        if !tags.is_empty() {
+            anyhow::bail!(
+                "Tagged memories are not supported by the {} backend",
+                self.name()
+            );
+        }
+        self.store(key, content, category).await
+    }
+
+    /// Recall memories matching a query that carry every one of `tags`
+    async fn recall_with_tags(
+        &self,
+        query: &str,
+        tags: &[String],
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        if !tags.is_empty() {
+            anyhow::bail!(
+                "Tagged memories are not supported by the {} backend",
+                self.name()
+            );
+        }
+        self.recall(query, limit).await
+    }
+
+    /// Recall memories matching a query within `category` only, in ranked
+    /// order. The default widens an unfiltered recall until `limit` entries of
+    /// the category turn up or the matches run out.
+    async fn recall_in_category(
+        &self,
+        query: &str,
+        category: &MemoryCategory,
+        limit: usize,
+    ) -> anyhow::Result<Vec<MemoryEntry>> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let mut fetch = limit.saturating_mul(RECALL_MULTI_OVERFETCH);
+        loop {
+            let candidates = self.recall(query, fetch).await?;
+            let exhausted = candidates.len() < fetch || fetch == usize::MAX;
+            let matched: Vec<MemoryEntry> = candidates
+                .into_iter()
+                .filter(|entry| &entry.category == category)
+                .take(limit)
+                .collect();
+            if matched.len() == limit || exhausted {
+                return Ok(matched);
+            }
+            fetch = fetch.saturating_mul(2);
+        }
+    }
+
+    /// Recall for several categories at once: one ranked search, partitioned
+    /// so each category keeps at most its own limit in ranked order. A
+    /// category that ranks too low to fill its limit from that search is
+    /// topped up with [`Memory::recall_in_category`]. Every requested category
+    /// has an entry, empty when nothing matched; when a category is listed
+    /// twice the first limit applies.
+    async fn recall_multi(
+        &self,
+        query: &str,
+        limits: &[(MemoryCategory, usize)],
+    ) -> anyhow::Result<HashMap<MemoryCategory, Vec<MemoryEntry>>> {
+        let mut by_category: HashMap<MemoryCategory, Vec<MemoryEntry>> = HashMap::new();
+        let mut remaining: HashMap<&MemoryCategory, usize> = HashMap::new();
+        for (category, limit) in limits {
+            by_category.entry(category.clone()).or_default();
+            remaining.entry(category).or_insert(*limit);
+        }
+        let wanted: usize = remaining.values().sum();
+        if wanted == 0 {
+            return Ok(by_category);
+        }
+
+        let fetch = wanted.saturating_mul(RECALL_MULTI_OVERFETCH);
+        let candidates = self.recall(query, fetch).await?;
+        // A short page already holds every match; nothing is left to top up.
+        let exhausted = candidates.len() < fetch;
+        for entry in candidates {
+            let Some(left) = remaining.get_mut(&entry.category) else {
+                continue;
+            };
+            if *left == 0 {
+                continue;
+            }
+            *left -= 1;
+            by_category
+                .entry(entry.category.clone())
+                .or_default()
+                .push(entry);
+        }
+
+        if !exhausted {
+            for (category, left) in remaining {
+                if left > 0 {
+                    let limit = by_category[category].len() + left;
+                    let entries = self.recall_in_category(query, category, limit).await?;
+                    by_category.insert(category.clone(), entries);
+                }
+            }
+        }
+        Ok(by_category)
+    }
+
+    /// Get a specific memory by its exact key — a direct lookup with no
+    /// ranking, scoring or category filtering. `Ok(None)` when absent.
     async fn get(&self, key: &str) -> anyhow::Result<Option<MemoryEntry>>;
 
     /// List all memory keys, optionally filtered by category
@@ -63,6 +184,12 @@ pub trait Memory: Send + Sync {
     /// Count total memories
     async fn count(&self) -> anyhow::Result<usize>;
 
+    /// Drop entries past the backend's retention window, returning how many
+    /// were removed. Backends without retention keep everything.
+    async fn prune_expired(&self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+
     /// Health check
     async fn health_check(&self) -> bool;
 }
@@ -2,6 +2,7 @@ pub mod log;
 pub mod multi;
 pub mod noop;
 pub mod otel;
+pub mod spans;
 pub mod traits;
 
 pub use self::log::LogObserver;
@@ -0,0 +1,183 @@
+//! Spans exported to OpenTelemetry when the `otel` feature is enabled.
+//!
+//! Provider calls, tool executions and channel sends open their spans through
+//! these helpers. Without the feature every helper returns a disabled span and
+//! recording on it is a no-op, so call sites stay unconditional and log output
+//! is unchanged.
+
+use tracing::field::Empty;
+use tracing::Span;
+
+/// One logical provider request, covering cache lookup, retries and fallback.
+pub fn provider_request(model: &str) -> Span {
+    if cfg!(feature = "otel") {
+        tracing::info_span!(
+            "provider.request",
+            model,
+            provider = Empty,
+            attempts = Empty,
+            cache_hit = Empty,
+            success = Empty
+        )
+    } else {
+        Span::none()
+    }
+}
+
+/// One attempt against a single provider in the chain.
+pub fn provider_attempt(provider: &str, attempt: u32) -> Span {
+    if cfg!(feature = "otel") {
+        // Signed, so OpenTelemetry exports an integer rather than a string.
+        let attempt = i64::from(attempt);
+        tracing::info_span!("provider.attempt", provider, attempt, success = Empty)
+    } else {
+        Span::none()
+    }
+}
+
+/// One tool `execute` call.
+pub fn tool_execute(tool: &str) -> Span {
+    if cfg!(feature = "otel") {
+        tracing::info_span!("tool.execute", tool, success = Empty)
+    } else {
+        Span::none()
+    }
+}
+
+/// One outbound channel `send`.
+pub fn channel_send(channel: &str) -> Span {
+    if cfg!(feature = "otel") {
+        tracing::info_span!("channel.send", channel, success = Empty)
+    } else {
+        Span::none()
+    }
+}
+
+pub fn record_success(span: &Span, success: bool) {
+    span.record("success", success);
+}
+
+/// Record whether `result` succeeded on `span` and pass it through.
+pub fn record_result<T, E>(span: &Span, result: Result<T, E>) -> Result<T, E> {
+    record_success(span, result.is_ok());
+    result
+}
+
+/// Record how a provider request was answered.
+pub fn record_provider_outcome(span: &Span, provider: &str, attempts: u32, cache_hit: bool) {
+    span.record("provider", provider);
+    span.record("attempts", i64::from(attempts));
+    span.record("cache_hit", cache_hit);
+}
+
+/// `tracing` layer that exports spans through the global OpenTelemetry tracer
+/// provider, which `OtelObserver` installs once observability is configured.
+#[cfg(feature = "otel")]
+pub fn layer<S>() -> tracing_opentelemetry::OpenTelemetryLayer<S, GlobalTracer>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(GlobalTracer)
+}
+
+/// Resolves the global tracer per span, so the layer can be installed before
+/// `OtelObserver` replaces the default no-op provider.
+#[cfg(feature = "otel")]
+#[derive(Debug, Clone, Copy)]
+pub struct GlobalTracer;
+
+#[cfg(feature = "otel")]
+impl opentelemetry::trace::Tracer for GlobalTracer {
+    type Span = opentelemetry::global::BoxedSpan;
+
+    fn build_with_context(
+        &self,
+        builder: opentelemetry::trace::SpanBuilder,
+        parent_cx: &opentelemetry::Context,
+    ) -> Self::Span {
+        opentelemetry::global::tracer("crabclaw").build_with_context(builder, parent_cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn helpers_are_disabled_without_the_feature() {
+        let span = provider_attempt("primary", 1);
+        record_success(&span, true);
+        assert!(span.is_disabled());
+    }
+
+    #[cfg(feature = "otel")]
+    mod otel {
+        use super::*;
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry::{KeyValue, Value};
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        /// Run `f` under a subscriber exporting to memory and return its spans.
+        fn exported_spans(f: impl FnOnce()) -> Vec<SpanData> {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build();
+            let subscriber = tracing_subscriber::registry()
+                .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+            tracing::subscriber::with_default(subscriber, f);
+            provider.force_flush().unwrap();
+            exporter.get_finished_spans().unwrap()
+        }
+
+        fn attr(span: &SpanData, key: &str) -> Option<Value> {
+            span.attributes
+                .iter()
+                .find(|kv: &&KeyValue| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        }
+
+        #[test]
+        fn provider_spans_carry_request_and_attempt_attributes() {
+            let spans = exported_spans(|| {
+                let request = provider_request("gpt-test");
+                let _entered = request.enter();
+                let attempt = provider_attempt("primary", 2);
+                record_success(&attempt, false);
+                drop(attempt);
+                record_provider_outcome(&request, "fallback", 3, false);
+                record_success(&request, true);
+            });
+
+            let attempt = spans.iter().find(|s| s.name == "provider.attempt").unwrap();
+            assert_eq!(attr(attempt, "provider"), Some("primary".into()));
+            assert_eq!(attr(attempt, "attempt"), Some(Value::I64(2)));
+            assert_eq!(attr(attempt, "success"), Some(Value::Bool(false)));
+
+            let request = spans.iter().find(|s| s.name == "provider.request").unwrap();
+            assert_eq!(attr(request, "model"), Some("gpt-test".into()));
+            assert_eq!(attr(request, "provider"), Some("fallback".into()));
+            assert_eq!(attr(request, "attempts"), Some(Value::I64(3)));
+            assert_eq!(attr(request, "cache_hit"), Some(Value::Bool(false)));
+            assert_eq!(attr(request, "success"), Some(Value::Bool(true)));
+            assert_eq!(attempt.parent_span_id, request.span_context.span_id());
+        }
+
+        #[test]
+        fn tool_and_channel_spans_record_success() {
+            let spans = exported_spans(|| {
+                record_success(&tool_execute("shell"), true);
+                record_success(&channel_send("telegram"), false);
+            });
+
+            let tool = spans.iter().find(|s| s.name == "tool.execute").unwrap();
+            assert_eq!(attr(tool, "tool"), Some("shell".into()));
+            assert_eq!(attr(tool, "success"), Some(Value::Bool(true)));
+            let send = spans.iter().find(|s| s.name == "channel.send").unwrap();
+            assert_eq!(attr(send, "channel"), Some("telegram".into()));
+            assert_eq!(attr(send, "success"), Some(Value::Bool(false)));
+        }
+    }
+}
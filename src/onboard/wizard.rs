@@ -97,6 +97,9 @@ pub fn run_wizard() -> Result<Config> {
         runtime: RuntimeConfig::default(),
         reliability: crate::config::ReliabilityConfig::default(),
         model_routes: Vec::new(),
+        model_routing: crate::config::ModelRoutingConfig::default(),
+        experiment: crate::config::ExperimentConfig::default(),
+        cost: crate::config::CostConfig::default(),
         heartbeat: HeartbeatConfig::default(),
         channels_config,
         memory: memory_config, // User-selected memory backend
@@ -274,6 +277,9 @@ pub fn run_quick_setup(
             0
         },
         chunk_max_tokens: 512,
+        max_content_bytes: 64 * 1024,
+        oversize_mode: "reject".to_string(),
+        max_recall_bytes: 256 * 1024,
     };
 
     let config = Config {
@@ -288,6 +294,9 @@ pub fn run_quick_setup(
         runtime: RuntimeConfig::default(),
         reliability: crate::config::ReliabilityConfig::default(),
         model_routes: Vec::new(),
+        model_routing: crate::config::ModelRoutingConfig::default(),
+        experiment: crate::config::ExperimentConfig::default(),
+        cost: crate::config::CostConfig::default(),
         heartbeat: HeartbeatConfig::default(),
         channels_config: ChannelsConfig::default(),
         memory: memory_config,
@@ -1102,6 +1111,9 @@ fn setup_memory() -> Result<MemoryConfig> {
         keyword_weight: 0.3,
         embedding_cache_size: if backend == "sqlite" { 10000 } else { 0 },
         chunk_max_tokens: 512,
+        max_content_bytes: 64 * 1024,
+        oversize_mode: "reject".to_string(),
+        max_recall_bytes: 256 * 1024,
     })
 }
 
@@ -1,12 +1,21 @@
-use crate::providers::traits::Provider;
+use crate::providers::metering::Usage;
+use crate::providers::traits::{
+    self, ChatMessage, ContentPart, ConversationMessage, MessageContent, Provider, SamplingParams,
+};
+use crate::tools::ToolSpec;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+/// The Messages API requires `max_tokens`; used when the caller sets none.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
 
 pub struct AnthropicProvider {
     credential: Option<String>,
     base_url: String,
     client: Client,
+    prompt_caching: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,6 +44,262 @@ struct ContentBlock {
     text: String,
 }
 
+/// Messages request in content-block form, optionally carrying tool
+/// definitions.
+#[derive(Debug, Serialize)]
+struct BlockChatRequest {
+    model: String,
+    max_tokens: u32,
+    /// A plain string, or text blocks once marked for prompt caching.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<serde_json::Value>,
+    messages: Vec<serde_json::Value>,
+    temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+impl BlockChatRequest {
+    fn new(
+        model: &str,
+        system: Option<String>,
+        messages: Vec<serde_json::Value>,
+        temperature: f64,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            max_tokens: DEFAULT_MAX_TOKENS,
+            system: system.map(serde_json::Value::String),
+            messages,
+            temperature,
+            top_p: None,
+            stop_sequences: Vec::new(),
+            tools: Vec::new(),
+            stream: false,
+        }
+    }
+
+    /// Request honoring `max_tokens`, `top_p` and `stop` (as
+    /// `stop_sequences`); the Messages API takes no seed.
+    fn with_params(
+        model: &str,
+        system: Option<String>,
+        messages: Vec<serde_json::Value>,
+        params: &SamplingParams,
+    ) -> Self {
+        let mut request = Self::new(model, system, messages, params.temperature);
+        request.max_tokens = params.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+        request.top_p = params.top_p;
+        request.stop_sequences.clone_from(&params.stop);
+        request
+    }
+
+    /// Mark the system prompt and the last tool definition as cache
+    /// breakpoints, so later turns reuse the cached prefix up to them.
+    /// Prefixes shorter than the model's minimum are simply not cached.
+    fn cache_prompt(&mut self) {
+        let ephemeral = serde_json::json!({"type": "ephemeral"});
+        self.system = self.system.take().map(|system| match system {
+            serde_json::Value::String(text) => serde_json::json!([
+                {"type": "text", "text": text, "cache_control": ephemeral}
+            ]),
+            blocks => blocks,
+        });
+        if let Some(serde_json::Value::Object(tool)) = self.tools.last_mut() {
+            tool.insert("cache_control".into(), ephemeral);
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ToolChatResponse {
+    content: Vec<ResponseBlock>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ResponseBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Message content as Anthropic expects it: plain text stays a string, parts
+/// become text and image blocks. The API takes no audio, so audio parts are
+/// dropped.
+fn content_blocks(content: &MessageContent) -> serde_json::Value {
+    let MessageContent::Parts(parts) = content else {
+        return serde_json::Value::String(content.text().into_owned());
+    };
+    let blocks: Vec<_> = parts
+        .iter()
+        .filter_map(|part| {
+            let source = match (part, part.inline_data()) {
+                (ContentPart::Text(text), _) => {
+                    return Some(serde_json::json!({"type": "text", "text": text}));
+                }
+                (ContentPart::Audio { .. }, _) => {
+                    tracing::warn!("Anthropic does not accept audio; dropping audio part");
+                    return None;
+                }
+                (_, Some((media_type, data))) => {
+                    serde_json::json!({"type": "base64", "media_type": media_type, "data": data})
+                }
+                (ContentPart::ImageUrl { url, .. }, None) => {
+                    serde_json::json!({"type": "url", "url": url})
+                }
+                (ContentPart::ImageBase64 { .. }, None) => return None,
+            };
+            Some(serde_json::json!({"type": "image", "source": source}))
+        })
+        .collect();
+    serde_json::Value::Array(blocks)
+}
+
+/// Split a conversation into the system prompt and content-block
+/// messages. Tool calls become `tool_use` blocks; consecutive tool results
+/// are merged into one user message of `tool_result` blocks, as the API
+/// requires results to follow their calls in a single turn.
+fn block_messages(messages: &[ConversationMessage]) -> (Option<String>, Vec<serde_json::Value>) {
+    let mut system: Vec<String> = Vec::new();
+    let mut wire: Vec<serde_json::Value> = Vec::new();
+    let mut pending_results: Vec<serde_json::Value> = Vec::new();
+    let flush = |wire: &mut Vec<serde_json::Value>, results: &mut Vec<serde_json::Value>| {
+        if !results.is_empty() {
+            wire.push(serde_json::json!({"role": "user", "content": std::mem::take(results)}));
+        }
+    };
+
+    for message in messages {
+        match message {
+            ConversationMessage::ToolResult(result) => {
+                pending_results.push(serde_json::json!({
+                    "type": "tool_result",
+                    "tool_use_id": result.tool_call_id,
+                    "content": result.content,
+                }));
+                continue;
+            }
+            ConversationMessage::Chat(chat) if chat.role == "system" => {
+                system.push(chat.text().into_owned());
+                continue;
+            }
+            _ => flush(&mut wire, &mut pending_results),
+        }
+        match message {
+            ConversationMessage::Chat(chat) => {
+                wire.push(serde_json::json!({"role": chat.role, "content": content_blocks(&chat.content)}));
+            }
+            ConversationMessage::AssistantToolCalls { text, tool_calls } => {
+                let mut blocks: Vec<serde_json::Value> = text
+                    .iter()
+                    .filter(|text| !text.is_empty())
+                    .map(|text| serde_json::json!({"type": "text", "text": text}))
+                    .collect();
+                blocks.extend(tool_calls.iter().map(|call| {
+                    let input = serde_json::from_str::<serde_json::Value>(&call.arguments)
+                        .unwrap_or_else(|_| serde_json::json!({}));
+                    serde_json::json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.name,
+                        "input": input,
+                    })
+                }));
+                wire.push(serde_json::json!({"role": "assistant", "content": blocks}));
+            }
+            ConversationMessage::ToolResult(_) => {}
+        }
+    }
+    flush(&mut wire, &mut pending_results);
+
+    let system = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system, wire)
+}
+
+fn conversation(messages: &[ChatMessage]) -> Vec<ConversationMessage> {
+    messages
+        .iter()
+        .cloned()
+        .map(ConversationMessage::Chat)
+        .collect()
+}
+
+fn tool_definitions(tools: &[ToolSpec]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "name": tool.name,
+                "description": tool.description,
+                "input_schema": tool.parameters,
+            })
+        })
+        .collect()
+}
+
+/// Text carried by one server-sent event of a streamed response, if any.
+/// Only `text_delta`s carry text; an `error` event fails the stream.
+fn stream_event_text(data: &str) -> anyhow::Result<Option<String>> {
+    let event: serde_json::Value = serde_json::from_str(data)?;
+    match event["type"].as_str() {
+        Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+            Ok(event["delta"]["text"].as_str().map(ToString::to_string))
+        }
+        Some("error") => Err(super::error::ProviderError::ServerError {
+            status: 500,
+            message: format!(
+                "Anthropic stream error: {}",
+                event["error"]["message"].as_str().unwrap_or("unknown")
+            ),
+        }
+        .into()),
+        _ => Ok(None),
+    }
+}
+
+fn parse_tool_response(response: ToolChatResponse) -> traits::ChatResponse {
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for block in response.content {
+        match block {
+            ResponseBlock::Text { text: chunk } => text.push_str(&chunk),
+            ResponseBlock::ToolUse { id, name, input } => tool_calls.push(traits::ToolCall {
+                id,
+                name,
+                arguments: input.to_string(),
+            }),
+            ResponseBlock::Other => {}
+        }
+    }
+    traits::ChatResponse {
+        text: (!text.is_empty()).then_some(text),
+        tool_calls,
+        usage: response.usage,
+        model: response.model,
+        finish_reason: response.stop_reason,
+    }
+}
+
 impl AnthropicProvider {
     pub fn new(api_key: Option<&str>) -> Self {
         Self::with_base_url(api_key, None)
@@ -55,12 +320,60 @@ impl AnthropicProvider {
                 .connect_timeout(std::time::Duration::from_secs(10))
                 .build()
                 .unwrap_or_else(|_| Client::new()),
+            prompt_caching: true,
         }
     }
 
+    /// Whether to mark system prompts and tool definitions for Anthropic's
+    /// prompt cache (on by default); cache reads are billed at a fraction
+    /// of the input price, so repeated agent turns cost less.
+    pub fn with_prompt_caching(mut self, enabled: bool) -> Self {
+        self.prompt_caching = enabled;
+        self
+    }
+
     fn is_setup_token(token: &str) -> bool {
         token.starts_with("sk-ant-oat01-")
     }
+
+    fn credential(&self) -> anyhow::Result<&str> {
+        self.credential.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Anthropic credentials not set. Set ANTHROPIC_API_KEY or ANTHROPIC_OAUTH_TOKEN (setup-token)."
+            )
+        })
+    }
+
+    /// `POST /v1/messages` with the version header and whichever auth header
+    /// suits `credential`.
+    fn messages_request(&self, credential: &str, body: &impl Serialize) -> RequestBuilder {
+        let request = self
+            .client
+            .post(format!("{}/v1/messages", self.base_url))
+            .header("anthropic-version", "2023-06-01")
+            .header("content-type", "application/json")
+            .json(body);
+
+        let request = if Self::is_setup_token(credential) {
+            request.header("Authorization", format!("Bearer {credential}"))
+        } else {
+            request.header("x-api-key", credential)
+        };
+        super::context::apply_request_id(request)
+    }
+
+    async fn send(&self, mut request: BlockChatRequest) -> anyhow::Result<ToolChatResponse> {
+        let credential = self.credential()?;
+        if self.prompt_caching {
+            request.cache_prompt();
+        }
+        let response = self.messages_request(credential, &request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error("Anthropic", response).await.into());
+        }
+        Ok(response.json().await?)
+    }
 }
 
 #[async_trait]
@@ -72,15 +385,11 @@ impl Provider for AnthropicProvider {
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
-        let credential = self.credential.as_ref().ok_or_else(|| {
-            anyhow::anyhow!(
-                "Anthropic credentials not set. Set ANTHROPIC_API_KEY or ANTHROPIC_OAUTH_TOKEN (setup-token)."
-            )
-        })?;
+        let credential = self.credential()?;
 
         let request = ChatRequest {
             model: model.to_string(),
-            max_tokens: 4096,
+            max_tokens: DEFAULT_MAX_TOKENS,
             system: system_prompt.map(ToString::to_string),
             messages: vec![Message {
                 role: "user".to_string(),
@@ -89,23 +398,10 @@ impl Provider for AnthropicProvider {
             temperature,
         };
 
-        let mut request = self
-            .client
-            .post(format!("{}/v1/messages", self.base_url))
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request);
-
-        if Self::is_setup_token(credential) {
-            request = request.header("Authorization", format!("Bearer {credential}"));
-        } else {
-            request = request.header("x-api-key", credential);
-        }
-
-        let response = request.send().await?;
+        let response = self.messages_request(credential, &request).send().await?;
 
         if !response.status().is_success() {
-            return Err(super::api_error("Anthropic", response).await);
+            return Err(super::api_error("Anthropic", response).await.into());
         }
 
         let chat_response: ChatResponse = response.json().await?;
@@ -117,6 +413,123 @@ impl Provider for AnthropicProvider {
             .map(|c| c.text)
             .ok_or_else(|| anyhow::anyhow!("No response from Anthropic"))
     }
+
+    /// Sends the whole conversation, including image parts, as content blocks.
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        self.chat_with_history_params(messages, model, &SamplingParams::new(temperature))
+            .await
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text("Anthropic")
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        let (system, messages) = block_messages(&conversation(messages));
+        let request = BlockChatRequest::with_params(model, system, messages, params);
+        Ok(parse_tool_response(self.send(request).await?))
+    }
+
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let messages = vec![serde_json::json!({"role": "user", "content": message})];
+        let request = BlockChatRequest::with_params(
+            model,
+            system_prompt.map(ToString::to_string),
+            messages,
+            params,
+        );
+        parse_tool_response(self.send(request).await?).into_text("Anthropic")
+    }
+
+    /// Streams the reply over server-sent events, forwarding each text delta
+    /// as it arrives.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+        chunks: &mpsc::Sender<String>,
+    ) -> anyhow::Result<String> {
+        let credential = self.credential()?;
+        let (system, messages) = block_messages(&conversation(messages));
+        let mut request = BlockChatRequest::new(model, system, messages, temperature);
+        request.stream = true;
+        if self.prompt_caching {
+            request.cache_prompt();
+        }
+
+        let mut response = self.messages_request(credential, &request).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error("Anthropic", response).await.into());
+        }
+
+        // Events are split on line boundaries, which chunks need not respect.
+        let mut text = String::new();
+        let mut pending = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            pending.extend_from_slice(&chunk);
+            while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim_end().strip_prefix("data:") else {
+                    continue;
+                };
+                if let Some(delta) = stream_event_text(data.trim())? {
+                    text.push_str(&delta);
+                    // A closed receiver only means nobody is watching anymore.
+                    let _ = chunks.send(delta).await;
+                }
+            }
+        }
+
+        if text.is_empty() {
+            anyhow::bail!("No response from Anthropic");
+        }
+        Ok(text)
+    }
+
+    /// Sends `tools` as tool definitions and returns `tool_use` blocks as
+    /// structured calls.
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        let (system, messages) = block_messages(messages);
+        let mut request = BlockChatRequest::new(model, system, messages, temperature);
+        request.tools = tool_definitions(tools);
+        let parsed = parse_tool_response(self.send(request).await?);
+        if parsed.text.is_none() && parsed.tool_calls.is_empty() {
+            anyhow::bail!("No response from Anthropic");
+        }
+        Ok(parsed)
+    }
 }
 
 #[cfg(test)]
@@ -262,6 +675,168 @@ mod tests {
         assert_eq!(resp.content[1].text, "Second");
     }
 
+    #[test]
+    fn tool_conversation_maps_to_tool_use_blocks() {
+        let messages = vec![
+            ConversationMessage::Chat(traits::ChatMessage::system("Be brief")),
+            ConversationMessage::Chat(traits::ChatMessage::user("read both")),
+            ConversationMessage::AssistantToolCalls {
+                text: Some("Reading".into()),
+                tool_calls: vec![
+                    traits::ToolCall {
+                        id: "t1".into(),
+                        name: "file_read".into(),
+                        arguments: r#"{"path":"a"}"#.into(),
+                    },
+                    traits::ToolCall {
+                        id: "t2".into(),
+                        name: "file_read".into(),
+                        arguments: r#"{"path":"b"}"#.into(),
+                    },
+                ],
+            },
+            ConversationMessage::ToolResult(traits::ToolResultMessage {
+                tool_call_id: "t1".into(),
+                content: "A".into(),
+            }),
+            ConversationMessage::ToolResult(traits::ToolResultMessage {
+                tool_call_id: "t2".into(),
+                content: "B".into(),
+            }),
+        ];
+        let (system, wire) = block_messages(&messages);
+        assert_eq!(system.as_deref(), Some("Be brief"));
+        assert_eq!(wire.len(), 3);
+        assert_eq!(wire[1]["content"][1]["type"], "tool_use");
+        assert_eq!(wire[1]["content"][1]["input"]["path"], "a");
+        assert_eq!(wire[2]["role"], "user");
+        assert_eq!(wire[2]["content"][1]["tool_use_id"], "t2");
+
+        let json = r#"{"content":[
+            {"type":"text","text":"Let me check."},
+            {"type":"tool_use","id":"t3","name":"shell","input":{"command":"ls"}},
+            {"type":"thinking","thinking":"..."}
+        ]}"#;
+        let parsed = parse_tool_response(serde_json::from_str(json).unwrap());
+        assert_eq!(parsed.text.as_deref(), Some("Let me check."));
+        assert_eq!(parsed.tool_calls[0].name, "shell");
+        assert_eq!(parsed.tool_calls[0].arguments, r#"{"command":"ls"}"#);
+    }
+
+    #[test]
+    fn image_parts_map_to_image_blocks() {
+        let content = MessageContent::Parts(vec![
+            ContentPart::Text("what is this?".into()),
+            ContentPart::ImageUrl {
+                url: "https://example.com/cat.png".into(),
+                detail: None,
+            },
+            ContentPart::ImageBase64 {
+                media_type: "image/png".into(),
+                data: "iVBORw0".into(),
+            },
+            ContentPart::Audio {
+                format: "wav".into(),
+                data: "UklGRg==".into(),
+            },
+        ]);
+        assert_eq!(
+            content_blocks(&content),
+            serde_json::json!([
+                {"type": "text", "text": "what is this?"},
+                {"type": "image", "source": {"type": "url", "url": "https://example.com/cat.png"}},
+                {"type": "image", "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0"}}
+            ])
+        );
+        assert_eq!(
+            content_blocks(&MessageContent::Text("hi".into())),
+            serde_json::json!("hi")
+        );
+    }
+
+    #[test]
+    fn sampling_params_and_streaming_serialize() {
+        let mut req = BlockChatRequest::new("claude-3-haiku", None, Vec::new(), 0.2);
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["max_tokens"], 4096);
+        assert!(json.get("stream").is_none());
+        assert!(json.get("top_p").is_none());
+        assert!(json.get("stop_sequences").is_none());
+
+        req.max_tokens = 64;
+        req.top_p = Some(0.9);
+        req.stop_sequences = vec!["END".into()];
+        req.stream = true;
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["max_tokens"], 64);
+        assert_eq!(json["top_p"], 0.9);
+        assert_eq!(json["stop_sequences"], serde_json::json!(["END"]));
+        assert_eq!(json["stream"], true);
+    }
+
+    #[test]
+    fn prompt_caching_marks_system_and_last_tool() {
+        let mut req =
+            BlockChatRequest::new("claude-sonnet-4", Some("Be brief".into()), Vec::new(), 0.2);
+        req.tools = tool_definitions(&[
+            ToolSpec {
+                name: "shell".into(),
+                description: "Run a command".into(),
+                parameters: serde_json::json!({"type": "object"}),
+            },
+            ToolSpec {
+                name: "file_read".into(),
+                description: "Read a file".into(),
+                parameters: serde_json::json!({"type": "object"}),
+            },
+        ]);
+        assert_eq!(serde_json::to_value(&req).unwrap()["system"], "Be brief");
+
+        req.cache_prompt();
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(
+            json["system"],
+            serde_json::json!([
+                {"type": "text", "text": "Be brief", "cache_control": {"type": "ephemeral"}}
+            ])
+        );
+        assert!(json["tools"][0].get("cache_control").is_none());
+        assert_eq!(json["tools"][1]["cache_control"]["type"], "ephemeral");
+
+        let json = r#"{"content":[{"type":"text","text":"ok"}],
+            "usage":{"input_tokens":12,"output_tokens":3,
+                "cache_creation_input_tokens":0,"cache_read_input_tokens":2048}}"#;
+        let usage = parse_tool_response(serde_json::from_str(json).unwrap())
+            .usage
+            .unwrap();
+        assert_eq!(usage.input_tokens, 12);
+        assert_eq!(usage.cache_read_tokens, 2048);
+        assert_eq!(usage.total_tokens(), 2063);
+    }
+
+    #[test]
+    fn stream_events_yield_text_deltas() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1"}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+            r#"{"type":"ping"}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"lo"}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let text: String = events
+            .iter()
+            .filter_map(|data| stream_event_text(data).unwrap())
+            .collect();
+        assert_eq!(text, "Hello");
+
+        let err = stream_event_text(
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Overloaded"));
+    }
+
     #[test]
     fn temperature_range_serializes() {
         for temp in [0.0, 0.5, 1.0, 2.0] {
@@ -0,0 +1,134 @@
+//! Per-provider concurrency limit whose waiters are admitted by
+//! [`RequestPriority`], so an interactive turn overtakes queued background
+//! work instead of waiting behind it.
+
+use super::context::RequestPriority;
+use std::sync::{Mutex, PoisonError};
+use tokio::sync::{Notify, Semaphore, SemaphorePermit, TryAcquireError};
+
+const PRIORITIES: usize = RequestPriority::Interactive as usize + 1;
+
+#[derive(Debug)]
+pub(crate) struct Bulkhead {
+    permits: Semaphore,
+    /// Waiting requests per priority.
+    waiting: Mutex<[usize; PRIORITIES]>,
+    /// Signalled when a waiter arrives or leaves while lower-priority
+    /// waiters exist.
+    changed: Notify,
+}
+
+impl Bulkhead {
+    pub(crate) fn new(permits: usize) -> Self {
+        Self {
+            permits: Semaphore::new(permits),
+            waiting: Mutex::new([0; PRIORITIES]),
+            changed: Notify::new(),
+        }
+    }
+
+    pub(crate) fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.permits.try_acquire()
+    }
+
+    /// Wait for a permit. Waiters of a higher priority go first; requests of
+    /// equal priority are served in arrival order. Also reports whether this
+    /// waiter had to let a higher-priority one ahead.
+    pub(crate) async fn acquire(&self, priority: RequestPriority) -> (SemaphorePermit<'_>, bool) {
+        let _waiter = Waiter::register(self, priority);
+        let mut yielded = false;
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            // Enabled before checking, so a change in between is not missed.
+            changed.as_mut().enable();
+            if self.outranked(priority) {
+                yielded = true;
+                changed.await;
+                continue;
+            }
+            tokio::select! {
+                biased;
+                permit = self.permits.acquire() => {
+                    let permit = permit.expect("bulkhead semaphore is never closed");
+                    return (permit, yielded);
+                }
+                () = &mut changed => {}
+            }
+        }
+    }
+
+    fn outranked(&self, priority: RequestPriority) -> bool {
+        let waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        waiting[priority as usize + 1..].iter().any(|&n| n > 0)
+    }
+
+    /// Adjust the waiter count for `priority`, waking waiters whose turn may
+    /// have changed.
+    fn update_waiting(&self, priority: RequestPriority, arrived: bool) {
+        let mut waiting = self.waiting.lock().unwrap_or_else(PoisonError::into_inner);
+        let slot = &mut waiting[priority as usize];
+        *slot = if arrived { *slot + 1 } else { *slot - 1 };
+        if waiting[..priority as usize].iter().any(|&n| n > 0) {
+            self.changed.notify_waiters();
+        }
+    }
+}
+
+/// Counts one waiter for as long as it waits, including when its wait is
+/// cancelled by a timeout.
+struct Waiter<'a> {
+    bulkhead: &'a Bulkhead,
+    priority: RequestPriority,
+}
+
+impl<'a> Waiter<'a> {
+    fn register(bulkhead: &'a Bulkhead, priority: RequestPriority) -> Self {
+        bulkhead.update_waiting(priority, true);
+        Self { bulkhead, priority }
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        self.bulkhead.update_waiting(self.priority, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn interactive_waiter_overtakes_queued_background_work() {
+        let bulkhead = Arc::new(Bulkhead::new(1));
+        let held = bulkhead.try_acquire().unwrap();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let waiter = |priority| {
+            let (bulkhead, order) = (Arc::clone(&bulkhead), Arc::clone(&order));
+            tokio::spawn(async move {
+                let (_permit, yielded) = bulkhead.acquire(priority).await;
+                order.lock().unwrap().push((priority, yielded));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            })
+        };
+        let background = waiter(RequestPriority::Background);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let interactive = waiter(RequestPriority::Interactive);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        drop(held);
+        background.await.unwrap();
+        interactive.await.unwrap();
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![
+                (RequestPriority::Interactive, false),
+                (RequestPriority::Background, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Process-wide record of provider circuits for the daemon state file.
+//!
+//! `ReliableProvider`s with circuit persistence enabled publish their circuit
+//! changes here; the daemon writes [`snapshot`] into its state file and, on the
+//! next start, [`seed`]s this store from it so new providers can restore the
+//! circuits that were still open.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+/// One provider and model's circuit, with its open deadline as wall-clock
+/// time so it survives a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PersistedCircuit {
+    pub provider: String,
+    /// Empty in state files written before circuits were kept per model
+    #[serde(default)]
+    pub model: String,
+    pub consecutive_failures: u32,
+    /// When the circuit half-opens; `None` while it is still closed
+    pub open_until: Option<DateTime<Utc>>,
+    /// Most recent failure; missing in state files from older versions
+    #[serde(default)]
+    pub last_failure_at: Option<DateTime<Utc>>,
+}
+
+impl PersistedCircuit {
+    fn key(&self) -> (String, String) {
+        (self.provider.clone(), self.model.clone())
+    }
+
+    /// Whether the circuit opened before the restart has since half-opened.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.open_until.is_some_and(|until| until <= now)
+    }
+
+    /// Whether the circuit is expired, or closed with a last failure older
+    /// than `max_age`, so its failure count no longer says much.
+    pub fn is_stale(&self, now: DateTime<Utc>, max_age: Duration) -> bool {
+        let aged = || {
+            let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+            self.last_failure_at
+                .is_some_and(|at| now.signed_duration_since(at) > max_age)
+        };
+        self.is_expired(now) || (self.open_until.is_none() && aged())
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    /// Circuits read from the state file at startup
+    seeded: BTreeMap<(String, String), PersistedCircuit>,
+    /// Latest state published by live providers, by provider and model;
+    /// `None` once healthy again
+    live: BTreeMap<(String, String), Option<PersistedCircuit>>,
+}
+
+static STORE: OnceLock<Mutex<Store>> = OnceLock::new();
+
+fn with_store<T>(f: impl FnOnce(&mut Store) -> T) -> T {
+    let store = STORE.get_or_init(Mutex::default);
+    f(&mut store.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Load circuits read from the daemon state file, dropping expired ones and
+/// closed ones whose last failure is older than `max_age`.
+pub fn seed(circuits: Vec<PersistedCircuit>, max_age: Duration) {
+    let now = Utc::now();
+    with_store(|store| {
+        store.seeded = circuits
+            .into_iter()
+            .filter(|circuit| !circuit.is_stale(now, max_age))
+            .map(|circuit| (circuit.key(), circuit))
+            .collect();
+    });
+}
+
+/// Seeded circuits that are still unexpired, for providers being constructed.
+pub fn restored() -> Vec<PersistedCircuit> {
+    let now = Utc::now();
+    with_store(|store| {
+        store
+            .seeded
+            .values()
+            .filter(|circuit| !circuit.is_expired(now))
+            .cloned()
+            .collect()
+    })
+}
+
+/// Record the current circuit for `provider` and `model`; `None` marks it
+/// healthy.
+pub fn publish(provider: &str, model: &str, circuit: Option<PersistedCircuit>) {
+    with_store(|store| {
+        store
+            .live
+            .insert((provider.to_string(), model.to_string()), circuit);
+    });
+}
+
+/// Circuits to write to the state file: live state where a provider has
+/// published any, otherwise the still-unexpired seeded state.
+pub fn snapshot() -> Vec<PersistedCircuit> {
+    let now = Utc::now();
+    with_store(|store| {
+        let mut merged = store.seeded.clone();
+        for (key, circuit) in &store.live {
+            match circuit {
+                Some(circuit) => merged.insert(key.clone(), circuit.clone()),
+                None => merged.remove(key),
+            };
+        }
+        merged
+            .into_values()
+            .filter(|circuit| !circuit.is_expired(now))
+            .collect()
+    })
+}
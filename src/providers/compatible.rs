@@ -2,10 +2,19 @@
 //! Most LLM APIs follow the same `/v1/chat/completions` format.
 //! This module provides a single implementation that works for all of them.
 
-use crate::providers::traits::{ChatMessage, Provider};
+use crate::providers::error::ProviderError;
+use crate::providers::metering::Usage;
+use crate::providers::traits::{
+    self, ChatMessage, ChatOptions, ChatResponse, ConversationMessage, MessageContent, ModelInfo,
+    Provider, SamplingParams,
+};
+use crate::tools::ToolSpec;
 use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
 
 /// A provider that speaks the OpenAI-compatible chat completions API.
 /// Used by: Venice, Vercel AI Gateway, Cloudflare AI Gateway, Moonshot,
@@ -15,7 +24,19 @@ pub struct OpenAiCompatibleProvider {
     pub(crate) base_url: String,
     pub(crate) api_key: Option<String>,
     pub(crate) auth_header: AuthStyle,
+    /// Sent with every request, e.g. an organization or routing header.
+    default_headers: HeaderMap,
     client: Client,
+    last_warmup: Mutex<Option<WarmupTiming>>,
+}
+
+/// Timing breakdown of the last successful [`Provider::warmup`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmupTiming {
+    /// Resolving the base URL host
+    pub dns_ms: f64,
+    /// First request over the shared client: connect, TLS and first byte
+    pub request_ms: f64,
 }
 
 /// How the provider expects the API key to be sent.
@@ -36,10 +57,46 @@ impl OpenAiCompatibleProvider {
             base_url: base_url.trim_end_matches('/').to_string(),
             api_key: api_key.map(ToString::to_string),
             auth_header: auth_style,
+            default_headers: HeaderMap::new(),
             client: super::build_provider_http_client(),
+            last_warmup: Mutex::new(None),
         }
     }
 
+    /// Send `headers` with every request. Headers with an invalid name or
+    /// value are skipped with a warning.
+    #[must_use]
+    pub fn with_default_headers<K, V>(mut self, headers: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (name, value) in headers {
+            let (name, value) = (name.as_ref(), value.as_ref());
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                self.default_headers.insert(name, value);
+            } else {
+                tracing::warn!(
+                    provider = self.name,
+                    header = name,
+                    "Skipping invalid default header"
+                );
+            }
+        }
+        self
+    }
+
+    /// Timing of the last successful warmup, if any.
+    pub fn last_warmup(&self) -> Option<WarmupTiming> {
+        *self
+            .last_warmup
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
     /// Build the full URL for chat completions, detecting if `base_url` already includes the path.
     /// This allows custom providers with non-standard endpoints (e.g., `VolcEngine` ARK uses
     /// `/api/coding/v3/chat/completions` instead of `/v1/chat/completions`).
@@ -52,6 +109,22 @@ impl OpenAiCompatibleProvider {
         }
     }
 
+    /// Build the full URL for the model listing endpoint, next to chat completions.
+    fn models_url(&self) -> String {
+        match self.base_url.strip_suffix("/chat/completions") {
+            Some(prefix) => format!("{prefix}/models"),
+            None => format!("{}/models", self.base_url),
+        }
+    }
+
+    /// Build the full URL for the embeddings endpoint, next to chat completions.
+    fn embeddings_url(&self) -> String {
+        match self.base_url.strip_suffix("/chat/completions") {
+            Some(prefix) => format!("{prefix}/embeddings"),
+            None => format!("{}/embeddings", self.base_url),
+        }
+    }
+
     /// Build the full URL for responses API, detecting if `base_url` already includes the path.
     fn responses_url(&self) -> String {
         // If base_url already contains "responses", use it as-is
@@ -68,22 +141,130 @@ struct ChatRequest {
     model: String,
     messages: Vec<Message>,
     temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(flatten)]
+    sampling: SamplingFields,
+}
+
+/// Chat completions sampling fields beyond `temperature`, left out when
+/// unset so the provider default applies.
+#[derive(Debug, Default, Serialize)]
+pub(super) struct SamplingFields {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+impl From<&SamplingParams> for SamplingFields {
+    fn from(params: &SamplingParams) -> Self {
+        Self {
+            top_p: params.top_p,
+            max_tokens: params.max_tokens,
+            stop: params.stop.clone(),
+            seed: params.seed,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct Message {
     role: String,
-    content: String,
+    content: MessageContent,
 }
 
 #[derive(Debug, Deserialize)]
-struct ApiChatResponse {
+pub(super) struct ApiChatResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<ApiUsage>,
+}
+
+/// Token counts of a chat completion; shared with `OpenRouter`.
+#[derive(Debug, Deserialize)]
+pub(super) struct ApiUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+/// Breakdown of `prompt_tokens`; `cached_tokens` were served from the
+/// upstream prompt cache, which `OpenAI` applies automatically to long
+/// repeated prefixes.
+#[derive(Debug, Deserialize)]
+pub(super) struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u64,
+}
+
+impl From<ApiUsage> for Usage {
+    fn from(usage: ApiUsage) -> Self {
+        let cached = usage
+            .prompt_tokens_details
+            .map_or(0, |details| details.cached_tokens);
+        Self {
+            input_tokens: usage.prompt_tokens.saturating_sub(cached),
+            output_tokens: usage.completion_tokens,
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+        }
+    }
+}
+
+impl ApiChatResponse {
+    /// First choice as structured tool calls and text.
+    pub(super) fn into_tool_response(self, provider: &str) -> anyhow::Result<ChatResponse> {
+        self.into_response(provider, parse_tool_response)
+    }
+
+    /// First choice as text; tool calls are kept in the text as the message's
+    /// JSON so `parse_tool_calls` can still find them.
+    pub(super) fn into_chat_response(self, provider: &str) -> anyhow::Result<ChatResponse> {
+        self.into_response(provider, |message| {
+            let text = if message.tool_calls.as_ref().is_some_and(|t| !t.is_empty()) {
+                serde_json::to_string(&message)
+                    .unwrap_or_else(|_| message.content.unwrap_or_default())
+            } else {
+                message.content.unwrap_or_default()
+            };
+            ChatResponse::from(text)
+        })
+    }
+
+    fn into_response(
+        self,
+        provider: &str,
+        parse: impl FnOnce(ResponseMessage) -> ChatResponse,
+    ) -> anyhow::Result<ChatResponse> {
+        let choice = self
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No response from {provider}"))?;
+        Ok(ChatResponse {
+            usage: self.usage.map(Usage::from),
+            model: self.model,
+            finish_reason: choice.finish_reason,
+            ..parse(choice.message)
+        })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -96,11 +277,160 @@ struct ResponseMessage {
 
 #[derive(Debug, Deserialize, Serialize)]
 struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     #[serde(rename = "type")]
     kind: Option<String>,
     function: Option<Function>,
 }
 
+/// `/embeddings` response; shared with the first-party `OpenAiProvider`.
+#[derive(Debug, Deserialize)]
+pub(super) struct EmbeddingsResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingItem {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+impl EmbeddingsResponse {
+    /// Vectors in input order, which the API does not promise to keep.
+    pub(super) fn into_vectors(
+        mut self,
+        provider: &str,
+        expected: usize,
+    ) -> anyhow::Result<Vec<Vec<f32>>> {
+        if self.data.len() != expected {
+            anyhow::bail!(
+                "{provider} returned {} embeddings for {expected} inputs",
+                self.data.len()
+            );
+        }
+        self.data.sort_by_key(|item| item.index);
+        Ok(self.data.into_iter().map(|item| item.embedding).collect())
+    }
+}
+
+/// Chat completions request carrying function definitions; shared with the
+/// first-party `OpenAiProvider`.
+#[derive(Debug, Serialize)]
+pub(super) struct ToolChatRequest {
+    model: String,
+    messages: Vec<serde_json::Value>,
+    temperature: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(flatten)]
+    sampling: SamplingFields,
+}
+
+/// Wire form of a tool conversation: tool calls ride on the assistant
+/// message and each result is a `tool` message answering its call id.
+fn tool_request_messages(messages: &[ConversationMessage]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|message| match message {
+            ConversationMessage::Chat(chat) => {
+                serde_json::json!({"role": chat.role, "content": chat.content})
+            }
+            ConversationMessage::AssistantToolCalls { text, tool_calls } => {
+                let calls: Vec<_> = tool_calls
+                    .iter()
+                    .map(|call| {
+                        serde_json::json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": {"name": call.name, "arguments": call.arguments},
+                        })
+                    })
+                    .collect();
+                serde_json::json!({"role": "assistant", "content": text, "tool_calls": calls})
+            }
+            ConversationMessage::ToolResult(result) => serde_json::json!({
+                "role": "tool",
+                "tool_call_id": result.tool_call_id,
+                "content": result.content,
+            }),
+        })
+        .collect()
+}
+
+impl ToolChatRequest {
+    pub(super) fn new(
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> Self {
+        Self {
+            model: model.to_string(),
+            messages: tool_request_messages(messages),
+            temperature,
+            tools: tool_definitions(tools),
+            sampling: SamplingFields::default(),
+        }
+    }
+
+    /// Plain conversation request honoring every field of `params`.
+    pub(super) fn with_params(
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> Self {
+        let conversation: Vec<_> = messages
+            .iter()
+            .cloned()
+            .map(ConversationMessage::Chat)
+            .collect();
+        let mut request = Self::new(&conversation, &[], model, params.temperature);
+        request.sampling = params.into();
+        request
+    }
+}
+
+fn tool_definitions(tools: &[ToolSpec]) -> Vec<serde_json::Value> {
+    tools
+        .iter()
+        .map(|tool| {
+            serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Structured calls from a response message; calls without a function name
+/// are dropped.
+fn parse_tool_response(message: ResponseMessage) -> ChatResponse {
+    let tool_calls = message
+        .tool_calls
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|call| {
+            let function = call.function?;
+            Some(traits::ToolCall {
+                id: call.id.unwrap_or_default(),
+                name: function.name.filter(|name| !name.is_empty())?,
+                arguments: function.arguments.unwrap_or_else(|| "{}".into()),
+            })
+        })
+        .collect();
+    ChatResponse {
+        text: message.content.filter(|text| !text.is_empty()),
+        tool_calls,
+        ..ChatResponse::default()
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Function {
     name: Option<String>,
@@ -144,6 +474,46 @@ struct ResponsesContent {
     text: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct ModelsResponse {
+    #[serde(default)]
+    data: Vec<ModelEntry>,
+}
+
+/// One `/models` entry. Plain `OpenAI` only returns `id`; aggregators such as
+/// `OpenRouter` also report context length, parameters and input modalities.
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    id: String,
+    #[serde(default, alias = "context_length")]
+    context_window: Option<u32>,
+    #[serde(default)]
+    supported_parameters: Vec<String>,
+    #[serde(default)]
+    architecture: Option<ModelArchitecture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelArchitecture {
+    #[serde(default)]
+    input_modalities: Vec<String>,
+}
+
+fn parse_models_response(response: ModelsResponse) -> Vec<ModelInfo> {
+    response
+        .data
+        .into_iter()
+        .map(|entry| ModelInfo {
+            supports_tools: entry.supported_parameters.iter().any(|p| p == "tools"),
+            supports_vision: entry
+                .architecture
+                .is_some_and(|a| a.input_modalities.iter().any(|m| m == "image")),
+            context_window: entry.context_window,
+            id: entry.id,
+        })
+        .collect()
+}
+
 fn first_nonempty(text: Option<&str>) -> Option<String> {
     text.and_then(|value| {
         let trimmed = value.trim();
@@ -182,11 +552,13 @@ fn extract_responses_text(response: &ResponsesResponse) -> Option<String> {
 }
 
 impl OpenAiCompatibleProvider {
+    /// Add the default headers and the API key in this provider's style.
     fn apply_auth_header(
         &self,
         req: reqwest::RequestBuilder,
         api_key: &str,
     ) -> reqwest::RequestBuilder {
+        let req = req.headers(self.default_headers.clone());
         match &self.auth_header {
             AuthStyle::Bearer => req.header("Authorization", format!("Bearer {api_key}")),
             AuthStyle::XApiKey => req.header("x-api-key", api_key),
@@ -213,14 +585,17 @@ impl OpenAiCompatibleProvider {
 
         let url = self.responses_url();
 
-        let response = self
-            .apply_auth_header(self.client.post(&url).json(&request), api_key)
-            .send()
-            .await?;
+        let req = self.apply_auth_header(self.client.post(&url).json(&request), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
+            let status = response.status();
             let error = response.text().await?;
-            anyhow::bail!("{} Responses API error: {error}", self.name);
+            return Err(ProviderError::from_status(
+                status,
+                format!("{} Responses API error: {error}", self.name),
+            )
+            .into());
         }
 
         let responses: ResponsesResponse = response.json().await?;
@@ -228,16 +603,15 @@ impl OpenAiCompatibleProvider {
         extract_responses_text(&responses)
             .ok_or_else(|| anyhow::anyhow!("No response from {} Responses API", self.name))
     }
-}
 
-#[async_trait]
-impl Provider for OpenAiCompatibleProvider {
-    async fn chat_with_system(
+    /// One chat-completions call, asking for a JSON object when `json_output`.
+    async fn chat_completion(
         &self,
         system_prompt: Option<&str>,
         message: &str,
         model: &str,
-        temperature: f64,
+        params: &SamplingParams,
+        json_output: bool,
     ) -> anyhow::Result<String> {
         let api_key = self.api_key.as_ref().ok_or_else(|| {
             anyhow::anyhow!(
@@ -251,27 +625,27 @@ impl Provider for OpenAiCompatibleProvider {
         if let Some(sys) = system_prompt {
             messages.push(Message {
                 role: "system".to_string(),
-                content: sys.to_string(),
+                content: sys.into(),
             });
         }
 
         messages.push(Message {
             role: "user".to_string(),
-            content: message.to_string(),
+            content: message.into(),
         });
 
         let request = ChatRequest {
             model: model.to_string(),
             messages,
-            temperature,
+            temperature: params.temperature,
+            response_format: json_output.then(|| serde_json::json!({"type": "json_object"})),
+            sampling: params.into(),
         };
 
         let url = self.chat_completions_url();
 
-        let response = self
-            .apply_auth_header(self.client.post(&url).json(&request), api_key)
-            .send()
-            .await?;
+        let req = self.apply_auth_header(self.client.post(&url).json(&request), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -283,36 +657,147 @@ impl Provider for OpenAiCompatibleProvider {
                     .chat_via_responses(api_key, system_prompt, message, model)
                     .await
                     .map_err(|responses_err| {
-                        anyhow::anyhow!(
-                            "{} API error ({status}): {sanitized} (chat completions unavailable; responses fallback failed: {responses_err})",
-                            self.name
+                        ProviderError::from_status(
+                            status,
+                            format!(
+                                "{} API error ({status}): {sanitized} (chat completions unavailable; responses fallback failed: {responses_err})",
+                                self.name
+                            ),
                         )
+                        .into()
                     });
             }
 
-            anyhow::bail!("{} API error ({status}): {sanitized}", self.name);
+            return Err(ProviderError::from_status(
+                status,
+                format!("{} API error ({status}): {sanitized}", self.name),
+            )
+            .into());
         }
 
         let chat_response: ApiChatResponse = response.json().await?;
-
         chat_response
-            .choices
-            .into_iter()
-            .next()
-            .map(|c| {
-                // If tool_calls are present, serialize the full message as JSON
-                // so parse_tool_calls can handle the OpenAI-style format
-                if c.message.tool_calls.is_some()
-                    && c.message.tool_calls.as_ref().is_some_and(|t| !t.is_empty())
-                {
-                    serde_json::to_string(&c.message)
-                        .unwrap_or_else(|_| c.message.content.unwrap_or_default())
-                } else {
-                    // No tool calls, return content as-is
-                    c.message.content.unwrap_or_default()
-                }
-            })
-            .ok_or_else(|| anyhow::anyhow!("No response from {}", self.name))
+            .into_chat_response(&self.name)?
+            .into_text(&self.name)
+    }
+}
+
+#[async_trait]
+impl Provider for OpenAiCompatibleProvider {
+    /// Resolve the base URL host, then send a lightweight `/models` request
+    /// over the shared client so its pool holds a live keep-alive connection
+    /// (DNS, TCP and TLS done) for the first real call. Any HTTP status counts
+    /// as warm; only resolution and transport failures are errors.
+    async fn warmup(&self) -> anyhow::Result<()> {
+        let url = reqwest::Url::parse(&self.base_url)?;
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow::anyhow!("{} base URL has no host", self.name))?;
+        let port = url.port_or_known_default().unwrap_or(443);
+
+        let dns_t0 = Instant::now();
+        let resolved = tokio::net::lookup_host((host, port)).await?.count();
+        if resolved == 0 {
+            anyhow::bail!("{} host {host} did not resolve", self.name);
+        }
+        let dns_ms = dns_t0.elapsed().as_secs_f64() * 1000.0;
+
+        let request_t0 = Instant::now();
+        let mut req = self
+            .client
+            .get(self.models_url())
+            .headers(self.default_headers.clone());
+        if let Some(api_key) = &self.api_key {
+            req = self.apply_auth_header(req, api_key);
+        }
+        // Drain the body so the connection goes back to the pool.
+        req.send().await?.bytes().await?;
+        let request_ms = request_t0.elapsed().as_secs_f64() * 1000.0;
+
+        tracing::debug!(
+            provider = self.name,
+            dns_ms,
+            request_ms,
+            "Provider warmed up"
+        );
+        *self
+            .last_warmup
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(WarmupTiming { dns_ms, request_ms });
+        Ok(())
+    }
+
+    async fn chat_with_system(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        self.chat_completion(
+            system_prompt,
+            message,
+            model,
+            &SamplingParams::new(temperature),
+            false,
+        )
+        .await
+    }
+
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_completion(system_prompt, message, model, params, false)
+            .await
+    }
+
+    /// Honors `options.json_output` with the `json_object` response format.
+    async fn chat_with_options(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+        options: &ChatOptions,
+    ) -> anyhow::Result<String> {
+        self.chat_completion(
+            system_prompt,
+            message,
+            model,
+            &SamplingParams::new(temperature),
+            options.json_output,
+        )
+        .await
+    }
+
+    async fn list_models(&self) -> anyhow::Result<Vec<ModelInfo>> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} API key not set. Run `crabclaw onboard` or set the appropriate env var.",
+                self.name
+            )
+        })?;
+
+        let req = self.apply_auth_header(self.client.get(self.models_url()), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error = response.text().await?;
+            let sanitized = super::sanitize_api_error(&error);
+            return Err(ProviderError::from_status(
+                status,
+                format!("{} models API error ({status}): {sanitized}", self.name),
+            )
+            .into());
+        }
+
+        let models: ModelsResponse = response.json().await?;
+        Ok(parse_models_response(models))
     }
 
     async fn chat_with_history(
@@ -321,6 +806,27 @@ impl Provider for OpenAiCompatibleProvider {
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
+        self.chat_with_history_params(messages, model, &SamplingParams::new(temperature))
+            .await
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text(&self.name)
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
         let api_key = self.api_key.as_ref().ok_or_else(|| {
             anyhow::anyhow!(
                 "{} API key not set. Run `crabclaw onboard` or set the appropriate env var.",
@@ -339,14 +845,14 @@ impl Provider for OpenAiCompatibleProvider {
         let request = ChatRequest {
             model: model.to_string(),
             messages: api_messages,
-            temperature,
+            temperature: params.temperature,
+            response_format: None,
+            sampling: params.into(),
         };
 
         let url = self.chat_completions_url();
-        let response = self
-            .apply_auth_header(self.client.post(&url).json(&request), api_key)
-            .send()
-            .await?;
+        let req = self.apply_auth_header(self.client.post(&url).json(&request), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -360,49 +866,92 @@ impl Provider for OpenAiCompatibleProvider {
                     return self
                         .chat_via_responses(
                             api_key,
-                            system.map(|m| m.content.as_str()),
-                            &user_msg.content,
+                            system.map(ChatMessage::text).as_deref(),
+                            &user_msg.text(),
                             model,
                         )
                         .await
+                        .map(ChatResponse::from)
                         .map_err(|responses_err| {
-                            anyhow::anyhow!(
-                                "{} API error (chat completions unavailable; responses fallback failed: {responses_err})",
-                                self.name
+                            ProviderError::from_status(
+                                status,
+                                format!(
+                                    "{} API error (chat completions unavailable; responses fallback failed: {responses_err})",
+                                    self.name
+                                ),
                             )
+                            .into()
                         });
                 }
             }
 
-            return Err(super::api_error(&self.name, response).await);
+            return Err(super::api_error(&self.name, response).await.into());
         }
 
         let chat_response: ApiChatResponse = response.json().await?;
+        chat_response.into_chat_response(&self.name)
+    }
 
-        chat_response
-            .choices
-            .into_iter()
-            .next()
-            .map(|c| {
-                // If tool_calls are present, serialize the full message as JSON
-                // so parse_tool_calls can handle the OpenAI-style format
-                if c.message.tool_calls.is_some()
-                    && c.message.tool_calls.as_ref().is_some_and(|t| !t.is_empty())
-                {
-                    serde_json::to_string(&c.message)
-                        .unwrap_or_else(|_| c.message.content.unwrap_or_default())
-                } else {
-                    // No tool calls, return content as-is
-                    c.message.content.unwrap_or_default()
-                }
-            })
-            .ok_or_else(|| anyhow::anyhow!("No response from {}", self.name))
+    async fn embed(&self, texts: &[String], model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} API key not set. Run `crabclaw onboard` or set the appropriate env var.",
+                self.name
+            )
+        })?;
+
+        let body = serde_json::json!({"model": model, "input": texts});
+        let url = self.embeddings_url();
+        let req = self.apply_auth_header(self.client.post(&url).json(&body), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error(&self.name, response).await.into());
+        }
+
+        let embeddings: EmbeddingsResponse = response.json().await?;
+        embeddings.into_vectors(&self.name, texts.len())
+    }
+
+    /// Sends `tools` as function definitions and returns the model's
+    /// structured tool calls alongside any text.
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<ChatResponse> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} API key not set. Run `crabclaw onboard` or set the appropriate env var.",
+                self.name
+            )
+        })?;
+
+        let request = ToolChatRequest::new(messages, tools, model, temperature);
+
+        let url = self.chat_completions_url();
+        let req = self.apply_auth_header(self.client.post(&url).json(&request), api_key);
+        let response = super::context::apply_request_id(req).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error(&self.name, response).await.into());
+        }
+
+        let chat_response: ApiChatResponse = response.json().await?;
+        chat_response.into_tool_response(&self.name)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
 
     fn make_provider(name: &str, url: &str, key: Option<&str>) -> OpenAiCompatibleProvider {
         OpenAiCompatibleProvider::new(name, url, key, AuthStyle::Bearer)
@@ -448,19 +997,38 @@ mod tests {
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: "You are CrabClaw".to_string(),
+                    content: "You are CrabClaw".into(),
                 },
                 Message {
                     role: "user".to_string(),
-                    content: "hello".to_string(),
+                    content: "hello".into(),
                 },
             ],
             temperature: 0.7,
+            response_format: None,
+            sampling: SamplingFields::default(),
         };
         let json = serde_json::to_string(&req).unwrap();
         assert!(json.contains("llama-3.3-70b"));
+        assert!(!json.contains("max_tokens"));
         assert!(json.contains("system"));
         assert!(json.contains("user"));
+        assert!(!json.contains("response_format"));
+
+        let params = SamplingParams {
+            top_p: Some(0.5),
+            max_tokens: Some(64),
+            stop: vec!["\n\n".into()],
+            seed: Some(42),
+            ..SamplingParams::new(0.7)
+        };
+        let req = ToolChatRequest::with_params(&[ChatMessage::user("hi")], "gpt-4o", &params);
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["top_p"], 0.5);
+        assert_eq!(json["max_tokens"], 64);
+        assert_eq!(json["stop"], serde_json::json!(["\n\n"]));
+        assert_eq!(json["seed"], 42);
+        assert!(json.get("tools").is_none());
     }
 
     #[test]
@@ -473,6 +1041,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn response_carries_usage_model_and_finish_reason() {
+        let json = r#"{"model":"gpt-4o-2024-08-06",
+            "choices":[{"message":{"content":"Hi"},"finish_reason":"length"}],
+            "usage":{"prompt_tokens":1200,"completion_tokens":1,"total_tokens":1201,
+                "prompt_tokens_details":{"cached_tokens":1024}}}"#;
+        let resp: ApiChatResponse = serde_json::from_str(json).unwrap();
+        let resp = resp.into_chat_response("test").unwrap();
+        assert_eq!(resp.text.as_deref(), Some("Hi"));
+        assert_eq!(resp.model.as_deref(), Some("gpt-4o-2024-08-06"));
+        assert_eq!(resp.finish_reason.as_deref(), Some("length"));
+        assert_eq!(
+            resp.usage,
+            Some(Usage {
+                input_tokens: 176,
+                output_tokens: 1,
+                cache_read_tokens: 1024,
+                cache_write_tokens: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn tool_conversation_maps_to_function_calling_wire_format() {
+        let messages = vec![
+            ConversationMessage::Chat(ChatMessage::user("list files")),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                tool_calls: vec![traits::ToolCall {
+                    id: "call_1".into(),
+                    name: "shell".into(),
+                    arguments: r#"{"command":"ls"}"#.into(),
+                }],
+            },
+            ConversationMessage::ToolResult(traits::ToolResultMessage {
+                tool_call_id: "call_1".into(),
+                content: "a.txt".into(),
+            }),
+        ];
+        let wire = tool_request_messages(&messages);
+        assert_eq!(wire[1]["tool_calls"][0]["function"]["name"], "shell");
+        assert_eq!(wire[2]["role"], "tool");
+        assert_eq!(wire[2]["tool_call_id"], "call_1");
+
+        let tools = tool_definitions(&[ToolSpec {
+            name: "shell".into(),
+            description: "Run a command".into(),
+            parameters: serde_json::json!({"type": "object"}),
+        }]);
+        assert_eq!(tools[0]["type"], "function");
+        assert_eq!(tools[0]["function"]["parameters"]["type"], "object");
+
+        let json = r#"{"choices":[{"message":{"content":null,"tool_calls":[
+            {"id":"call_2","type":"function","function":{"name":"shell","arguments":"{\"command\":\"pwd\"}"}}
+        ]}}]}"#;
+        let resp: ApiChatResponse = serde_json::from_str(json).unwrap();
+        let parsed = parse_tool_response(resp.choices.into_iter().next().unwrap().message);
+        assert_eq!(parsed.text, None);
+        assert_eq!(parsed.tool_calls[0].id, "call_2");
+        assert_eq!(parsed.tool_calls[0].arguments, r#"{"command":"pwd"}"#);
+    }
+
+    #[test]
+    fn embeddings_come_back_in_input_order() {
+        let p = make_provider("test", "https://api.example.com/v1/chat/completions", None);
+        assert_eq!(p.embeddings_url(), "https://api.example.com/v1/embeddings");
+
+        let json = r#"{"data":[
+            {"index":1,"embedding":[0.0,1.0]},
+            {"index":0,"embedding":[1.0,0.0]}
+        ]}"#;
+        let resp: EmbeddingsResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            resp.into_vectors("test", 2).unwrap(),
+            vec![vec![1.0, 0.0], vec![0.0, 1.0]]
+        );
+        let resp: EmbeddingsResponse = serde_json::from_str(json).unwrap();
+        assert!(resp.into_vectors("test", 3).is_err());
+    }
+
     #[test]
     fn response_empty_choices() {
         let json = r#"{"choices":[]}"#;
@@ -560,6 +1208,61 @@ mod tests {
     // Custom endpoint path tests (Issue #114)
     // ══════════════════════════════════════════════════════════
 
+    #[test]
+    fn models_response_parses_openai_and_openrouter_shapes() {
+        let json = r#"{
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o-mini", "object": "model", "owned_by": "openai"},
+                {
+                    "id": "anthropic/claude-sonnet-4",
+                    "context_length": 200000,
+                    "supported_parameters": ["temperature", "tools"],
+                    "architecture": {"input_modalities": ["text", "image"]}
+                }
+            ]
+        }"#;
+        let models = parse_models_response(serde_json::from_str(json).unwrap());
+
+        assert_eq!(models.len(), 2);
+        assert_eq!(
+            models[0],
+            ModelInfo {
+                id: "gpt-4o-mini".into(),
+                context_window: None,
+                supports_tools: false,
+                supports_vision: false,
+            }
+        );
+        assert_eq!(models[1].id, "anthropic/claude-sonnet-4");
+        assert_eq!(models[1].context_window, Some(200_000));
+        assert!(models[1].supports_tools);
+        assert!(models[1].supports_vision);
+    }
+
+    #[test]
+    fn models_url_sits_next_to_chat_completions() {
+        let p = make_provider("test", "https://api.example.com/v1", None);
+        assert_eq!(p.models_url(), "https://api.example.com/v1/models");
+
+        let p = make_provider(
+            "volcengine",
+            "https://ark.cn-beijing.volces.com/api/coding/v3/chat/completions",
+            None,
+        );
+        assert_eq!(
+            p.models_url(),
+            "https://ark.cn-beijing.volces.com/api/coding/v3/models"
+        );
+    }
+
+    #[tokio::test]
+    async fn list_models_fails_without_key() {
+        let p = make_provider("Venice", "https://api.venice.ai", None);
+        let err = p.list_models().await.unwrap_err();
+        assert!(err.to_string().contains("Venice API key not set"));
+    }
+
     #[test]
     fn chat_completions_url_standard_openai() {
         // Standard OpenAI-compatible providers get /chat/completions appended
@@ -682,4 +1385,57 @@ mod tests {
             "https://opencode.ai/zen/v1/chat/completions"
         );
     }
+
+    /// Chat completions stand-in: answers `/models` with an empty list and
+    /// anything else with a chat completion. Returns its base URL and a
+    /// counter of accepted TCP connections.
+    async fn mock_openai_server() -> (String, Arc<AtomicUsize>) {
+        let (address, connections) = super::super::mock_http::serve(|path, _| {
+            let reply = if path.contains("/models") {
+                r#"{"data":[]}"#
+            } else {
+                r#"{"choices":[{"message":{"content":"warm hello"}}]}"#
+            };
+            ("200 OK", reply.to_string())
+        })
+        .await;
+        (format!("{address}/v1"), connections)
+    }
+
+    #[tokio::test]
+    async fn warmup_records_timing_and_keeps_the_connection_for_chat() {
+        let (base_url, connections) = mock_openai_server().await;
+        let p = make_provider("mock", &base_url, Some("key"));
+        assert!(p.last_warmup().is_none());
+
+        p.warmup().await.unwrap();
+        p.warmup().await.unwrap();
+        let timing = p.last_warmup().unwrap();
+        assert!(timing.dns_ms >= 0.0 && timing.request_ms > 0.0);
+
+        let reply = p.chat("hi", "m", 0.0).await.unwrap();
+        assert_eq!(reply, "warm hello");
+        // Both warmups and the chat reuse the one pooled connection.
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn default_headers_ride_with_every_request() {
+        let p = make_provider("vllm", "http://localhost:8000/v1", Some("key"))
+            .with_default_headers([("X-Team", "infra"), ("bad header", "x")]);
+        let request = p
+            .apply_auth_header(p.client.get(p.models_url()), "key")
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-team"], "infra");
+        assert_eq!(request.headers()["authorization"], "Bearer key");
+        assert_eq!(request.headers().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn warmup_fails_for_unreachable_host() {
+        let p = make_provider("down", "http://127.0.0.1:1/v1", None);
+        assert!(p.warmup().await.is_err());
+        assert!(p.last_warmup().is_none());
+    }
 }
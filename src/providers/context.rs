@@ -0,0 +1,160 @@
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// Header used to forward the request id to upstream HTTP providers.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// How urgently a request should be served once a provider's concurrency
+/// limit is reached; waiting requests are admitted highest priority first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RequestPriority {
+    /// Heartbeat tasks, memory compaction and other work nobody waits on.
+    Background,
+    /// Agent-loop turns that feed tool results back to the model.
+    ToolCall,
+    /// A user waiting on a chat reply.
+    #[default]
+    Interactive,
+}
+
+/// Per-request context propagated through the provider stack.
+///
+/// The context lives in a task-local so it reaches every layer (reliable
+/// wrapper, router, HTTP providers) without changing the `Provider` trait.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    /// End user or tenant the request is billed to, for metering.
+    pub tenant_id: Option<String>,
+    /// Conversation the request belongs to, for spend tracking.
+    pub session_id: Option<String>,
+    /// Channel the conversation came in on, for spend tracking and alerts.
+    pub channel: Option<String>,
+    pub priority: RequestPriority,
+    /// Cancelled once the caller no longer wants the answer, e.g. the user
+    /// sent a newer message; `ReliableProvider` then stops retrying.
+    pub cancel: Option<CancellationToken>,
+}
+
+impl RequestContext {
+    /// Create a context with a freshly generated request id.
+    pub fn new() -> Self {
+        Self::with_request_id(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Create a context carrying a caller-supplied request id.
+    pub fn with_request_id(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            tenant_id: None,
+            session_id: None,
+            channel: None,
+            priority: RequestPriority::default(),
+            cancel: None,
+        }
+    }
+
+    /// Attribute this request's usage to `tenant_id`.
+    pub fn with_tenant_id(mut self, tenant_id: impl Into<String>) -> Self {
+        self.tenant_id = Some(tenant_id.into());
+        self
+    }
+
+    /// Attribute this request's spend to conversation `session_id`.
+    pub fn with_session_id(mut self, session_id: impl Into<String>) -> Self {
+        self.session_id = Some(session_id.into());
+        self
+    }
+
+    /// Attribute this request's spend to `channel`.
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// Schedule this request at `priority` behind a saturated provider.
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Give up on this request once `token` is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Whether the caller has cancelled this request.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// The context of the current task, if one is in scope.
+    pub fn current() -> Option<Self> {
+        REQUEST_CONTEXT.try_with(Clone::clone).ok()
+    }
+
+    /// The context of the current task, or a new one when none is in scope.
+    pub fn current_or_new() -> Self {
+        Self::current().unwrap_or_default()
+    }
+
+    /// Run `fut` with this context in scope.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        REQUEST_CONTEXT.scope(self, fut).await
+    }
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Attach the current request id (if any) to an outbound provider request.
+pub(crate) fn apply_request_id(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match RequestContext::current() {
+        Some(ctx) => req.header(REQUEST_ID_HEADER, ctx.request_id),
+        None => req,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn current_is_none_outside_scope() {
+        assert!(RequestContext::current().is_none());
+    }
+
+    #[tokio::test]
+    async fn scope_exposes_context() {
+        let ctx = RequestContext::with_request_id("req-123");
+        let seen = ctx
+            .scope(async { RequestContext::current().map(|c| c.request_id) })
+            .await;
+        assert_eq!(seen.as_deref(), Some("req-123"));
+    }
+
+    #[tokio::test]
+    async fn apply_request_id_sets_header_in_scope() {
+        let client = reqwest::Client::new();
+        let request = RequestContext::with_request_id("req-456")
+            .scope(async { apply_request_id(client.post("http://localhost")).build() })
+            .await
+            .unwrap();
+        assert_eq!(request.headers().get(REQUEST_ID_HEADER).unwrap(), "req-456");
+
+        let request = apply_request_id(client.post("http://localhost"))
+            .build()
+            .unwrap();
+        assert!(request.headers().get(REQUEST_ID_HEADER).is_none());
+    }
+}
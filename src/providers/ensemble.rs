@@ -0,0 +1,340 @@
+use super::traits::ChatMessage;
+use super::Provider;
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const JUDGE_SYSTEM_PROMPT: &str = "You are judging candidate answers to the same question. \
+Reply with the single best final answer, without mentioning the candidates.";
+
+/// How an [`EnsembleProvider`] turns its members' answers into one response.
+pub enum CombineStrategy {
+    /// The answer of the first member, in order, that succeeded.
+    FirstSuccess,
+    /// The most common answer, compared after trimming and lowercasing; ties
+    /// go to the earliest member.
+    MajorityVote,
+    /// Every successful answer, labelled with its member's name.
+    Concatenate,
+    /// Ask this provider to pick or synthesize the best answer.
+    JudgedBy(Box<dyn Provider>),
+}
+
+/// Counters for one ensemble member.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnsembleMemberStats {
+    pub name: String,
+    pub successes: u64,
+    pub failures: u64,
+    /// Successful answers that matched the majority answer
+    pub agreements: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnsembleStats {
+    pub requests: u64,
+    /// Requests where every member answered and all answers matched
+    pub unanimous: u64,
+    pub members: Vec<EnsembleMemberStats>,
+}
+
+#[derive(Default)]
+struct MemberCounters {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    agreements: AtomicU64,
+}
+
+/// Sends every request to all members concurrently and combines their
+/// answers with a [`CombineStrategy`].
+///
+/// Unlike `ReliableProvider`, which falls back only when a provider fails,
+/// this always queries every member, trading cost for answer quality on
+/// quality-critical queries. It fails only when every member fails.
+pub struct EnsembleProvider {
+    members: Vec<(String, Box<dyn Provider>)>,
+    strategy: CombineStrategy,
+    requests: AtomicU64,
+    unanimous: AtomicU64,
+    counters: Vec<MemberCounters>,
+}
+
+/// Key answers are compared by for voting and agreement.
+fn vote_key(answer: &str) -> String {
+    answer.trim().to_lowercase()
+}
+
+impl EnsembleProvider {
+    pub fn new(members: Vec<(String, Box<dyn Provider>)>, strategy: CombineStrategy) -> Self {
+        let counters = members.iter().map(|_| MemberCounters::default()).collect();
+        Self {
+            members,
+            strategy,
+            requests: AtomicU64::new(0),
+            unanimous: AtomicU64::new(0),
+            counters,
+        }
+    }
+
+    pub fn stats(&self) -> EnsembleStats {
+        EnsembleStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            unanimous: self.unanimous.load(Ordering::Relaxed),
+            members: self
+                .members
+                .iter()
+                .zip(&self.counters)
+                .map(|((name, _), counters)| EnsembleMemberStats {
+                    name: name.clone(),
+                    successes: counters.successes.load(Ordering::Relaxed),
+                    failures: counters.failures.load(Ordering::Relaxed),
+                    agreements: counters.agreements.load(Ordering::Relaxed),
+                })
+                .collect(),
+        }
+    }
+
+    /// Record per-member outcomes and return the successful answers with
+    /// their member index, in member order.
+    fn tally(&self, results: Vec<anyhow::Result<String>>) -> anyhow::Result<Vec<(usize, String)>> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let mut answers = Vec::new();
+        let mut errors = Vec::new();
+        for (idx, result) in results.into_iter().enumerate() {
+            let counters = &self.counters[idx];
+            match result {
+                Ok(answer) => {
+                    counters.successes.fetch_add(1, Ordering::Relaxed);
+                    answers.push((idx, answer));
+                }
+                Err(e) => {
+                    counters.failures.fetch_add(1, Ordering::Relaxed);
+                    tracing::warn!(member = self.members[idx].0, "Ensemble member failed: {e}");
+                    errors.push(format!("{}: {e}", self.members[idx].0));
+                }
+            }
+        }
+        if answers.is_empty() {
+            anyhow::bail!("All ensemble members failed: {}", errors.join("; "));
+        }
+
+        let majority = Self::majority(&answers);
+        let majority_key = vote_key(&answers[majority].1);
+        let mut agreeing = 0;
+        for (idx, answer) in &answers {
+            if vote_key(answer) == majority_key {
+                agreeing += 1;
+                self.counters[*idx]
+                    .agreements
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if agreeing == self.members.len() {
+            self.unanimous.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(answers)
+    }
+
+    /// Position in `answers` of the first answer with the most votes.
+    fn majority(answers: &[(usize, String)]) -> usize {
+        let mut votes: HashMap<String, usize> = HashMap::new();
+        for (_, answer) in answers {
+            *votes.entry(vote_key(answer)).or_default() += 1;
+        }
+        let mut best = 0;
+        for (pos, (_, answer)) in answers.iter().enumerate() {
+            if votes[&vote_key(answer)] > votes[&vote_key(&answers[best].1)] {
+                best = pos;
+            }
+        }
+        best
+    }
+
+    async fn combine(
+        &self,
+        question: &str,
+        model: &str,
+        temperature: f64,
+        results: Vec<anyhow::Result<String>>,
+    ) -> anyhow::Result<String> {
+        let mut answers = self.tally(results)?;
+        match &self.strategy {
+            CombineStrategy::FirstSuccess => Ok(answers.swap_remove(0).1),
+            CombineStrategy::MajorityVote => {
+                let best = Self::majority(&answers);
+                Ok(answers.swap_remove(best).1)
+            }
+            CombineStrategy::Concatenate => Ok(answers
+                .iter()
+                .map(|(idx, answer)| format!("[{}]\n{answer}", self.members[*idx].0))
+                .collect::<Vec<_>>()
+                .join("\n\n")),
+            CombineStrategy::JudgedBy(judge) => {
+                let mut prompt = format!("Question:\n{question}\n");
+                for (n, (_, answer)) in answers.iter().enumerate() {
+                    let _ = write!(prompt, "\nCandidate {}:\n{answer}\n", n + 1);
+                }
+                judge
+                    .chat_with_system(Some(JUDGE_SYSTEM_PROMPT), &prompt, model, temperature)
+                    .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for EnsembleProvider {
+    async fn chat_with_system(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let results = join_all(self.members.iter().map(|(_, provider)| {
+            provider.chat_with_system(system_prompt, message, model, temperature)
+        }))
+        .await;
+        self.combine(message, model, temperature, results).await
+    }
+
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let results = join_all(
+            self.members
+                .iter()
+                .map(|(_, provider)| provider.chat_with_history(messages, model, temperature)),
+        )
+        .await;
+        let question = messages
+            .iter()
+            .rfind(|m| m.role == "user")
+            .map(ChatMessage::text)
+            .unwrap_or_default();
+        self.combine(&question, model, temperature, results).await
+    }
+
+    async fn warmup(&self) -> anyhow::Result<()> {
+        for (name, provider) in &self.members {
+            if let Err(e) = provider.warmup().await {
+                tracing::warn!(member = name, "Warmup failed (non-fatal): {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers with a fixed reply (or fails when `None`), recording prompts.
+    struct FixedProvider {
+        reply: Option<&'static str>,
+        prompts: Arc<Mutex<Vec<String>>>,
+    }
+
+    fn fixed(reply: Option<&'static str>) -> Box<dyn Provider> {
+        Box::new(FixedProvider {
+            reply,
+            prompts: Arc::default(),
+        })
+    }
+
+    #[async_trait]
+    impl Provider for FixedProvider {
+        async fn chat_with_system(
+            &self,
+            _system_prompt: Option<&str>,
+            message: &str,
+            _model: &str,
+            _temperature: f64,
+        ) -> anyhow::Result<String> {
+            self.prompts.lock().unwrap().push(message.to_string());
+            self.reply
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("member down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn majority_vote_picks_the_most_common_answer() {
+        let ensemble = EnsembleProvider::new(
+            vec![
+                ("a".into(), fixed(Some("negative"))),
+                ("b".into(), fixed(Some("Positive"))),
+                ("c".into(), fixed(Some("positive\n"))),
+            ],
+            CombineStrategy::MajorityVote,
+        );
+
+        let answer = ensemble.chat("classify: great!", "m", 0.0).await.unwrap();
+        assert_eq!(answer, "Positive");
+
+        let stats = ensemble.stats();
+        assert_eq!(stats.requests, 1);
+        assert_eq!(stats.unanimous, 0);
+        let agreements: Vec<u64> = stats.members.iter().map(|m| m.agreements).collect();
+        assert_eq!(agreements, vec![0, 1, 1]);
+        assert!(stats.members.iter().all(|m| m.successes == 1));
+    }
+
+    #[tokio::test]
+    async fn judge_sees_every_successful_candidate() {
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let judge = Box::new(FixedProvider {
+            reply: Some("Paris"),
+            prompts: Arc::clone(&prompts),
+        });
+        let ensemble = EnsembleProvider::new(
+            vec![
+                ("a".into(), fixed(Some("Paris, France"))),
+                ("b".into(), fixed(None)),
+                ("c".into(), fixed(Some("It is Paris"))),
+            ],
+            CombineStrategy::JudgedBy(judge),
+        );
+
+        let history = [ChatMessage::user("Capital of France?")];
+        let answer = ensemble
+            .chat_with_history(&history, "m", 0.0)
+            .await
+            .unwrap();
+        assert_eq!(answer, "Paris");
+
+        let prompt = prompts.lock().unwrap().pop().unwrap();
+        assert!(prompt.contains("Capital of France?"));
+        assert!(prompt.contains("Candidate 1:\nParis, France"));
+        assert!(prompt.contains("Candidate 2:\nIt is Paris"));
+        assert_eq!(ensemble.stats().members[1].failures, 1);
+    }
+
+    #[tokio::test]
+    async fn concatenate_and_total_failure() {
+        let ensemble = EnsembleProvider::new(
+            vec![
+                ("a".into(), fixed(Some("one"))),
+                ("b".into(), fixed(Some("two"))),
+            ],
+            CombineStrategy::Concatenate,
+        );
+        assert_eq!(
+            ensemble.chat("q", "m", 0.0).await.unwrap(),
+            "[a]\none\n\n[b]\ntwo"
+        );
+
+        let down = EnsembleProvider::new(
+            vec![("a".into(), fixed(None)), ("b".into(), fixed(None))],
+            CombineStrategy::FirstSuccess,
+        );
+        let err = down.chat("q", "m", 0.0).await.unwrap_err();
+        assert!(err.to_string().contains("All ensemble members failed"));
+    }
+}
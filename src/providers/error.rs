@@ -0,0 +1,138 @@
+//! Typed provider failures, so retry and circuit-breaker decisions never
+//! depend on digits that happen to appear in an error message.
+
+use reqwest::StatusCode;
+
+/// Why a provider call failed. Built-in providers return these (inside
+/// `anyhow::Error`) for HTTP error responses; `Display` is the provider's
+/// sanitized message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProviderError {
+    /// 429: the provider is shedding load; worth retrying after a backoff.
+    RateLimited { message: String },
+    /// The request timed out, at the provider (408) or on the client.
+    Timeout { message: String },
+    /// 401/403: bad or missing credentials. Retrying cannot help, and the
+    /// provider is unusable until reconfigured.
+    Auth { status: u16, message: String },
+    /// Any other 4xx: the request itself is at fault.
+    BadRequest { status: u16, message: String },
+    /// 5xx: the provider failed to serve a valid request.
+    ServerError { status: u16, message: String },
+    /// Connect, DNS or transport failure before a response arrived.
+    Network { message: String },
+    /// The caller gave up on the request.
+    Canceled,
+}
+
+impl ProviderError {
+    /// Classify an HTTP error response.
+    pub fn from_status(status: StatusCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let code = status.as_u16();
+        match code {
+            429 => Self::RateLimited { message },
+            408 => Self::Timeout { message },
+            401 | 403 => Self::Auth {
+                status: code,
+                message,
+            },
+            400..=499 => Self::BadRequest {
+                status: code,
+                message,
+            },
+            _ => Self::ServerError {
+                status: code,
+                message,
+            },
+        }
+    }
+
+    /// Classify a `reqwest` failure, which may or may not carry a status.
+    pub fn from_reqwest(err: &reqwest::Error) -> Self {
+        let message = err.to_string();
+        if err.is_timeout() {
+            Self::Timeout { message }
+        } else if let Some(status) = err.status() {
+            Self::from_status(status, message)
+        } else {
+            Self::Network { message }
+        }
+    }
+
+    /// HTTP status behind the failure, where there was one.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::RateLimited { .. } => Some(429),
+            Self::Auth { status, .. }
+            | Self::BadRequest { status, .. }
+            | Self::ServerError { status, .. } => Some(*status),
+            Self::Timeout { .. } | Self::Network { .. } | Self::Canceled => None,
+        }
+    }
+
+    /// Whether the same request may succeed on a later attempt.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RateLimited { .. }
+                | Self::Timeout { .. }
+                | Self::ServerError { .. }
+                | Self::Network { .. }
+        )
+    }
+
+    /// Whether the failure says something about the provider's health. Bad
+    /// requests and cancellations are the caller's doing and leave its
+    /// circuit alone.
+    pub fn counts_against_circuit(&self) -> bool {
+        !matches!(self, Self::BadRequest { .. } | Self::Canceled)
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RateLimited { message }
+            | Self::Timeout { message }
+            | Self::Auth { message, .. }
+            | Self::BadRequest { message, .. }
+            | Self::ServerError { message, .. }
+            | Self::Network { message } => f.write_str(message),
+            Self::Canceled => f.write_str("Request canceled"),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_map_to_variants() {
+        let classify = |code| ProviderError::from_status(StatusCode::from_u16(code).unwrap(), "x");
+        assert!(matches!(classify(429), ProviderError::RateLimited { .. }));
+        assert!(matches!(classify(408), ProviderError::Timeout { .. }));
+        assert!(matches!(classify(403), ProviderError::Auth { .. }));
+        assert!(matches!(classify(422), ProviderError::BadRequest { .. }));
+        assert!(matches!(classify(502), ProviderError::ServerError { .. }));
+
+        assert!(classify(429).is_retryable());
+        assert!(classify(503).is_retryable());
+        assert!(!classify(401).is_retryable());
+        assert!(!classify(400).is_retryable());
+        assert_eq!(classify(429).status(), Some(429));
+        assert_eq!(classify(408).status(), None);
+    }
+
+    #[test]
+    fn only_provider_side_failures_count_against_the_circuit() {
+        let auth = ProviderError::from_status(StatusCode::UNAUTHORIZED, "bad key");
+        let bad = ProviderError::from_status(StatusCode::BAD_REQUEST, "context too long");
+        assert!(auth.counts_against_circuit());
+        assert!(!bad.counts_against_circuit());
+        assert!(!ProviderError::Canceled.counts_against_circuit());
+    }
+}
@@ -0,0 +1,579 @@
+use super::context::RequestContext;
+use super::metering::Usage;
+use super::traits::{
+    ChatMessage, ChatOptions, ChatResponse, ConversationMessage, ModelInfo, SamplingParams,
+};
+use super::Provider;
+use crate::tools::ToolSpec;
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Instant;
+
+/// Requests whose arm is remembered for [`ExperimentProvider::arm_for`] and
+/// feedback; older ones are forgotten.
+const ASSIGNMENT_HISTORY: usize = 4096;
+
+/// Spend is kept in nano-dollars so it can live in an atomic.
+const NANOS_PER_USD: f64 = 1e9;
+
+/// Which side of an experiment served a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExperimentArm {
+    /// The provider and model requests normally go to.
+    Control,
+    /// The alternate provider or model under evaluation.
+    Treatment,
+}
+
+impl ExperimentArm {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Control => "control",
+            Self::Treatment => "treatment",
+        }
+    }
+}
+
+impl std::fmt::Display for ExperimentArm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcomes of the requests one arm served.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArmStats {
+    pub arm: ExperimentArm,
+    pub requests: u64,
+    pub failures: u64,
+    /// Mean latency of all requests, failed ones included
+    pub avg_latency_ms: f64,
+    /// Spend priced by the installed cost tracker, from reported usage or an
+    /// estimate when the provider reports none
+    pub cost_usd: f64,
+    pub positive_feedback: u64,
+    pub negative_feedback: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentStats {
+    pub name: String,
+    pub treatment_percent: u8,
+    pub control: ArmStats,
+    pub treatment: ArmStats,
+}
+
+#[derive(Default)]
+struct ArmCounters {
+    requests: AtomicU64,
+    failures: AtomicU64,
+    latency_ms_total: AtomicU64,
+    cost_nanos: AtomicU64,
+    positive: AtomicU64,
+    negative: AtomicU64,
+}
+
+impl ArmCounters {
+    #[allow(clippy::cast_precision_loss)]
+    fn snapshot(&self, arm: ExperimentArm) -> ArmStats {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let latency_ms_total = self.latency_ms_total.load(Ordering::Relaxed);
+        ArmStats {
+            arm,
+            requests,
+            failures: self.failures.load(Ordering::Relaxed),
+            avg_latency_ms: if requests == 0 {
+                0.0
+            } else {
+                latency_ms_total as f64 / requests as f64
+            },
+            cost_usd: self.cost_nanos.load(Ordering::Relaxed) as f64 / NANOS_PER_USD,
+            positive_feedback: self.positive.load(Ordering::Relaxed),
+            negative_feedback: self.negative.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Splits traffic between a control provider and a treatment provider or
+/// model, so a cheaper model can be evaluated on a slice of real requests.
+///
+/// `treatment_percent` of requests go to the treatment arm. Assignment hashes
+/// the request's session id (falling back to its request id), so every turn
+/// of a conversation stays on one arm. Each arm's latency, cost and feedback
+/// are aggregated in [`Self::stats`]; [`Self::arm_for`] tells which arm
+/// served a recent request.
+pub struct ExperimentProvider {
+    name: String,
+    control: Box<dyn Provider>,
+    treatment: Box<dyn Provider>,
+    /// Model the treatment arm is asked for; `None` keeps the request's model.
+    treatment_model: Option<String>,
+    treatment_percent: u8,
+    counters: [ArmCounters; 2],
+    assignments: Mutex<VecDeque<(String, ExperimentArm)>>,
+}
+
+impl ExperimentProvider {
+    /// Experiment `name` sending `treatment_percent` (capped at 100) of
+    /// requests to `treatment` and the rest to `control`.
+    pub fn new(
+        name: impl Into<String>,
+        control: Box<dyn Provider>,
+        treatment: Box<dyn Provider>,
+        treatment_percent: u8,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            control,
+            treatment,
+            treatment_model: None,
+            treatment_percent: treatment_percent.min(100),
+            counters: Default::default(),
+            assignments: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Ask the treatment arm for `model` instead of the requested model.
+    pub fn with_treatment_model(mut self, model: impl Into<String>) -> Self {
+        self.treatment_model = Some(model.into());
+        self
+    }
+
+    pub fn stats(&self) -> ExperimentStats {
+        ExperimentStats {
+            name: self.name.clone(),
+            treatment_percent: self.treatment_percent,
+            control: self.counters[0].snapshot(ExperimentArm::Control),
+            treatment: self.counters[1].snapshot(ExperimentArm::Treatment),
+        }
+    }
+
+    /// Arm that served request `request_id`, if it is recent enough to be
+    /// remembered.
+    pub fn arm_for(&self, request_id: &str) -> Option<ExperimentArm> {
+        self.assignments
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .rev()
+            .find(|(id, _)| id == request_id)
+            .map(|(_, arm)| *arm)
+    }
+
+    /// Credit a user's verdict on request `request_id` to the arm that served
+    /// it. Returns `false` when the request is unknown or forgotten.
+    pub fn record_feedback(&self, request_id: &str, positive: bool) -> bool {
+        let Some(arm) = self.arm_for(request_id) else {
+            return false;
+        };
+        let counters = self.counters(arm);
+        if positive {
+            counters.positive.fetch_add(1, Ordering::Relaxed);
+        } else {
+            counters.negative.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+
+    fn counters(&self, arm: ExperimentArm) -> &ArmCounters {
+        match arm {
+            ExperimentArm::Control => &self.counters[0],
+            ExperimentArm::Treatment => &self.counters[1],
+        }
+    }
+
+    /// Arm for the key's bucket in `0..100`, salted with the experiment name
+    /// so separate experiments split traffic independently.
+    fn arm_for_key(&self, key: &str) -> ExperimentArm {
+        let digest = Sha256::digest(format!("{}:{key}", self.name).as_bytes());
+        let mut head = [0u8; 8];
+        head.copy_from_slice(&digest[..8]);
+        if u64::from_be_bytes(head) % 100 < u64::from(self.treatment_percent) {
+            ExperimentArm::Treatment
+        } else {
+            ExperimentArm::Control
+        }
+    }
+
+    /// Pick the arm for the current request, remember it, and return the
+    /// provider and model to call.
+    fn assign<'a>(&'a self, model: &'a str) -> (ExperimentArm, &'a dyn Provider, &'a str) {
+        let ctx = RequestContext::current_or_new();
+        let arm = self.arm_for_key(ctx.session_id.as_deref().unwrap_or(&ctx.request_id));
+        {
+            let mut assignments = self
+                .assignments
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if assignments.len() == ASSIGNMENT_HISTORY {
+                assignments.pop_front();
+            }
+            assignments.push_back((ctx.request_id.clone(), arm));
+        }
+        tracing::debug!(
+            experiment = self.name.as_str(),
+            arm = arm.as_str(),
+            request_id = ctx.request_id.as_str(),
+            "Experiment assigned request"
+        );
+        match arm {
+            ExperimentArm::Control => (arm, self.control.as_ref(), model),
+            ExperimentArm::Treatment => (
+                arm,
+                self.treatment.as_ref(),
+                self.treatment_model.as_deref().unwrap_or(model),
+            ),
+        }
+    }
+
+    /// Count a finished request against `arm`. `usage` is `None` for a
+    /// failure.
+    fn record(&self, arm: ExperimentArm, model: &str, started: Instant, usage: Option<Usage>) {
+        let counters = self.counters(arm);
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        counters.latency_ms_total.fetch_add(
+            u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+        let Some(usage) = usage else {
+            counters.failures.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        if let Some(tracker) = crate::cost::tracker() {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let nanos = (tracker.cost(model, &usage) * NANOS_PER_USD).round() as u64;
+            counters.cost_nanos.fetch_add(nanos, Ordering::Relaxed);
+        }
+    }
+
+    /// Record a string reply, estimating its usage from `input_chars`.
+    fn record_text(
+        &self,
+        arm: ExperimentArm,
+        model: &str,
+        started: Instant,
+        input_chars: usize,
+        result: &anyhow::Result<String>,
+    ) {
+        let usage = result
+            .as_ref()
+            .ok()
+            .map(|text| Usage::estimate(input_chars, text));
+        self.record(arm, model, started, usage);
+    }
+
+    /// Record a structured reply, using its reported usage when present.
+    fn record_response(
+        &self,
+        arm: ExperimentArm,
+        model: &str,
+        started: Instant,
+        input_chars: usize,
+        result: &anyhow::Result<ChatResponse>,
+    ) {
+        let usage = result.as_ref().ok().map(|response| {
+            response
+                .usage
+                .unwrap_or_else(|| Usage::estimate(input_chars, response.text_or_empty()))
+        });
+        self.record(arm, model, started, usage);
+    }
+}
+
+fn prompt_chars(system_prompt: Option<&str>, message: &str) -> usize {
+    system_prompt.map_or(0, |s| s.chars().count()) + message.chars().count()
+}
+
+fn history_chars(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(|m| m.text().chars().count()).sum()
+}
+
+#[async_trait]
+impl Provider for ExperimentProvider {
+    async fn chat_with_system(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_system(system_prompt, message, model, temperature)
+            .await;
+        let input_chars = prompt_chars(system_prompt, message);
+        self.record_text(arm, model, started, input_chars, &result);
+        result
+    }
+
+    async fn chat_with_options(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+        options: &ChatOptions,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_options(system_prompt, message, model, temperature, options)
+            .await;
+        let input_chars = prompt_chars(system_prompt, message);
+        self.record_text(arm, model, started, input_chars, &result);
+        result
+    }
+
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_params(system_prompt, message, model, params)
+            .await;
+        let input_chars = prompt_chars(system_prompt, message);
+        self.record_text(arm, model, started, input_chars, &result);
+        result
+    }
+
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_history(messages, model, temperature)
+            .await;
+        self.record_text(arm, model, started, history_chars(messages), &result);
+        result
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_history_params(messages, model, params)
+            .await;
+        self.record_text(arm, model, started, history_chars(messages), &result);
+        result
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider.chat_response(messages, model, params).await;
+        self.record_response(arm, model, started, history_chars(messages), &result);
+        result
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+        chunks: &tokio::sync::mpsc::Sender<String>,
+    ) -> anyhow::Result<String> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_stream(messages, model, temperature, chunks)
+            .await;
+        self.record_text(arm, model, started, history_chars(messages), &result);
+        result
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<ChatResponse> {
+        let (arm, provider, model) = self.assign(model);
+        let started = Instant::now();
+        let result = provider
+            .chat_with_tools(messages, tools, model, temperature)
+            .await;
+        let input_chars = history_chars(&ConversationMessage::to_chat_messages(messages));
+        self.record_response(arm, model, started, input_chars, &result);
+        result
+    }
+
+    /// Embeddings are not part of the experiment and always use control.
+    async fn embed(&self, texts: &[String], model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        self.control.embed(texts, model).await
+    }
+
+    async fn list_models(&self) -> anyhow::Result<Vec<ModelInfo>> {
+        self.control.list_models().await
+    }
+
+    async fn warmup(&self) -> anyhow::Result<()> {
+        for (arm, provider) in [
+            (ExperimentArm::Control, &self.control),
+            (ExperimentArm::Treatment, &self.treatment),
+        ] {
+            if let Err(e) = provider.warmup().await {
+                tracing::warn!(arm = arm.as_str(), "Warmup failed (non-fatal): {e}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Answers with its own label (or fails), recording the models asked for.
+    struct LabelProvider {
+        label: &'static str,
+        fail: bool,
+        models: Arc<Mutex<Vec<String>>>,
+    }
+
+    fn labelled(label: &'static str, models: &Arc<Mutex<Vec<String>>>) -> Box<dyn Provider> {
+        Box::new(LabelProvider {
+            label,
+            fail: false,
+            models: Arc::clone(models),
+        })
+    }
+
+    #[async_trait]
+    impl Provider for LabelProvider {
+        async fn chat_with_system(
+            &self,
+            _system_prompt: Option<&str>,
+            _message: &str,
+            model: &str,
+            _temperature: f64,
+        ) -> anyhow::Result<String> {
+            self.models.lock().unwrap().push(model.to_string());
+            if self.fail {
+                anyhow::bail!("{} down", self.label);
+            }
+            Ok(self.label.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn splits_traffic_by_percentage_and_tracks_each_arm() {
+        let models = Arc::new(Mutex::new(Vec::new()));
+        let experiment = ExperimentProvider::new(
+            "cheap-trial",
+            labelled("control", &models),
+            labelled("treatment", &models),
+            30,
+        )
+        .with_treatment_model("small-model");
+
+        let mut treated = 0;
+        for n in 0..400 {
+            let ctx = RequestContext::with_request_id(format!("req-{n}"));
+            let answer = ctx.scope(experiment.chat("hi", "big-model", 0.0)).await;
+            let arm = experiment.arm_for(&format!("req-{n}")).unwrap();
+            assert_eq!(answer.unwrap(), arm.as_str());
+            if arm == ExperimentArm::Treatment {
+                treated += 1;
+            }
+        }
+        assert!((80..=160).contains(&treated), "treated {treated} of 400");
+
+        let models = models.lock().unwrap();
+        assert_eq!(
+            models.iter().filter(|m| *m == "small-model").count(),
+            treated
+        );
+        assert_eq!(
+            models.iter().filter(|m| *m == "big-model").count(),
+            400 - treated
+        );
+
+        let stats = experiment.stats();
+        assert_eq!(stats.treatment.requests, treated as u64);
+        assert_eq!(stats.control.requests + stats.treatment.requests, 400);
+        assert_eq!(stats.control.failures, 0);
+    }
+
+    #[tokio::test]
+    async fn sessions_stay_on_one_arm_and_feedback_is_credited_to_it() {
+        let models = Arc::new(Mutex::new(Vec::new()));
+        let experiment = ExperimentProvider::new(
+            "sticky",
+            labelled("control", &models),
+            Box::new(LabelProvider {
+                label: "treatment",
+                fail: true,
+                models: Arc::clone(&models),
+            }),
+            50,
+        );
+
+        let mut arms = Vec::new();
+        for turn in 0..5 {
+            let id = format!("turn-{turn}");
+            let ctx = RequestContext::with_request_id(id.clone()).with_session_id("conv-1");
+            let _ = ctx.scope(experiment.chat("hi", "m", 0.0)).await;
+            arms.push(experiment.arm_for(&id).unwrap());
+        }
+        assert!(arms.iter().all(|arm| *arm == arms[0]));
+
+        assert!(experiment.record_feedback("turn-0", true));
+        assert!(experiment.record_feedback("turn-1", false));
+        assert!(!experiment.record_feedback("unknown", true));
+
+        let stats = experiment.stats();
+        let served = match arms[0] {
+            ExperimentArm::Control => &stats.control,
+            ExperimentArm::Treatment => &stats.treatment,
+        };
+        assert_eq!(served.requests, 5);
+        assert_eq!(served.positive_feedback, 1);
+        assert_eq!(served.negative_feedback, 1);
+        let expected_failures = if arms[0] == ExperimentArm::Treatment {
+            5
+        } else {
+            0
+        };
+        assert_eq!(served.failures, expected_failures);
+    }
+
+    #[tokio::test]
+    async fn zero_percent_sends_everything_to_control() {
+        let models = Arc::new(Mutex::new(Vec::new()));
+        let experiment = ExperimentProvider::new(
+            "off",
+            labelled("control", &models),
+            labelled("treatment", &models),
+            0,
+        );
+        for _ in 0..20 {
+            assert_eq!(experiment.chat("hi", "m", 0.0).await.unwrap(), "control");
+        }
+        assert_eq!(experiment.stats().treatment.requests, 0);
+    }
+}
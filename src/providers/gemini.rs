@@ -3,7 +3,11 @@
 //! - Gemini CLI OAuth tokens (reuse existing ~/.gemini/ authentication)
 //! - Google Cloud ADC (`GOOGLE_APPLICATION_CREDENTIALS`)
 
-use crate::providers::traits::Provider;
+use crate::providers::error::ProviderError;
+use crate::providers::metering::Usage;
+use crate::providers::traits::{
+    ChatMessage, ChatResponse, ContentPart, MessageContent, Provider, SamplingParams,
+};
 use async_trait::async_trait;
 use directories::UserDirs;
 use reqwest::Client;
@@ -13,9 +17,27 @@ use std::path::PathBuf;
 /// Gemini provider supporting multiple authentication methods.
 pub struct GeminiProvider {
     auth: Option<GeminiAuth>,
+    safety_settings: Vec<SafetySetting>,
     client: Client,
 }
 
+/// Blocking threshold for one harm category, sent as-is in `safetySettings`.
+/// See Google's docs for the category and threshold names, e.g.
+/// `HARM_CATEGORY_HARASSMENT` and `BLOCK_ONLY_HIGH`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SafetySetting {
+    pub category: String,
+    pub threshold: String,
+}
+
+/// Harm categories `GEMINI_SAFETY_THRESHOLD` applies to.
+const HARM_CATEGORIES: [&str; 4] = [
+    "HARM_CATEGORY_HARASSMENT",
+    "HARM_CATEGORY_HATE_SPEECH",
+    "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+    "HARM_CATEGORY_DANGEROUS_CONTENT",
+];
+
 /// Resolved credential — the variant determines both the HTTP auth method
 /// and the diagnostic label returned by `auth_source()`.
 #[derive(Debug)]
@@ -61,6 +83,8 @@ struct GenerateContentRequest {
     system_instruction: Option<Content>,
     #[serde(rename = "generationConfig")]
     generation_config: GenerationConfig,
+    #[serde(rename = "safetySettings", skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<SafetySetting>,
 }
 
 #[derive(Debug, Serialize)]
@@ -70,9 +94,19 @@ struct Content {
     parts: Vec<Part>,
 }
 
+/// Serializes as `{"text": ...}` or `{"inline_data": {...}}`.
 #[derive(Debug, Serialize)]
-struct Part {
-    text: String,
+#[serde(rename_all = "snake_case")]
+enum Part {
+    Text(String),
+    InlineData(Blob),
+}
+
+#[derive(Debug, Serialize)]
+struct Blob {
+    mime_type: String,
+    /// Base64-encoded bytes
+    data: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -80,24 +114,72 @@ struct GenerationConfig {
     temperature: f64,
     #[serde(rename = "maxOutputTokens")]
     max_output_tokens: u32,
+    #[serde(rename = "topP", skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(rename = "stopSequences", skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+impl From<&SamplingParams> for GenerationConfig {
+    fn from(params: &SamplingParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            max_output_tokens: params.max_tokens.unwrap_or(8192),
+            top_p: params.top_p,
+            stop_sequences: params.stop.clone(),
+            seed: params.seed,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct GenerateContentResponse {
     candidates: Option<Vec<Candidate>>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+    #[serde(rename = "modelVersion")]
+    model_version: Option<String>,
     error: Option<ApiError>,
 }
 
+#[derive(Debug, Deserialize)]
+struct UsageMetadata {
+    #[serde(rename = "promptTokenCount", default)]
+    prompt_token_count: u64,
+    /// Absent when nothing was generated.
+    #[serde(rename = "candidatesTokenCount", default)]
+    candidates_token_count: u64,
+    /// Part of `promptTokenCount` served from cached content.
+    #[serde(rename = "cachedContentTokenCount", default)]
+    cached_content_token_count: u64,
+}
+
 #[derive(Debug, Deserialize)]
 struct Candidate {
+    /// Missing when the candidate was blocked before producing anything.
+    #[serde(default)]
     content: CandidateContent,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct CandidateContent {
+    #[serde(default)]
     parts: Vec<ResponsePart>,
 }
 
+/// Set when the prompt itself was blocked and no candidates were generated.
+#[derive(Debug, Deserialize)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct ResponsePart {
     text: Option<String>,
@@ -105,9 +187,81 @@ struct ResponsePart {
 
 #[derive(Debug, Deserialize)]
 struct ApiError {
+    code: Option<u16>,
     message: String,
 }
 
+impl GenerateContentResponse {
+    /// The first candidate with its usage, or the failure its finish reason
+    /// implies.
+    /// Content the filters blocked fails the same way on every attempt, so it
+    /// is a bad request (the next provider may answer); an unexplained stop
+    /// is a server fault worth retrying.
+    fn into_response(self) -> Result<ChatResponse, ProviderError> {
+        if let Some(err) = self.error {
+            let status = err
+                .code
+                .and_then(|code| reqwest::StatusCode::from_u16(code).ok())
+                .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(ProviderError::from_status(
+                status,
+                format!("Gemini API error: {}", err.message),
+            ));
+        }
+        let blocked = |reason: &str| ProviderError::BadRequest {
+            status: 400,
+            message: format!("Gemini blocked the response ({reason})"),
+        };
+        let Some(candidate) = self.candidates.and_then(|c| c.into_iter().next()) else {
+            return Err(match self.prompt_feedback.and_then(|f| f.block_reason) {
+                Some(reason) => blocked(&reason),
+                None => ProviderError::ServerError {
+                    status: 500,
+                    message: "No response from Gemini".into(),
+                },
+            });
+        };
+
+        let text: String = candidate
+            .content
+            .parts
+            .into_iter()
+            .filter_map(|p| p.text)
+            .collect();
+        match candidate.finish_reason.as_deref() {
+            Some(
+                reason @ ("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII"
+                | "IMAGE_SAFETY" | "LANGUAGE"),
+            ) => Err(blocked(reason)),
+            _ if !text.is_empty() => {
+                if candidate.finish_reason.as_deref() == Some("MAX_TOKENS") {
+                    tracing::warn!("Gemini response was cut off at the output token limit");
+                }
+                Ok(ChatResponse {
+                    usage: self.usage_metadata.map(|usage| Usage {
+                        input_tokens: usage
+                            .prompt_token_count
+                            .saturating_sub(usage.cached_content_token_count),
+                        output_tokens: usage.candidates_token_count,
+                        cache_read_tokens: usage.cached_content_token_count,
+                        cache_write_tokens: 0,
+                    }),
+                    model: self.model_version,
+                    finish_reason: candidate.finish_reason,
+                    ..ChatResponse::from(text)
+                })
+            }
+            reason => Err(ProviderError::ServerError {
+                status: 500,
+                message: format!(
+                    "No response from Gemini (finish reason {})",
+                    reason.unwrap_or("unknown")
+                ),
+            }),
+        }
+    }
+}
+
 // ══════════════════════════════════════════════════════════════════════════════
 // GEMINI CLI TOKEN STRUCTURES
 // ══════════════════════════════════════════════════════════════════════════════
@@ -127,6 +281,9 @@ impl GeminiProvider {
     /// 2. `GEMINI_API_KEY` environment variable
     /// 3. `GOOGLE_API_KEY` environment variable
     /// 4. Gemini CLI OAuth tokens (`~/.gemini/oauth_creds.json`)
+    ///
+    /// `GEMINI_SAFETY_THRESHOLD` (e.g. `BLOCK_ONLY_HIGH`) sets the blocking
+    /// threshold of the standard harm categories.
     pub fn new(api_key: Option<&str>) -> Self {
         let resolved_auth = api_key
             .and_then(Self::normalize_non_empty)
@@ -135,8 +292,21 @@ impl GeminiProvider {
             .or_else(|| Self::load_non_empty_env("GOOGLE_API_KEY").map(GeminiAuth::EnvGoogleKey))
             .or_else(|| Self::try_load_gemini_cli_token().map(GeminiAuth::OAuthToken));
 
+        let safety_settings = Self::load_non_empty_env("GEMINI_SAFETY_THRESHOLD")
+            .map(|threshold| {
+                HARM_CATEGORIES
+                    .iter()
+                    .map(|category| SafetySetting {
+                        category: (*category).to_string(),
+                        threshold: threshold.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Self {
             auth: resolved_auth,
+            safety_settings,
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(120))
                 .connect_timeout(std::time::Duration::from_secs(10))
@@ -145,6 +315,13 @@ impl GeminiProvider {
         }
     }
 
+    /// Send `settings` with every request, replacing any from the environment.
+    #[must_use]
+    pub fn with_safety_settings(mut self, settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
     fn normalize_non_empty(value: &str) -> Option<String> {
         let trimmed = value.trim();
         if trimmed.is_empty() {
@@ -238,13 +415,76 @@ impl GeminiProvider {
         }
     }
 
+    fn auth(&self) -> anyhow::Result<&GeminiAuth> {
+        self.auth.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Gemini API key not found. Options:\n\
+                 1. Set GEMINI_API_KEY env var\n\
+                 2. Run `gemini` CLI to authenticate (tokens will be reused)\n\
+                 3. Get an API key from https://aistudio.google.com/app/apikey\n\
+                 4. Run `crabclaw onboard` to configure"
+            )
+        })
+    }
+
+    /// Send `request` to `generateContent` and return the first candidate.
+    async fn generate(
+        &self,
+        auth: &GeminiAuth,
+        model: &str,
+        request: &GenerateContentRequest,
+    ) -> anyhow::Result<ChatResponse> {
+        let url = Self::build_generate_content_url(model, auth);
+
+        let response = self
+            .build_generate_content_request(auth, &url, request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ProviderError::from_status(
+                status,
+                format!("Gemini API error ({status}): {error_text}"),
+            )
+            .into());
+        }
+
+        let result: GenerateContentResponse = response.json().await?;
+        Ok(result.into_response()?)
+    }
+
+    /// Gemini content for one message: text, plus images and audio sent as
+    /// inline data. Gemini cannot fetch arbitrary image URLs, so those are
+    /// dropped.
+    fn message_parts(content: &MessageContent) -> Vec<Part> {
+        let MessageContent::Parts(parts) = content else {
+            return vec![Part::Text(content.text().into_owned())];
+        };
+        parts
+            .iter()
+            .filter_map(|part| match (part, part.inline_data()) {
+                (ContentPart::Text(text), _) => Some(Part::Text(text.clone())),
+                (_, Some((mime_type, data))) => Some(Part::InlineData(Blob {
+                    mime_type: mime_type.into_owned(),
+                    data: data.to_string(),
+                })),
+                (_, None) => {
+                    tracing::warn!("Gemini needs inline image data; dropping image URL");
+                    None
+                }
+            })
+            .collect()
+    }
+
     fn build_generate_content_request(
         &self,
         auth: &GeminiAuth,
         url: &str,
         request: &GenerateContentRequest,
     ) -> reqwest::RequestBuilder {
-        let req = self.client.post(url).json(request);
+        let req = super::context::apply_request_id(self.client.post(url).json(request));
         match auth {
             GeminiAuth::OAuthToken(token) => req.bearer_auth(token),
             _ => req,
@@ -261,65 +501,104 @@ impl Provider for GeminiProvider {
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
-        let auth = self.auth.as_ref().ok_or_else(|| {
-            anyhow::anyhow!(
-                "Gemini API key not found. Options:\n\
-                 1. Set GEMINI_API_KEY env var\n\
-                 2. Run `gemini` CLI to authenticate (tokens will be reused)\n\
-                 3. Get an API key from https://aistudio.google.com/app/apikey\n\
-                 4. Run `crabclaw onboard` to configure"
-            )
-        })?;
+        let auth = self.auth()?;
 
         // Build request
         let system_instruction = system_prompt.map(|sys| Content {
             role: None,
-            parts: vec![Part {
-                text: sys.to_string(),
-            }],
+            parts: vec![Part::Text(sys.to_string())],
         });
 
         let request = GenerateContentRequest {
             contents: vec![Content {
                 role: Some("user".to_string()),
-                parts: vec![Part {
-                    text: message.to_string(),
-                }],
+                parts: vec![Part::Text(message.to_string())],
             }],
             system_instruction,
-            generation_config: GenerationConfig {
-                temperature,
-                max_output_tokens: 8192,
-            },
+            generation_config: GenerationConfig::from(&SamplingParams::new(temperature)),
+            safety_settings: self.safety_settings.clone(),
         };
 
-        let url = Self::build_generate_content_url(model, auth);
+        self.generate(auth, model, &request)
+            .await?
+            .into_text("Gemini")
+    }
 
-        let response = self
-            .build_generate_content_request(auth, &url, &request)
-            .send()
-            .await?;
+    /// Every message in order, with system messages moved into the system
+    /// instruction and images and audio sent inline.
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        self.chat_with_history_params(messages, model, &SamplingParams::new(temperature))
+            .await
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Gemini API error ({status}): {error_text}");
-        }
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let mut messages: Vec<ChatMessage> =
+            system_prompt.map(ChatMessage::system).into_iter().collect();
+        messages.push(ChatMessage::user(message));
+        self.chat_with_history_params(&messages, model, params)
+            .await
+    }
 
-        let result: GenerateContentResponse = response.json().await?;
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text("Gemini")
+    }
 
-        // Check for API error in response body
-        if let Some(err) = result.error {
-            anyhow::bail!("Gemini API error: {}", err.message);
-        }
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
+        let auth = self.auth()?;
+        let system_parts: Vec<Part> = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .flat_map(|m| Self::message_parts(&m.content))
+            .collect();
+        let contents = messages
+            .iter()
+            .filter(|m| m.role != "system")
+            .map(|m| Content {
+                role: Some(
+                    if m.role == "assistant" {
+                        "model"
+                    } else {
+                        "user"
+                    }
+                    .to_string(),
+                ),
+                parts: Self::message_parts(&m.content),
+            })
+            .collect();
 
-        // Extract text from response
-        result
-            .candidates
-            .and_then(|c| c.into_iter().next())
-            .and_then(|c| c.content.parts.into_iter().next())
-            .and_then(|p| p.text)
-            .ok_or_else(|| anyhow::anyhow!("No response from Gemini"))
+        let request = GenerateContentRequest {
+            contents,
+            system_instruction: (!system_parts.is_empty()).then_some(Content {
+                role: None,
+                parts: system_parts,
+            }),
+            generation_config: GenerationConfig::from(params),
+            safety_settings: self.safety_settings.clone(),
+        };
+        self.generate(auth, model, &request).await
     }
 }
 
@@ -374,6 +653,7 @@ mod tests {
     fn auth_source_explicit_key() {
         let provider = GeminiProvider {
             auth: Some(GeminiAuth::ExplicitKey("key".into())),
+            safety_settings: Vec::new(),
             client: Client::new(),
         };
         assert_eq!(provider.auth_source(), "config");
@@ -383,6 +663,7 @@ mod tests {
     fn auth_source_none_without_credentials() {
         let provider = GeminiProvider {
             auth: None,
+            safety_settings: Vec::new(),
             client: Client::new(),
         };
         assert_eq!(provider.auth_source(), "none");
@@ -392,6 +673,7 @@ mod tests {
     fn auth_source_oauth() {
         let provider = GeminiProvider {
             auth: Some(GeminiAuth::OAuthToken("ya29.mock".into())),
+            safety_settings: Vec::new(),
             client: Client::new(),
         };
         assert_eq!(provider.auth_source(), "Gemini CLI OAuth");
@@ -428,6 +710,7 @@ mod tests {
     fn oauth_request_uses_bearer_auth_header() {
         let provider = GeminiProvider {
             auth: Some(GeminiAuth::OAuthToken("ya29.mock-token".into())),
+            safety_settings: Vec::new(),
             client: Client::new(),
         };
         let auth = GeminiAuth::OAuthToken("ya29.mock-token".into());
@@ -435,15 +718,11 @@ mod tests {
         let body = GenerateContentRequest {
             contents: vec![Content {
                 role: Some("user".into()),
-                parts: vec![Part {
-                    text: "hello".into(),
-                }],
+                parts: vec![Part::Text("hello".into())],
             }],
             system_instruction: None,
-            generation_config: GenerationConfig {
-                temperature: 0.7,
-                max_output_tokens: 8192,
-            },
+            generation_config: GenerationConfig::from(&SamplingParams::new(0.7)),
+            safety_settings: Vec::new(),
         };
 
         let request = provider
@@ -464,6 +743,7 @@ mod tests {
     fn api_key_request_does_not_set_bearer_header() {
         let provider = GeminiProvider {
             auth: Some(GeminiAuth::ExplicitKey("api-key-123".into())),
+            safety_settings: Vec::new(),
             client: Client::new(),
         };
         let auth = GeminiAuth::ExplicitKey("api-key-123".into());
@@ -471,15 +751,11 @@ mod tests {
         let body = GenerateContentRequest {
             contents: vec![Content {
                 role: Some("user".into()),
-                parts: vec![Part {
-                    text: "hello".into(),
-                }],
+                parts: vec![Part::Text("hello".into())],
             }],
             system_instruction: None,
-            generation_config: GenerationConfig {
-                temperature: 0.7,
-                max_output_tokens: 8192,
-            },
+            generation_config: GenerationConfig::from(&SamplingParams::new(0.7)),
+            safety_settings: Vec::new(),
         };
 
         let request = provider
@@ -492,23 +768,17 @@ mod tests {
 
     #[test]
     fn request_serialization() {
-        let request = GenerateContentRequest {
+        let mut request = GenerateContentRequest {
             contents: vec![Content {
                 role: Some("user".to_string()),
-                parts: vec![Part {
-                    text: "Hello".to_string(),
-                }],
+                parts: vec![Part::Text("Hello".to_string())],
             }],
             system_instruction: Some(Content {
                 role: None,
-                parts: vec![Part {
-                    text: "You are helpful".to_string(),
-                }],
+                parts: vec![Part::Text("You are helpful".to_string())],
             }),
-            generation_config: GenerationConfig {
-                temperature: 0.7,
-                max_output_tokens: 8192,
-            },
+            generation_config: GenerationConfig::from(&SamplingParams::new(0.7)),
+            safety_settings: Vec::new(),
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -516,6 +786,44 @@ mod tests {
         assert!(json.contains("\"text\":\"Hello\""));
         assert!(json.contains("\"temperature\":0.7"));
         assert!(json.contains("\"maxOutputTokens\":8192"));
+        assert!(!json.contains("safetySettings"));
+
+        request.safety_settings.push(SafetySetting {
+            category: "HARM_CATEGORY_HARASSMENT".into(),
+            threshold: "BLOCK_ONLY_HIGH".into(),
+        });
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["safetySettings"],
+            serde_json::json!([{"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_ONLY_HIGH"}])
+        );
+    }
+
+    #[test]
+    fn media_parts_are_sent_inline() {
+        let parts = GeminiProvider::message_parts(&MessageContent::Parts(vec![
+            ContentPart::Text("what is this?".into()),
+            ContentPart::ImageBase64 {
+                media_type: "image/jpeg".into(),
+                data: "/9j/4AAQ".into(),
+            },
+            ContentPart::Audio {
+                format: "mp3".into(),
+                data: "SUQz".into(),
+            },
+            ContentPart::ImageUrl {
+                url: "https://example.com/cat.png".into(),
+                detail: None,
+            },
+        ]));
+        assert_eq!(
+            serde_json::to_value(&parts).unwrap(),
+            serde_json::json!([
+                {"text": "what is this?"},
+                {"inline_data": {"mime_type": "image/jpeg", "data": "/9j/4AAQ"}},
+                {"inline_data": {"mime_type": "audio/mp3", "data": "SUQz"}}
+            ])
+        );
     }
 
     #[test]
@@ -557,4 +865,39 @@ mod tests {
         assert!(response.error.is_some());
         assert_eq!(response.error.unwrap().message, "Invalid API key");
     }
+
+    #[test]
+    fn finish_reasons_map_to_provider_errors() {
+        let parse = |json: &str| {
+            serde_json::from_str::<GenerateContentResponse>(json)
+                .unwrap()
+                .into_response()
+                .map(|response| response.text_or_empty().to_string())
+        };
+
+        let cut_off = parse(
+            r#"{"candidates": [{"content": {"parts": [{"text": "Hel"}, {"text": "lo"}]},
+                "finishReason": "MAX_TOKENS"}]}"#,
+        );
+        assert_eq!(cut_off.unwrap(), "Hello");
+
+        let unsafe_reply = parse(r#"{"candidates": [{"finishReason": "SAFETY"}]}"#).unwrap_err();
+        assert!(matches!(unsafe_reply, ProviderError::BadRequest { .. }));
+        assert!(!unsafe_reply.is_retryable());
+
+        let blocked_prompt =
+            parse(r#"{"promptFeedback": {"blockReason": "PROHIBITED_CONTENT"}}"#).unwrap_err();
+        assert!(blocked_prompt.to_string().contains("PROHIBITED_CONTENT"));
+        assert!(!blocked_prompt.is_retryable());
+
+        let empty = parse(r#"{"candidates": [{"finishReason": "OTHER"}]}"#).unwrap_err();
+        assert!(empty.is_retryable());
+
+        let overloaded =
+            parse(r#"{"error": {"code": 503, "message": "The model is overloaded"}}"#).unwrap_err();
+        assert!(matches!(
+            overloaded,
+            ProviderError::ServerError { status: 503, .. }
+        ));
+    }
 }
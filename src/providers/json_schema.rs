@@ -0,0 +1,294 @@
+//! Validation of JSON values against the subset of JSON Schema that
+//! structured-output schemas use in practice: `type`, `enum`, `const`,
+//! `properties`, `required`, `additionalProperties`, `items`, length and
+//! numeric bounds, `pattern`, and `anyOf`/`oneOf`/`allOf`. Unknown keywords
+//! are ignored, so a schema never fails for using something unsupported.
+
+use serde_json::Value;
+
+/// Check `value` against `schema`, describing the first violation with the
+/// JSON Pointer of the offending value.
+pub(crate) fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    check(schema, value, "")
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        // `true` and `{}` accept anything; `false` accepts nothing.
+        return if schema.as_bool() == Some(false) {
+            Err(violation(path, "no value is allowed here"))
+        } else {
+            Ok(())
+        };
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let allowed: Vec<&str> = match expected {
+            Value::String(kind) => vec![kind.as_str()],
+            Value::Array(kinds) => kinds.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|kind| has_type(value, kind)) {
+            return Err(violation(
+                path,
+                &format!(
+                    "expected {}, found {}",
+                    allowed.join(" or "),
+                    type_name(value)
+                ),
+            ));
+        }
+    }
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(violation(
+                path,
+                &format!("must be one of {}", Value::from(options.clone())),
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(violation(path, &format!("must equal {expected}")));
+        }
+    }
+
+    match value {
+        Value::Object(object) => check_object(schema, object, path)?,
+        Value::Array(items) => check_array(schema, items, path)?,
+        Value::String(text) => check_string(schema, text, path)?,
+        Value::Number(number) => {
+            if let Some(number) = number.as_f64() {
+                check_number(schema, number, path)?;
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all {
+            check(sub, value, path)?;
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(any) = schema.get(keyword).and_then(Value::as_array) {
+            let matched = any
+                .iter()
+                .filter(|sub| check(sub, value, path).is_ok())
+                .count();
+            let ok = if keyword == "oneOf" {
+                matched == 1
+            } else {
+                matched > 0
+            };
+            if !ok {
+                return Err(violation(path, &format!("does not match {keyword}")));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_object(
+    schema: &serde_json::Map<String, Value>,
+    object: &serde_json::Map<String, Value>,
+    path: &str,
+) -> Result<(), String> {
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        if let Some(missing) = required
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|name| !object.contains_key(*name))
+        {
+            return Err(violation(
+                path,
+                &format!("missing required property \"{missing}\""),
+            ));
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, field) in object {
+        let field_path = format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"));
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(field_schema) => check(field_schema, field, &field_path)?,
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    return Err(violation(path, &format!("unexpected property \"{name}\"")));
+                }
+                Some(extra) => check(extra, field, &field_path)?,
+                None => {}
+            },
+        }
+    }
+    Ok(())
+}
+
+fn check_array(
+    schema: &serde_json::Map<String, Value>,
+    items: &[Value],
+    path: &str,
+) -> Result<(), String> {
+    check_len(schema, items.len(), "minItems", "maxItems", "items", path)?;
+    if let Some(item_schema) = schema.get("items") {
+        for (idx, item) in items.iter().enumerate() {
+            check(item_schema, item, &format!("{path}/{idx}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_string(
+    schema: &serde_json::Map<String, Value>,
+    text: &str,
+    path: &str,
+) -> Result<(), String> {
+    check_len(
+        schema,
+        text.chars().count(),
+        "minLength",
+        "maxLength",
+        "characters",
+        path,
+    )?;
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        // An invalid pattern is the schema's fault, not the response's.
+        if let Ok(re) = regex::Regex::new(pattern) {
+            if !re.is_match(text) {
+                return Err(violation(
+                    path,
+                    &format!("does not match pattern {pattern}"),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_number(
+    schema: &serde_json::Map<String, Value>,
+    number: f64,
+    path: &str,
+) -> Result<(), String> {
+    let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    let failed = [
+        ("minimum", bound("minimum").filter(|min| number < *min)),
+        ("maximum", bound("maximum").filter(|max| number > *max)),
+        (
+            "exclusiveMinimum",
+            bound("exclusiveMinimum").filter(|min| number <= *min),
+        ),
+        (
+            "exclusiveMaximum",
+            bound("exclusiveMaximum").filter(|max| number >= *max),
+        ),
+    ]
+    .into_iter()
+    .find_map(|(keyword, limit)| limit.map(|limit| (keyword, limit)));
+    match failed {
+        Some((keyword, limit)) => Err(violation(path, &format!("violates {keyword} {limit}"))),
+        None => Ok(()),
+    }
+}
+
+fn check_len(
+    schema: &serde_json::Map<String, Value>,
+    len: usize,
+    min_keyword: &str,
+    max_keyword: &str,
+    unit: &str,
+    path: &str,
+) -> Result<(), String> {
+    let bound = |keyword: &str| {
+        schema
+            .get(keyword)
+            .and_then(Value::as_u64)
+            .and_then(|n| usize::try_from(n).ok())
+    };
+    if let Some(min) = bound(min_keyword).filter(|min| len < *min) {
+        return Err(violation(
+            path,
+            &format!("needs at least {min} {unit}, has {len}"),
+        ));
+    }
+    if let Some(max) = bound(max_keyword).filter(|max| len > *max) {
+        return Err(violation(
+            path,
+            &format!("allows at most {max} {unit}, has {len}"),
+        ));
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, kind: &str) -> bool {
+    match kind {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+fn violation(path: &str, message: &str) -> String {
+    let at = if path.is_empty() { "/" } else { path };
+    format!("{at}: {message}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reports_the_first_violation_with_its_path() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name", "legs"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "legs": {"type": "integer", "minimum": 0, "maximum": 10},
+                "tags": {"type": "array", "items": {"enum": ["red", "blue"]}}
+            }
+        });
+
+        assert!(validate(&schema, &json!({"name": "Ferris", "legs": 10})).is_ok());
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ferris"})).unwrap_err(),
+            "/: missing required property \"legs\""
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ferris", "legs": 12})).unwrap_err(),
+            "/legs: violates maximum 10"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "Ferris", "legs": "ten"})).unwrap_err(),
+            "/legs: expected integer, found string"
+        );
+        assert!(
+            validate(&schema, &json!({"name": "F", "legs": 1, "tags": ["green"]}))
+                .unwrap_err()
+                .starts_with("/tags/0: must be one of")
+        );
+        assert_eq!(
+            validate(&schema, &json!({"name": "F", "legs": 1, "shell": true})).unwrap_err(),
+            "/: unexpected property \"shell\""
+        );
+    }
+}
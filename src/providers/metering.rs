@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+/// Rough characters-per-token ratio used when a provider reports no usage.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Token usage of one successful provider call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Prompt tokens billed at the full input rate; tokens read from or
+    /// written to the provider's prompt cache are counted separately.
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache.
+    #[serde(default, alias = "cache_read_input_tokens")]
+    pub cache_read_tokens: u64,
+    /// Prompt tokens written to the provider's prompt cache.
+    #[serde(default, alias = "cache_creation_input_tokens")]
+    pub cache_write_tokens: u64,
+}
+
+impl Usage {
+    /// Estimate usage from prompt and response lengths (about four characters
+    /// per token), for providers that do not report token counts.
+    pub fn estimate(input_chars: usize, output: &str) -> Self {
+        let tokens = |chars: usize| chars.div_ceil(CHARS_PER_TOKEN) as u64;
+        Self {
+            input_tokens: tokens(input_chars),
+            output_tokens: tokens(output.chars().count()),
+            ..Self::default()
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens + self.cache_read_tokens + self.cache_write_tokens
+    }
+}
+
+/// Receives per-request usage so it can be attributed to a tenant.
+///
+/// `ReliableProvider` calls `record` once per request answered by a provider
+/// (cache hits cost nothing and are not recorded), with the tenant taken from
+/// the current [`RequestContext`](super::RequestContext).
+pub trait MeteringSink: Send + Sync {
+    fn record(&self, tenant_id: Option<&str>, provider: &str, usage: &Usage, cost: f64);
+}
+
+/// Default sink that discards everything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMeteringSink;
+
+impl MeteringSink for NoopMeteringSink {
+    fn record(&self, _tenant_id: Option<&str>, _provider: &str, _usage: &Usage, _cost: f64) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_rounds_up_to_whole_tokens() {
+        let usage = Usage::estimate(9, "abcd");
+        assert_eq!(
+            usage,
+            Usage {
+                input_tokens: 3,
+                output_tokens: 1,
+                ..Usage::default()
+            }
+        );
+        assert_eq!(usage.total_tokens(), 4);
+        assert_eq!(Usage::estimate(0, ""), Usage::default());
+    }
+}
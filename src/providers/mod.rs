@@ -1,17 +1,39 @@
 pub mod anthropic;
+mod bulkhead;
+pub mod circuit_store;
+pub mod clock;
 pub mod compatible;
+pub mod context;
+pub mod ensemble;
+pub mod error;
+pub mod experiment;
 pub mod gemini;
+mod json_schema;
+pub mod metering;
+#[cfg(test)]
+mod mock_http;
 pub mod ollama;
 pub mod openai;
 pub mod openrouter;
+pub mod rate_limit;
+pub mod redact;
 pub mod reliable;
+pub mod replay;
 pub mod router;
+pub mod semantic_cache;
+pub mod tokens;
 pub mod traits;
 
+pub use context::{RequestContext, RequestPriority};
+pub use error::ProviderError;
+pub use metering::Usage;
 pub use traits::{ChatMessage, Provider};
 
 use compatible::{AuthStyle, OpenAiCompatibleProvider};
+use experiment::ExperimentProvider;
 use reliable::ReliableProvider;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) fn build_provider_http_client() -> reqwest::Client {
     reqwest::Client::builder()
@@ -91,15 +113,18 @@ pub fn sanitize_api_error(input: &str) -> String {
     format!("{}...", &scrubbed[..end])
 }
 
-/// Build a sanitized provider error from a failed HTTP response.
-pub async fn api_error(provider: &str, response: reqwest::Response) -> anyhow::Error {
+/// Build a sanitized, classified provider error from a failed HTTP response.
+pub async fn api_error(provider: &str, response: reqwest::Response) -> ProviderError {
     let status = response.status();
     let body = response
         .text()
         .await
         .unwrap_or_else(|_| "<failed to read provider error body>".to_string());
     let sanitized = sanitize_api_error(&body);
-    anyhow::anyhow!("{provider} API error ({status}): {sanitized}")
+    ProviderError::from_status(
+        status,
+        format!("{provider} API error ({status}): {sanitized}"),
+    )
 }
 
 /// Resolve API key for a provider from config and environment variables.
@@ -284,25 +309,71 @@ pub fn create_provider(name: &str, api_key: Option<&str>) -> anyhow::Result<Box<
     }
 }
 
+/// OpenAI-compatible provider for a configured endpoint, named after it.
+pub fn create_compatible_provider(
+    config: &crate::config::CompatibleProviderConfig,
+) -> OpenAiCompatibleProvider {
+    OpenAiCompatibleProvider::new(
+        &config.name,
+        &config.base_url,
+        config.api_key.as_deref().filter(|k| !k.trim().is_empty()),
+        AuthStyle::Bearer,
+    )
+    .with_default_headers(&config.headers)
+}
+
 /// Create provider chain with retry and fallback behavior.
 pub fn create_resilient_provider(
     primary_name: &str,
     api_key: Option<&str>,
     reliability: &crate::config::ReliabilityConfig,
 ) -> anyhow::Result<Box<dyn Provider>> {
+    Ok(Box::new(create_reliable_provider(
+        primary_name,
+        api_key,
+        reliability,
+    )?))
+}
+
+/// Like [`create_resilient_provider`], but keeps the concrete type so the
+/// caller can read its stats.
+pub fn create_reliable_provider(
+    primary_name: &str,
+    api_key: Option<&str>,
+    reliability: &crate::config::ReliabilityConfig,
+) -> anyhow::Result<ReliableProvider> {
     let mut providers: Vec<(String, Box<dyn Provider>)> = Vec::new();
 
-    providers.push((
-        primary_name.to_string(),
-        create_provider(primary_name, api_key)?,
-    ));
+    // Configured compatible endpoints carry their own key and shadow any
+    // built-in provider of the same name.
+    let create = |name: &str, key: Option<&str>| -> anyhow::Result<Box<dyn Provider>> {
+        match reliability
+            .compatible_providers
+            .iter()
+            .find(|c| c.name == name)
+        {
+            Some(config) => Ok(Box::new(create_compatible_provider(config))),
+            None => match (name, &reliability.keepalive_preload_model) {
+                ("ollama", Some(model)) => Ok(Box::new(
+                    ollama::OllamaProvider::new(None).with_warm_model(model.clone()),
+                )),
+                _ => create_provider(name, key),
+            },
+        }
+    };
+
+    providers.push((primary_name.to_string(), create(primary_name, api_key)?));
 
     for fallback in &reliability.fallback_providers {
         if fallback == primary_name || providers.iter().any(|(name, _)| name == fallback) {
             continue;
         }
 
-        if api_key.is_some() && fallback != "ollama" {
+        let configured = reliability
+            .compatible_providers
+            .iter()
+            .any(|c| &c.name == fallback);
+        if api_key.is_some() && fallback != "ollama" && !configured {
             tracing::warn!(
                 fallback_provider = fallback,
                 primary_provider = primary_name,
@@ -311,7 +382,7 @@ pub fn create_resilient_provider(
             );
         }
 
-        match create_provider(fallback, api_key) {
+        match create(fallback, api_key) {
             Ok(provider) => providers.push((fallback.clone(), provider)),
             Err(e) => {
                 tracing::warn!(
@@ -322,11 +393,93 @@ pub fn create_resilient_provider(
         }
     }
 
-    Ok(Box::new(ReliableProvider::new(
+    let request_timeouts: Vec<Duration> = providers
+        .iter()
+        .map(|(name, _)| {
+            Duration::from_millis(
+                reliability
+                    .request_timeout_ms
+                    .get(name)
+                    .copied()
+                    .unwrap_or(0),
+            )
+        })
+        .collect();
+    let mut reliable = ReliableProvider::new(
         providers,
         reliability.provider_retries,
         reliability.provider_backoff_ms,
-    )))
+    )
+    .with_provider_request_timeouts(&request_timeouts);
+    if reliability.persist_circuit_state {
+        reliable = reliable.with_circuit_persistence();
+    }
+    if let Some(tracker) = crate::cost::tracker() {
+        reliable = reliable.with_cost_tracker(tracker);
+    }
+    Ok(reliable)
+}
+
+/// Re-warm `provider` every `interval` so its connections (and any preloaded
+/// local model) stay warm through idle periods. The task holds only a weak
+/// reference and ends once every other owner has dropped the provider.
+pub fn spawn_keepalive(
+    provider: &Arc<dyn Provider>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    let provider = Arc::downgrade(provider);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let Some(provider) = provider.upgrade() else {
+                break;
+            };
+            tracing::debug!("Keepalive re-warming provider connections");
+            if let Err(e) = provider.warmup().await {
+                tracing::warn!("Provider keepalive failed (non-fatal): {e}");
+            }
+        }
+    })
+}
+
+/// Wrap `control` in an `ExperimentProvider` when `experiment` sends any
+/// traffic to a treatment arm; otherwise return it unchanged. The treatment
+/// arm gets its own retry/fallback chain.
+pub fn create_experiment_provider(
+    control: Box<dyn Provider>,
+    primary_name: &str,
+    api_key: Option<&str>,
+    reliability: &crate::config::ReliabilityConfig,
+    experiment: &crate::config::ExperimentConfig,
+) -> anyhow::Result<Box<dyn Provider>> {
+    if experiment.treatment_percent == 0 {
+        return Ok(control);
+    }
+    let treatment_name = experiment
+        .treatment_provider
+        .as_deref()
+        .unwrap_or(primary_name);
+    let key = experiment.treatment_api_key.as_deref().or(api_key);
+    let treatment = create_resilient_provider(treatment_name, key, reliability)?;
+    let name = if experiment.name.is_empty() {
+        "experiment"
+    } else {
+        experiment.name.as_str()
+    };
+    tracing::info!(
+        experiment = name,
+        treatment_provider = treatment_name,
+        treatment_percent = experiment.treatment_percent,
+        "A/B experiment enabled"
+    );
+    let mut provider =
+        ExperimentProvider::new(name, control, treatment, experiment.treatment_percent);
+    if let Some(model) = &experiment.treatment_model {
+        provider = provider.with_treatment_model(model.clone());
+    }
+    Ok(Box::new(provider))
 }
 
 /// Create a `RouterProvider` if model routes are configured, otherwise return a
@@ -337,27 +490,37 @@ pub fn create_routed_provider(
     api_key: Option<&str>,
     reliability: &crate::config::ReliabilityConfig,
     model_routes: &[crate::config::ModelRouteConfig],
+    model_routing: &crate::config::ModelRoutingConfig,
     default_model: &str,
 ) -> anyhow::Result<Box<dyn Provider>> {
-    if model_routes.is_empty() {
+    if model_routes.is_empty() && model_routing.models.is_empty() {
         return create_resilient_provider(primary_name, api_key, reliability);
     }
 
     // Collect unique provider names needed
     let mut needed: Vec<String> = vec![primary_name.to_string()];
-    for route in model_routes {
-        if !needed.iter().any(|n| n == &route.provider) {
-            needed.push(route.provider.clone());
+    let route_providers = model_routes
+        .iter()
+        .map(|r| (&r.provider, r.api_key.as_deref()));
+    let registry_providers = model_routing
+        .models
+        .iter()
+        .map(|m| (&m.provider, m.api_key.as_deref()));
+    let provider_keys: Vec<(&String, Option<&str>)> =
+        route_providers.chain(registry_providers).collect();
+    for (provider, _) in &provider_keys {
+        if !needed.iter().any(|n| n == *provider) {
+            needed.push((*provider).clone());
         }
     }
 
     // Create each provider (with its own resilience wrapper)
     let mut providers: Vec<(String, Box<dyn Provider>)> = Vec::new();
     for name in &needed {
-        let key = model_routes
+        let key = provider_keys
             .iter()
-            .find(|r| &r.provider == name)
-            .and_then(|r| r.api_key.as_deref())
+            .find(|(provider, key)| *provider == name && key.is_some())
+            .and_then(|(_, key)| *key)
             .or(api_key);
         match create_resilient_provider(name, key, reliability) {
             Ok(provider) => providers.push((name.clone(), provider)),
@@ -387,11 +550,30 @@ pub fn create_routed_provider(
         })
         .collect();
 
-    Ok(Box::new(router::RouterProvider::new(
-        providers,
-        routes,
-        default_model.to_string(),
-    )))
+    let policy = model_routing.policy.parse().unwrap_or_else(|e| {
+        tracing::warn!("{e}; routing to the cheapest capable model");
+        router::RoutingPolicy::default()
+    });
+    let registry = model_routing
+        .models
+        .iter()
+        .map(|m| router::ModelEntry {
+            provider_name: m.provider.clone(),
+            model: m.model.clone(),
+            capabilities: router::ModelCapabilities {
+                context_window: m.context_window,
+                vision: m.vision,
+                tools: m.tools,
+                cost_tier: m.cost_tier,
+                speed_tier: m.speed_tier,
+            },
+        })
+        .collect();
+
+    Ok(Box::new(
+        router::RouterProvider::new(providers, routes, default_model.to_string())
+            .with_model_registry(registry, policy),
+    ))
 }
 
 #[cfg(test)]
@@ -642,12 +824,41 @@ mod tests {
             channel_max_backoff_secs: 60,
             scheduler_poll_secs: 15,
             scheduler_retries: 2,
+            persist_circuit_state: false,
+            circuit_state_max_age_secs: 3600,
+            compatible_providers: Vec::new(),
+            request_timeout_ms: std::collections::BTreeMap::new(),
+            keepalive_interval_secs: 0,
+            keepalive_preload_model: None,
         };
 
         let provider = create_resilient_provider("openrouter", Some("sk-test"), &reliability);
         assert!(provider.is_ok());
     }
 
+    #[test]
+    fn resilient_provider_builds_configured_compatible_endpoints() {
+        let endpoint = |name: &str, base_url: &str| crate::config::CompatibleProviderConfig {
+            name: name.into(),
+            base_url: base_url.into(),
+            ..Default::default()
+        };
+        let reliability = crate::config::ReliabilityConfig {
+            fallback_providers: vec!["lmstudio".into()],
+            compatible_providers: vec![
+                endpoint("vllm", "http://gpu-box:8000/v1"),
+                endpoint("lmstudio", "http://localhost:1234/v1"),
+            ],
+            ..Default::default()
+        };
+        assert!(create_resilient_provider("vllm", None, &reliability).is_ok());
+
+        let provider = create_compatible_provider(&reliability.compatible_providers[1]);
+        assert_eq!(provider.name, "lmstudio");
+        assert_eq!(provider.base_url, "http://localhost:1234/v1");
+        assert!(provider.api_key.is_none());
+    }
+
     #[test]
     fn resilient_provider_errors_for_invalid_primary() {
         let reliability = crate::config::ReliabilityConfig::default();
@@ -779,4 +990,43 @@ mod tests {
         let result = sanitize_api_error(input);
         assert_eq!(result, input);
     }
+
+    #[tokio::test]
+    async fn keepalive_rewarms_until_the_provider_is_dropped() {
+        use async_trait::async_trait;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingWarmup(Arc<AtomicUsize>);
+
+        #[async_trait]
+        impl Provider for CountingWarmup {
+            async fn chat_with_system(
+                &self,
+                _system_prompt: Option<&str>,
+                _message: &str,
+                _model: &str,
+                _temperature: f64,
+            ) -> anyhow::Result<String> {
+                Ok("ok".into())
+            }
+
+            async fn warmup(&self) -> anyhow::Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let warmups = Arc::new(AtomicUsize::new(0));
+        let provider: Arc<dyn Provider> = Arc::new(CountingWarmup(Arc::clone(&warmups)));
+        let handle = spawn_keepalive(&provider, Duration::from_millis(20));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(warmups.load(Ordering::SeqCst) >= 2);
+
+        drop(provider);
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("keepalive should stop once the provider is dropped")
+            .unwrap();
+    }
 }
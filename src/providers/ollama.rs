@@ -1,10 +1,21 @@
-use crate::providers::traits::Provider;
+use crate::providers::error::ProviderError;
+use crate::providers::metering::Usage;
+use crate::providers::traits::{
+    self, ChatMessage, ContentPart, MessageContent, Provider, SamplingParams,
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 pub struct OllamaProvider {
     base_url: String,
+    /// How long the server keeps a model loaded after a request, e.g. `"30m"`,
+    /// or `"-1"` for as long as it runs; `None` leaves the server default.
+    keep_alive: Option<String>,
+    /// Model `warmup` loads, so the first real call does not wait for it.
+    warm_model: Option<String>,
+    /// Pull models the server does not have yet instead of failing.
+    auto_pull: bool,
     client: Client,
 }
 
@@ -14,22 +25,116 @@ struct ChatRequest {
     messages: Vec<Message>,
     stream: bool,
     options: Options,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 struct Message {
     role: String,
     content: String,
+    /// Base64-encoded images for vision models
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+}
+
+impl Message {
+    fn text(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+            images: Vec::new(),
+        }
+    }
+
+    /// Ollama takes a message's images as a list of base64 strings beside its
+    /// text. Images given by URL and audio cannot be sent and are dropped.
+    fn from_chat(message: &ChatMessage) -> Self {
+        let MessageContent::Parts(parts) = &message.content else {
+            return Self::text(&message.role, &message.text());
+        };
+        let images = parts
+            .iter()
+            .filter_map(|part| match (part, part.inline_data()) {
+                (ContentPart::Text(_), _) => None,
+                (ContentPart::Audio { .. }, _) | (_, None) => {
+                    tracing::warn!("Ollama only accepts inline images; dropping media part");
+                    None
+                }
+                (_, Some((_, data))) => Some(data.to_string()),
+            })
+            .collect();
+        Self {
+            role: message.role.clone(),
+            content: message.text().into_owned(),
+            images,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct Options {
     temperature: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    /// Ollama's name for the generated token limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+impl From<&SamplingParams> for Options {
+    fn from(params: &SamplingParams) -> Self {
+        Self {
+            temperature: params.temperature,
+            top_p: params.top_p,
+            num_predict: params.max_tokens,
+            stop: params.stop.clone(),
+            seed: params.seed,
+        }
+    }
+}
+
+/// `/api/embed` response
+#[derive(Debug, Deserialize)]
+struct EmbedResponse {
+    embeddings: Vec<Vec<f32>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     message: ResponseMessage,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    done_reason: Option<String>,
+    /// Prompt tokens evaluated; omitted when the prompt was cached.
+    #[serde(default)]
+    prompt_eval_count: Option<u64>,
+    #[serde(default)]
+    eval_count: Option<u64>,
+}
+
+impl From<ChatResponse> for traits::ChatResponse {
+    fn from(response: ChatResponse) -> Self {
+        let usage =
+            (response.prompt_eval_count.is_some() || response.eval_count.is_some()).then(|| {
+                Usage {
+                    input_tokens: response.prompt_eval_count.unwrap_or(0),
+                    output_tokens: response.eval_count.unwrap_or(0),
+                    ..Usage::default()
+                }
+            });
+        Self {
+            usage,
+            model: response.model,
+            finish_reason: response.done_reason,
+            ..Self::from(response.message.content)
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,12 +143,23 @@ struct ResponseMessage {
 }
 
 impl OllamaProvider {
+    /// Reads `CRABCLAW_OLLAMA_KEEP_ALIVE`, `CRABCLAW_OLLAMA_WARM_MODEL` and
+    /// `CRABCLAW_OLLAMA_AUTO_PULL` (`false` disables pulling).
     pub fn new(base_url: Option<&str>) -> Self {
+        let env = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
         Self {
             base_url: base_url
                 .unwrap_or("http://localhost:11434")
                 .trim_end_matches('/')
                 .to_string(),
+            keep_alive: env("CRABCLAW_OLLAMA_KEEP_ALIVE"),
+            warm_model: env("CRABCLAW_OLLAMA_WARM_MODEL"),
+            auto_pull: env("CRABCLAW_OLLAMA_AUTO_PULL").is_none_or(|v| v != "false"),
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(300)) // Ollama runs locally, may be slow
                 .connect_timeout(std::time::Duration::from_secs(10))
@@ -51,6 +167,94 @@ impl OllamaProvider {
                 .unwrap_or_else(|_| Client::new()),
         }
     }
+
+    /// Keep models loaded for `keep_alive` after each request (Ollama
+    /// duration syntax, e.g. `"30m"`; `"-1"` keeps them loaded).
+    #[must_use]
+    pub fn with_keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// Load `model` on `warmup`, pulling it first if needed.
+    #[must_use]
+    pub fn with_warm_model(mut self, model: impl Into<String>) -> Self {
+        self.warm_model = Some(model.into());
+        self
+    }
+
+    /// Whether a model the server lacks is pulled on first use (default on).
+    #[must_use]
+    pub fn with_auto_pull(mut self, auto_pull: bool) -> Self {
+        self.auto_pull = auto_pull;
+        self
+    }
+
+    /// Download `model` to the server, waiting until the pull completes.
+    async fn pull(&self, model: &str) -> anyhow::Result<()> {
+        tracing::info!(
+            model,
+            "Pulling Ollama model; the first call may take a while"
+        );
+        let response = self
+            .client
+            .post(format!("{}/api/pull", self.base_url))
+            // Multi-gigabyte downloads outlast the client's usual timeout.
+            .timeout(std::time::Duration::from_secs(3600))
+            .json(&serde_json::json!({"model": model, "stream": false}))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error("Ollama", response).await.into());
+        }
+        // Drain the body so the connection goes back to the pool.
+        response.bytes().await?;
+        Ok(())
+    }
+
+    /// POST `request` to `/api/chat`, pulling its model and trying once more
+    /// when the server does not have it.
+    async fn send_chat(&self, request: &ChatRequest) -> anyhow::Result<reqwest::Response> {
+        let url = format!("{}/api/chat", self.base_url);
+        let send = || super::context::apply_request_id(self.client.post(&url).json(request)).send();
+
+        let mut response = send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND && self.auto_pull {
+            self.pull(&request.model).await?;
+            response = send().await?;
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let err = super::api_error("Ollama", response).await;
+            return Err(ProviderError::from_status(
+                status,
+                format!("{err}. Is Ollama running? (brew install ollama && ollama serve)"),
+            )
+            .into());
+        }
+        Ok(response)
+    }
+
+    /// POST `messages` to `/api/chat` and return the reply.
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        let request = ChatRequest {
+            model: model.to_string(),
+            messages,
+            stream: false,
+            options: params.into(),
+            keep_alive: self.keep_alive.clone(),
+        };
+
+        let chat_response: ChatResponse = self.send_chat(&request).await?.json().await?;
+        Ok(chat_response.into())
+    }
 }
 
 #[async_trait]
@@ -65,41 +269,123 @@ impl Provider for OllamaProvider {
         let mut messages = Vec::new();
 
         if let Some(sys) = system_prompt {
-            messages.push(Message {
-                role: "system".to_string(),
-                content: sys.to_string(),
-            });
+            messages.push(Message::text("system", sys));
         }
 
-        messages.push(Message {
-            role: "user".to_string(),
-            content: message.to_string(),
-        });
+        messages.push(Message::text("user", message));
 
-        let request = ChatRequest {
-            model: model.to_string(),
-            messages,
-            stream: false,
-            options: Options { temperature },
-        };
+        self.chat(messages, model, &SamplingParams::new(temperature))
+            .await?
+            .into_text("Ollama")
+    }
 
-        let url = format!("{}/api/chat", self.base_url);
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let mut messages: Vec<Message> = system_prompt
+            .map(|sys| Message::text("system", sys))
+            .into_iter()
+            .collect();
+        messages.push(Message::text("user", message));
+        self.chat(messages, model, params)
+            .await?
+            .into_text("Ollama")
+    }
 
-        let response = self.client.post(&url).json(&request).send().await?;
+    /// Embeds locally through `/api/embed` with a model such as
+    /// `nomic-embed-text`.
+    async fn embed(&self, texts: &[String], model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let url = format!("{}/api/embed", self.base_url);
+        let req = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({"model": model, "input": texts}));
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
-            let err = super::api_error("Ollama", response).await;
-            anyhow::bail!("{err}. Is Ollama running? (brew install ollama && ollama serve)");
+            return Err(super::api_error("Ollama", response).await.into());
         }
 
-        let chat_response: ChatResponse = response.json().await?;
-        Ok(chat_response.message.content)
+        let embed_response: EmbedResponse = response.json().await?;
+        if embed_response.embeddings.len() != texts.len() {
+            anyhow::bail!(
+                "Ollama returned {} embeddings for {} inputs",
+                embed_response.embeddings.len(),
+                texts.len()
+            );
+        }
+        Ok(embed_response.embeddings)
+    }
+
+    /// Loads the warm model, if one is set, with an empty chat (pulling it
+    /// if needed) so it stays resident for `keep_alive`; otherwise just
+    /// checks the server is up.
+    async fn warmup(&self) -> anyhow::Result<()> {
+        let Some(model) = &self.warm_model else {
+            let url = format!("{}/api/version", self.base_url);
+            self.client.get(url).send().await?.bytes().await?;
+            return Ok(());
+        };
+        let request = ChatRequest {
+            model: model.clone(),
+            messages: Vec::new(),
+            stream: false,
+            options: Options::from(&SamplingParams::new(0.0)),
+            keep_alive: self.keep_alive.clone(),
+        };
+        self.send_chat(&request).await?.bytes().await?;
+        tracing::info!(model = %model, "Ollama model loaded");
+        Ok(())
+    }
+
+    /// Sends the whole conversation, with images attached to their messages.
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        self.chat_with_history_params(messages, model, &SamplingParams::new(temperature))
+            .await
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text("Ollama")
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        self.chat(
+            messages.iter().map(Message::from_chat).collect(),
+            model,
+            params,
+        )
+        .await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn default_url() {
@@ -125,42 +411,58 @@ mod tests {
         assert_eq!(p.base_url, "");
     }
 
+    #[test]
+    fn inline_images_ride_beside_the_text() {
+        let message = Message::from_chat(&ChatMessage::user_parts(vec![
+            ContentPart::Text("what is this?".into()),
+            ContentPart::ImageBase64 {
+                media_type: "image/png".into(),
+                data: "iVBORw0".into(),
+            },
+            ContentPart::ImageUrl {
+                url: "data:image/jpeg;base64,/9j/4AAQ".into(),
+                detail: None,
+            },
+        ]));
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["content"], "what is this?");
+        assert_eq!(json["images"], serde_json::json!(["iVBORw0", "/9j/4AAQ"]));
+
+        let plain = serde_json::to_value(Message::text("user", "hi")).unwrap();
+        assert!(plain.get("images").is_none());
+    }
+
     #[test]
     fn request_serializes_with_system() {
         let req = ChatRequest {
             model: "llama3".to_string(),
             messages: vec![
-                Message {
-                    role: "system".to_string(),
-                    content: "You are CrabClaw".to_string(),
-                },
-                Message {
-                    role: "user".to_string(),
-                    content: "hello".to_string(),
-                },
+                Message::text("system", "You are CrabClaw"),
+                Message::text("user", "hello"),
             ],
             stream: false,
-            options: Options { temperature: 0.7 },
+            options: Options::from(&SamplingParams::new(0.7)),
+            keep_alive: None,
         };
         let json = serde_json::to_string(&req).unwrap();
         assert!(json.contains("\"stream\":false"));
         assert!(json.contains("llama3"));
         assert!(json.contains("system"));
         assert!(json.contains("\"temperature\":0.7"));
+        assert!(!json.contains("keep_alive"));
     }
 
     #[test]
     fn request_serializes_without_system() {
         let req = ChatRequest {
             model: "mistral".to_string(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: "test".to_string(),
-            }],
+            messages: vec![Message::text("user", "test")],
             stream: false,
-            options: Options { temperature: 0.0 },
+            options: Options::from(&SamplingParams::new(0.0)),
+            keep_alive: Some("30m".into()),
         };
         let json = serde_json::to_string(&req).unwrap();
+        assert!(json.contains("\"keep_alive\":\"30m\""));
         assert!(!json.contains("\"role\":\"system\""));
         assert!(json.contains("mistral"));
     }
@@ -185,4 +487,92 @@ mod tests {
         let resp: ChatResponse = serde_json::from_str(json).unwrap();
         assert!(resp.message.content.contains("line1"));
     }
+
+    #[test]
+    fn response_reports_usage_and_done_reason() {
+        let json = r#"{"model":"llama3","message":{"role":"assistant","content":"hi"},
+            "done":true,"done_reason":"stop","prompt_eval_count":26,"eval_count":3}"#;
+        let resp: traits::ChatResponse = serde_json::from_str::<ChatResponse>(json).unwrap().into();
+        assert_eq!(resp.text.as_deref(), Some("hi"));
+        assert_eq!(resp.model.as_deref(), Some("llama3"));
+        assert_eq!(resp.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(
+            resp.usage,
+            Some(Usage {
+                input_tokens: 26,
+                output_tokens: 3,
+                ..Usage::default()
+            })
+        );
+
+        let json = r#"{"message":{"role":"assistant","content":"hi"}}"#;
+        let resp: traits::ChatResponse = serde_json::from_str::<ChatResponse>(json).unwrap().into();
+        assert!(resp.usage.is_none());
+    }
+
+    /// Ollama stand-in that lacks every model until it is pulled, logging
+    /// each request as `"<path> <body>"`.
+    async fn mock_ollama() -> (String, Arc<Mutex<Vec<String>>>) {
+        let log = Arc::new(Mutex::new(Vec::<String>::new()));
+        let seen = Arc::clone(&log);
+        let (base_url, _) = super::super::mock_http::serve(move |path, body| {
+            let mut log = seen.lock().unwrap();
+            let pulled = log.iter().any(|r| r.starts_with("/api/pull"));
+            log.push(format!("{path} {}", String::from_utf8_lossy(body)));
+            let (status, reply) = match path {
+                "/api/chat" if !pulled => ("404 Not Found", r#"{"error":"model not found"}"#),
+                "/api/chat" => (
+                    "200 OK",
+                    r#"{"message":{"role":"assistant","content":"local hello"}}"#,
+                ),
+                _ => ("200 OK", r#"{"status":"success"}"#),
+            };
+            (status, reply.to_string())
+        })
+        .await;
+        (base_url, log)
+    }
+
+    #[tokio::test]
+    async fn missing_models_are_pulled_on_first_use() {
+        let (base_url, log) = mock_ollama().await;
+        let provider = OllamaProvider::new(Some(&base_url)).with_auto_pull(true);
+
+        assert_eq!(
+            Provider::chat(&provider, "hi", "llama3.2", 0.0)
+                .await
+                .unwrap(),
+            "local hello"
+        );
+        let paths: Vec<String> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.split(' ').next().unwrap().to_string())
+            .collect();
+        assert_eq!(paths, ["/api/chat", "/api/pull", "/api/chat"]);
+        assert!(log.lock().unwrap()[1].contains(r#""model":"llama3.2""#));
+
+        let (base_url, _) = mock_ollama().await;
+        let provider = OllamaProvider::new(Some(&base_url)).with_auto_pull(false);
+        assert!(Provider::chat(&provider, "hi", "llama3.2", 0.0)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn warmup_loads_the_warm_model_with_keep_alive() {
+        let (base_url, log) = mock_ollama().await;
+        let provider = OllamaProvider::new(Some(&base_url))
+            .with_auto_pull(true)
+            .with_warm_model("qwen3")
+            .with_keep_alive("-1");
+
+        provider.warmup().await.unwrap();
+        let requests = log.lock().unwrap().clone();
+        let loaded = requests.last().unwrap();
+        assert!(loaded.starts_with("/api/chat"));
+        assert!(loaded.contains(r#""messages":[]"#));
+        assert!(loaded.contains(r#""keep_alive":"-1""#));
+    }
 }
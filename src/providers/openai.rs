@@ -1,7 +1,16 @@
-use crate::providers::traits::Provider;
+use super::compatible::{ApiChatResponse, EmbeddingsResponse, ToolChatRequest};
+use super::error::ProviderError;
+use crate::providers::traits::{
+    self, BatchRequest, ChatMessage, ConversationMessage, Provider, SamplingParams,
+};
+use crate::tools::ToolSpec;
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How often a submitted Batch API job is checked for completion.
+const BATCH_POLL_INTERVAL: Duration = Duration::from_secs(30);
 
 pub struct OpenAiProvider {
     api_key: Option<String>,
@@ -36,6 +45,116 @@ struct ResponseMessage {
     content: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct FileObject {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchJob {
+    id: String,
+    status: String,
+    #[serde(default)]
+    output_file_id: Option<String>,
+    #[serde(default)]
+    error_file_id: Option<String>,
+}
+
+impl BatchJob {
+    fn is_finished(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "completed" | "failed" | "expired" | "cancelled"
+        )
+    }
+}
+
+/// One line of a batch output or error file.
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    #[serde(default)]
+    response: Option<BatchOutputResponse>,
+    #[serde(default)]
+    error: Option<BatchLineError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchLineError {
+    message: String,
+}
+
+/// Batch API input file: one chat completion request per line, keyed by
+/// its position in `requests`.
+fn batch_input(requests: &[BatchRequest]) -> anyhow::Result<String> {
+    let mut jsonl = String::new();
+    for (idx, request) in requests.iter().enumerate() {
+        let body = ToolChatRequest::with_params(&request.messages, &request.model, &request.params);
+        let line = serde_json::json!({
+            "custom_id": idx.to_string(),
+            "method": "POST",
+            "url": "/v1/chat/completions",
+            "body": serde_json::to_value(&body)?,
+        });
+        jsonl.push_str(&line.to_string());
+        jsonl.push('\n');
+    }
+    Ok(jsonl)
+}
+
+/// Results for `count` requests from the lines of a batch's output and error
+/// files; requests that appear in neither fail.
+fn batch_results(count: usize, files: &[String]) -> Vec<anyhow::Result<String>> {
+    let mut results: Vec<Option<anyhow::Result<String>>> = (0..count).map(|_| None).collect();
+    for line in files.iter().flat_map(|file| file.lines()) {
+        let Ok(line) = serde_json::from_str::<BatchOutputLine>(line) else {
+            continue;
+        };
+        if let Some(slot) = line
+            .custom_id
+            .parse::<usize>()
+            .ok()
+            .and_then(|idx| results.get_mut(idx))
+        {
+            *slot = Some(batch_line_result(line));
+        }
+    }
+    results
+        .into_iter()
+        .map(|result| {
+            result.unwrap_or_else(|| Err(anyhow::anyhow!("OpenAI batch returned no result")))
+        })
+        .collect()
+}
+
+fn batch_line_result(line: BatchOutputLine) -> anyhow::Result<String> {
+    if let Some(error) = line.error {
+        anyhow::bail!("OpenAI batch request failed: {}", error.message);
+    }
+    let response = line
+        .response
+        .ok_or_else(|| anyhow::anyhow!("OpenAI batch returned no response"))?;
+    let status = reqwest::StatusCode::from_u16(response.status_code)
+        .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR);
+    if !status.is_success() {
+        let message = response.body["error"]["message"]
+            .as_str()
+            .unwrap_or("unknown error");
+        return Err(
+            ProviderError::from_status(status, format!("OpenAI API error: {message}")).into(),
+        );
+    }
+    serde_json::from_value::<ApiChatResponse>(response.body)?
+        .into_chat_response("OpenAI")?
+        .into_text("OpenAI")
+}
+
 impl OpenAiProvider {
     pub fn new(api_key: Option<&str>) -> Self {
         Self {
@@ -43,6 +162,105 @@ impl OpenAiProvider {
             client: super::build_provider_http_client(),
         }
     }
+
+    /// POST `request` to chat completions.
+    async fn send_chat(&self, request: &ToolChatRequest) -> anyhow::Result<ApiChatResponse> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("OpenAI API key not set. Set OPENAI_API_KEY or edit config.toml.")
+        })?;
+
+        let req = self
+            .client
+            .post("https://api.openai.com/v1/chat/completions")
+            .header("Authorization", format!("Bearer {api_key}"))
+            .json(request);
+        let response = super::context::apply_request_id(req).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error("OpenAI", response).await.into());
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Send an authenticated Batch or Files API request, failing on an error
+    /// status.
+    async fn send_batch_call(&self, request: RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("OpenAI API key not set. Set OPENAI_API_KEY or edit config.toml.")
+        })?;
+        let request = request.header("Authorization", format!("Bearer {api_key}"));
+        let response = super::context::apply_request_id(request).send().await?;
+        if !response.status().is_success() {
+            return Err(super::api_error("OpenAI", response).await.into());
+        }
+        Ok(response)
+    }
+
+    /// Upload `requests` as a Batch API job, wait for it to finish and read
+    /// back whatever it answered.
+    async fn run_batch(
+        &self,
+        requests: &[BatchRequest],
+    ) -> anyhow::Result<Vec<anyhow::Result<String>>> {
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", "batch")
+            .part(
+                "file",
+                reqwest::multipart::Part::text(batch_input(requests)?).file_name("batch.jsonl"),
+            );
+        let file: FileObject = self
+            .send_batch_call(
+                self.client
+                    .post("https://api.openai.com/v1/files")
+                    .multipart(form),
+            )
+            .await?
+            .json()
+            .await?;
+        let mut job: BatchJob = self
+            .send_batch_call(self.client.post("https://api.openai.com/v1/batches").json(
+                &serde_json::json!({
+                    "input_file_id": file.id,
+                    "endpoint": "/v1/chat/completions",
+                    "completion_window": "24h",
+                }),
+            ))
+            .await?
+            .json()
+            .await?;
+        tracing::info!(batch = %job.id, requests = requests.len(), "Submitted OpenAI batch");
+
+        while !job.is_finished() {
+            tokio::time::sleep(BATCH_POLL_INTERVAL).await;
+            job = self
+                .send_batch_call(
+                    self.client
+                        .get(format!("https://api.openai.com/v1/batches/{}", job.id)),
+                )
+                .await?
+                .json()
+                .await?;
+        }
+        if job.status == "failed" {
+            anyhow::bail!("OpenAI batch {} failed", job.id);
+        }
+
+        // Expired and cancelled jobs still return what they finished.
+        let mut files = Vec::new();
+        for file_id in [job.output_file_id, job.error_file_id]
+            .into_iter()
+            .flatten()
+        {
+            let url = format!("https://api.openai.com/v1/files/{file_id}/content");
+            files.push(
+                self.send_batch_call(self.client.get(url))
+                    .await?
+                    .text()
+                    .await?,
+            );
+        }
+        Ok(batch_results(requests.len(), &files))
+    }
 }
 
 #[async_trait]
@@ -78,16 +296,15 @@ impl Provider for OpenAiProvider {
             temperature,
         };
 
-        let response = self
+        let req = self
             .client
             .post("https://api.openai.com/v1/chat/completions")
             .header("Authorization", format!("Bearer {api_key}"))
-            .json(&request)
-            .send()
-            .await?;
+            .json(&request);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
-            return Err(super::api_error("OpenAI", response).await);
+            return Err(super::api_error("OpenAI", response).await.into());
         }
 
         let chat_response: ChatResponse = response.json().await?;
@@ -99,6 +316,93 @@ impl Provider for OpenAiProvider {
             .map(|c| c.message.content)
             .ok_or_else(|| anyhow::anyhow!("No response from OpenAI"))
     }
+
+    async fn embed(&self, texts: &[String], model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("OpenAI API key not set. Set OPENAI_API_KEY or edit config.toml.")
+        })?;
+
+        let req = self
+            .client
+            .post("https://api.openai.com/v1/embeddings")
+            .header("Authorization", format!("Bearer {api_key}"))
+            .json(&serde_json::json!({"model": model, "input": texts}));
+        let response = super::context::apply_request_id(req).send().await?;
+
+        if !response.status().is_success() {
+            return Err(super::api_error("OpenAI", response).await.into());
+        }
+
+        let embeddings: EmbeddingsResponse = response.json().await?;
+        embeddings.into_vectors("OpenAI", texts.len())
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        let request = ToolChatRequest::new(messages, tools, model, temperature);
+        self.send_chat(&request).await?.into_tool_response("OpenAI")
+    }
+
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let mut messages: Vec<ChatMessage> =
+            system_prompt.map(ChatMessage::system).into_iter().collect();
+        messages.push(ChatMessage::user(message));
+        self.chat_with_history_params(&messages, model, params)
+            .await
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text("OpenAI")
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<traits::ChatResponse> {
+        let request = ToolChatRequest::with_params(messages, model, params);
+        self.send_chat(&request).await?.into_tool_response("OpenAI")
+    }
+
+    /// Submits `requests` as one Batch API job, billed at half price and
+    /// finished within 24 hours, and waits for it.
+    async fn chat_batch(&self, requests: &[BatchRequest]) -> Vec<anyhow::Result<String>> {
+        if requests.is_empty() {
+            return Vec::new();
+        }
+        match self.run_batch(requests).await {
+            Ok(results) => results,
+            Err(e) => {
+                let message = e.to_string();
+                requests
+                    .iter()
+                    .map(|_| Err(anyhow::anyhow!("{message}")))
+                    .collect()
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -117,6 +421,53 @@ mod tests {
         assert!(p.api_key.is_none());
     }
 
+    #[test]
+    fn batch_files_round_trip_by_custom_id() {
+        let requests = vec![
+            BatchRequest::new(
+                vec![ChatMessage::user("one")],
+                "gpt-4o-mini",
+                SamplingParams::new(0.2),
+            ),
+            BatchRequest::new(
+                vec![ChatMessage::user("two")],
+                "gpt-4o-mini",
+                SamplingParams::new(0.2),
+            ),
+            BatchRequest::new(
+                vec![ChatMessage::user("three")],
+                "gpt-4o-mini",
+                SamplingParams::new(0.2),
+            ),
+        ];
+        let input = batch_input(&requests).unwrap();
+        let lines: Vec<serde_json::Value> = input
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1]["custom_id"], "1");
+        assert_eq!(lines[1]["url"], "/v1/chat/completions");
+        assert_eq!(lines[1]["body"]["messages"][0]["content"], "two");
+
+        let output = [
+            r#"{"custom_id":"1","response":{"status_code":200,"body":{"choices":[{"message":{"content":"2"}}]}}}"#,
+            r#"{"custom_id":"0","response":{"status_code":200,"body":{"choices":[{"message":{"content":"1"}}]}}}"#,
+        ]
+        .join("\n");
+        let errors = r#"{"custom_id":"2","response":{"status_code":429,"body":{"error":{"message":"slow down"}}}}"#;
+        let results = batch_results(3, &[output, errors.to_string()]);
+        assert_eq!(results[0].as_deref().unwrap(), "1");
+        assert_eq!(results[1].as_deref().unwrap(), "2");
+        let err = results[2].as_ref().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ProviderError>(),
+            Some(ProviderError::RateLimited { .. })
+        ));
+
+        assert!(batch_results(1, &[]).remove(0).is_err());
+    }
+
     #[test]
     fn creates_with_empty_key() {
         let p = OpenAiProvider::new(Some(""));
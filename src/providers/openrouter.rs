@@ -1,4 +1,7 @@
-use crate::providers::traits::{ChatMessage, Provider};
+use super::compatible::{self, SamplingFields};
+use crate::providers::traits::{
+    ChatMessage, ChatResponse, MessageContent, Provider, SamplingParams,
+};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -13,12 +16,14 @@ struct ChatRequest {
     model: String,
     messages: Vec<Message>,
     temperature: f64,
+    #[serde(flatten)]
+    sampling: SamplingFields,
 }
 
 #[derive(Debug, Serialize)]
 struct Message {
     role: String,
-    content: String,
+    content: MessageContent,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,22 +81,23 @@ impl Provider for OpenRouterProvider {
         if let Some(sys) = system_prompt {
             messages.push(Message {
                 role: "system".to_string(),
-                content: sys.to_string(),
+                content: sys.into(),
             });
         }
 
         messages.push(Message {
             role: "user".to_string(),
-            content: message.to_string(),
+            content: message.into(),
         });
 
         let request = ChatRequest {
             model: model.to_string(),
             messages,
             temperature,
+            sampling: SamplingFields::default(),
         };
 
-        let response = self
+        let req = self
             .client
             .post("https://openrouter.ai/api/v1/chat/completions")
             .header("Authorization", format!("Bearer {api_key}"))
@@ -100,12 +106,11 @@ impl Provider for OpenRouterProvider {
                 "https://github.com/theonlyhennygod/crabclaw",
             )
             .header("X-Title", "CrabClaw")
-            .json(&request)
-            .send()
-            .await?;
+            .json(&request);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
-            return Err(super::api_error("OpenRouter", response).await);
+            return Err(super::api_error("OpenRouter", response).await.into());
         }
 
         let chat_response: ApiChatResponse = response.json().await?;
@@ -118,12 +123,47 @@ impl Provider for OpenRouterProvider {
             .ok_or_else(|| anyhow::anyhow!("No response from OpenRouter"))
     }
 
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let mut messages: Vec<ChatMessage> =
+            system_prompt.map(ChatMessage::system).into_iter().collect();
+        messages.push(ChatMessage::user(message));
+        self.chat_with_history_params(&messages, model, params)
+            .await
+    }
+
     async fn chat_with_history(
         &self,
         messages: &[ChatMessage],
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
+        self.chat_with_history_params(messages, model, &SamplingParams::new(temperature))
+            .await
+    }
+
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_response(messages, model, params)
+            .await?
+            .into_text("OpenRouter")
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
         let api_key = self.api_key.as_ref()
             .ok_or_else(|| anyhow::anyhow!("OpenRouter API key not set. Run `crabclaw onboard` or set OPENROUTER_API_KEY env var."))?;
 
@@ -138,10 +178,11 @@ impl Provider for OpenRouterProvider {
         let request = ChatRequest {
             model: model.to_string(),
             messages: api_messages,
-            temperature,
+            temperature: params.temperature,
+            sampling: params.into(),
         };
 
-        let response = self
+        let req = self
             .client
             .post("https://openrouter.ai/api/v1/chat/completions")
             .header("Authorization", format!("Bearer {api_key}"))
@@ -150,21 +191,14 @@ impl Provider for OpenRouterProvider {
                 "https://github.com/theonlyhennygod/crabclaw",
             )
             .header("X-Title", "CrabClaw")
-            .json(&request)
-            .send()
-            .await?;
+            .json(&request);
+        let response = super::context::apply_request_id(req).send().await?;
 
         if !response.status().is_success() {
-            return Err(super::api_error("OpenRouter", response).await);
+            return Err(super::api_error("OpenRouter", response).await.into());
         }
 
-        let chat_response: ApiChatResponse = response.json().await?;
-
-        chat_response
-            .choices
-            .into_iter()
-            .next()
-            .map(|c| c.message.content)
-            .ok_or_else(|| anyhow::anyhow!("No response from OpenRouter"))
+        let chat_response: compatible::ApiChatResponse = response.json().await?;
+        chat_response.into_chat_response("OpenRouter")
     }
 }
@@ -0,0 +1,156 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Request and token budgets for one provider, enforced before each call so
+/// bursts queue locally instead of drawing 429s from the upstream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Calls per minute; 0 is unlimited.
+    pub requests_per_minute: u32,
+    /// Estimated prompt plus response tokens per minute; 0 is unlimited.
+    pub tokens_per_minute: u32,
+    /// How long a call may wait for budget before the provider is skipped.
+    /// Zero skips it as soon as the budget is spent.
+    pub max_wait: Duration,
+}
+
+impl RateLimit {
+    fn is_unlimited(&self) -> bool {
+        self.requests_per_minute == 0 && self.tokens_per_minute == 0
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: f64,
+    /// May go negative when responses use more tokens than were reserved.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Token buckets for a [`RateLimit`], each holding a minute's worth of budget
+/// and refilling continuously.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// `None` when `limit` restricts nothing.
+    pub(crate) fn new(limit: RateLimit) -> Option<Self> {
+        if limit.is_unlimited() {
+            return None;
+        }
+        Some(Self {
+            limit,
+            buckets: Mutex::new(Buckets {
+                requests: f64::from(limit.requests_per_minute),
+                tokens: f64::from(limit.tokens_per_minute),
+                last_refill: Instant::now(),
+            }),
+        })
+    }
+
+    /// Take one request and `tokens` tokens, or report how long until they
+    /// would be available.
+    fn try_take(&self, tokens: u64) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let minutes = now.duration_since(buckets.last_refill).as_secs_f64() / 60.0;
+        buckets.last_refill = now;
+
+        let rpm = f64::from(self.limit.requests_per_minute);
+        let tpm = f64::from(self.limit.tokens_per_minute);
+        let mut wait_minutes: f64 = 0.0;
+        if rpm > 0.0 {
+            buckets.requests = (buckets.requests + minutes * rpm).min(rpm);
+            wait_minutes = wait_minutes.max((1.0 - buckets.requests) / rpm);
+        }
+        if tpm > 0.0 {
+            buckets.tokens = (buckets.tokens + minutes * tpm).min(tpm);
+            // A prompt larger than the whole budget waits for a full bucket.
+            #[allow(clippy::cast_precision_loss)]
+            let needed = (tokens as f64).min(tpm);
+            wait_minutes = wait_minutes.max((needed - buckets.tokens) / tpm);
+        }
+        if wait_minutes > 0.0 {
+            return Err(Duration::from_secs_f64(wait_minutes * 60.0));
+        }
+        buckets.requests -= 1.0;
+        #[allow(clippy::cast_precision_loss)]
+        {
+            buckets.tokens -= tokens as f64;
+        }
+        Ok(())
+    }
+
+    /// Wait for budget for one call using `tokens` tokens, giving up (and
+    /// taking nothing) if that would take longer than `max_wait` or `left`.
+    pub(crate) async fn acquire(&self, tokens: u64, left: Option<Duration>) -> Result<(), ()> {
+        let allowed = left.map_or(self.limit.max_wait, |left| left.min(self.limit.max_wait));
+        let started = Instant::now();
+        loop {
+            match self.try_take(tokens) {
+                Ok(()) => return Ok(()),
+                Err(wait) if started.elapsed() + wait > allowed => return Err(()),
+                Err(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Take one request without waiting, for opportunistic calls such as hedges.
+    pub(crate) fn try_acquire(&self) -> bool {
+        self.try_take(0).is_ok()
+    }
+
+    /// Debit tokens used beyond those taken up front, e.g. by the response.
+    pub(crate) fn charge(&self, tokens: u64) {
+        if self.limit.tokens_per_minute == 0 {
+            return;
+        }
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        #[allow(clippy::cast_precision_loss)]
+        {
+            buckets.tokens -= tokens as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn request_budget_rejects_once_spent_without_waiting() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_minute: 2,
+            ..RateLimit::default()
+        })
+        .unwrap();
+        assert!(limiter.acquire(0, None).await.is_ok());
+        assert!(limiter.acquire(0, None).await.is_ok());
+        assert!(limiter.acquire(0, None).await.is_err());
+        assert!(RateLimiter::new(RateLimit::default()).is_none());
+    }
+
+    #[tokio::test]
+    async fn token_budget_queues_within_max_wait() {
+        // 60k tokens per minute refill at 1k per second.
+        let limiter = RateLimiter::new(RateLimit {
+            tokens_per_minute: 60_000,
+            max_wait: Duration::from_secs(1),
+            ..RateLimit::default()
+        })
+        .unwrap();
+        assert!(limiter.acquire(59_950, None).await.is_ok());
+
+        let started = Instant::now();
+        assert!(limiter.acquire(100, None).await.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        // A response overspending the budget pushes the next call past max_wait.
+        limiter.charge(5_000);
+        assert!(limiter.acquire(100, None).await.is_err());
+    }
+}
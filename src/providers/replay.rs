@@ -0,0 +1,362 @@
+//! Cassette-style providers for offline tests.
+//!
+//! A cassette is a JSON file mapping request fingerprints to recorded
+//! responses. `RecordingProvider` wraps a real provider and writes every
+//! successful response to a cassette; `ReplayProvider` serves them back
+//! without touching the network.
+
+use super::traits::{ChatMessage, Provider};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Fingerprint of a single-turn request.
+pub fn chat_fingerprint(
+    system_prompt: Option<&str>,
+    message: &str,
+    model: &str,
+    temperature: f64,
+) -> String {
+    fingerprint(&serde_json::json!([
+        "chat",
+        system_prompt,
+        message,
+        model,
+        format!("{temperature:.4}")
+    ]))
+}
+
+/// Fingerprint of a multi-turn request.
+pub fn history_fingerprint(messages: &[ChatMessage], model: &str, temperature: f64) -> String {
+    fingerprint(&serde_json::json!([
+        "history",
+        messages,
+        model,
+        format!("{temperature:.4}")
+    ]))
+}
+
+fn fingerprint(request: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(request.to_string().as_bytes()))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    responses: BTreeMap<String, String>,
+}
+
+/// Recorded responses backed by a cassette file.
+struct Cassette {
+    path: PathBuf,
+    responses: Mutex<BTreeMap<String, String>>,
+}
+
+impl Cassette {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read cassette {}: {e}", path.display()))?;
+        let file: CassetteFile = serde_json::from_str(&raw)
+            .map_err(|e| anyhow::anyhow!("Invalid cassette {}: {e}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            responses: Mutex::new(file.responses),
+        })
+    }
+
+    /// Load `path`, or start an empty cassette when it does not exist yet.
+    fn load_or_empty(path: &Path) -> anyhow::Result<Self> {
+        if path.exists() {
+            return Self::load(path);
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            responses: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn get(&self, fingerprint: &str) -> Option<String> {
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(fingerprint)
+            .cloned()
+    }
+
+    /// Store `response` and rewrite the cassette file.
+    fn record(&self, fingerprint: String, response: &str) -> anyhow::Result<()> {
+        let mut responses = self
+            .responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        responses.insert(fingerprint, response.to_string());
+        let file = CassetteFile {
+            responses: responses.clone(),
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&file)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write cassette {}: {e}", self.path.display()))
+    }
+
+    fn miss(&self, fingerprint: &str, model: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "No recorded response for request {fingerprint} (model {model}) in cassette {}",
+            self.path.display()
+        )
+    }
+}
+
+/// Serves responses recorded in a cassette. Unrecorded requests fail, or are
+/// forwarded to a live provider and recorded when built with `recording_misses`.
+pub struct ReplayProvider {
+    cassette: Cassette,
+    live: Option<Box<dyn Provider>>,
+}
+
+impl ReplayProvider {
+    /// Replay-only provider over an existing cassette file.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            cassette: Cassette::load(path.as_ref())?,
+            live: None,
+        })
+    }
+
+    /// Replay what is recorded in `path` and record anything else from `live`.
+    pub fn recording_misses(
+        path: impl AsRef<Path>,
+        live: Box<dyn Provider>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            cassette: Cassette::load_or_empty(path.as_ref())?,
+            live: Some(live),
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for ReplayProvider {
+    async fn chat_with_system(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let fingerprint = chat_fingerprint(system_prompt, message, model, temperature);
+        if let Some(response) = self.cassette.get(&fingerprint) {
+            return Ok(response);
+        }
+        let Some(live) = &self.live else {
+            return Err(self.cassette.miss(&fingerprint, model));
+        };
+        let response = live
+            .chat_with_system(system_prompt, message, model, temperature)
+            .await?;
+        self.cassette.record(fingerprint, &response)?;
+        Ok(response)
+    }
+
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let fingerprint = history_fingerprint(messages, model, temperature);
+        if let Some(response) = self.cassette.get(&fingerprint) {
+            return Ok(response);
+        }
+        let Some(live) = &self.live else {
+            return Err(self.cassette.miss(&fingerprint, model));
+        };
+        let response = live.chat_with_history(messages, model, temperature).await?;
+        self.cassette.record(fingerprint, &response)?;
+        Ok(response)
+    }
+}
+
+/// Forwards every request to `inner` and records successful responses to a
+/// cassette that `ReplayProvider` can serve later.
+pub struct RecordingProvider {
+    inner: Box<dyn Provider>,
+    cassette: Cassette,
+}
+
+impl RecordingProvider {
+    /// Record into `path`, keeping any responses it already holds.
+    pub fn new(inner: Box<dyn Provider>, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner,
+            cassette: Cassette::load_or_empty(path.as_ref())?,
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for RecordingProvider {
+    async fn chat_with_system(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let response = self
+            .inner
+            .chat_with_system(system_prompt, message, model, temperature)
+            .await?;
+        self.cassette.record(
+            chat_fingerprint(system_prompt, message, model, temperature),
+            &response,
+        )?;
+        Ok(response)
+    }
+
+    async fn chat_with_history(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let response = self
+            .inner
+            .chat_with_history(messages, model, temperature)
+            .await?;
+        self.cassette
+            .record(history_fingerprint(messages, model, temperature), &response)?;
+        Ok(response)
+    }
+
+    async fn warmup(&self) -> anyhow::Result<()> {
+        self.inner.warmup().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Answers `live: <last user message>` and counts calls.
+    struct LiveProvider {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Provider for LiveProvider {
+        async fn chat_with_system(
+            &self,
+            _system_prompt: Option<&str>,
+            message: &str,
+            _model: &str,
+            _temperature: f64,
+        ) -> anyhow::Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("live: {message}"))
+        }
+    }
+
+    fn write_cassette(path: &Path, responses: &[(String, &str)]) {
+        let file = CassetteFile {
+            responses: responses
+                .iter()
+                .map(|(fp, resp)| (fp.clone(), (*resp).to_string()))
+                .collect(),
+        };
+        std::fs::write(path, serde_json::to_vec(&file).unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_serves_recorded_response() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("cassette.json");
+        write_cassette(
+            &path,
+            &[(chat_fingerprint(Some("sys"), "hello", "m", 0.7), "recorded")],
+        );
+
+        let provider = ReplayProvider::from_file(&path).unwrap();
+        let response = provider
+            .chat_with_system(Some("sys"), "hello", "m", 0.7)
+            .await
+            .unwrap();
+        assert_eq!(response, "recorded");
+    }
+
+    #[tokio::test]
+    async fn replay_miss_names_the_request() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("cassette.json");
+        write_cassette(
+            &path,
+            &[(chat_fingerprint(Some("sys"), "hello", "m", 0.7), "recorded")],
+        );
+
+        let provider = ReplayProvider::from_file(&path).unwrap();
+        let err = provider
+            .chat_with_system(None, "hello", "m", 0.7)
+            .await
+            .unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("No recorded response"));
+        assert!(msg.contains(&chat_fingerprint(None, "hello", "m", 0.7)));
+    }
+
+    #[tokio::test]
+    async fn record_then_replay_round_trip() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("nested").join("cassette.json");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let history = vec![ChatMessage::system("sys"), ChatMessage::user("second")];
+
+        let recorder = RecordingProvider::new(
+            Box::new(LiveProvider {
+                calls: Arc::clone(&calls),
+            }),
+            &path,
+        )
+        .unwrap();
+        recorder.chat("first", "m", 0.0).await.unwrap();
+        recorder
+            .chat_with_history(&history, "m", 0.0)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        assert_eq!(replay.chat("first", "m", 0.0).await.unwrap(), "live: first");
+        assert_eq!(
+            replay.chat_with_history(&history, "m", 0.0).await.unwrap(),
+            "live: second"
+        );
+        assert!(replay.chat("first", "m", 0.5).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn recording_misses_forwards_and_records_only_unrecorded_requests() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("cassette.json");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let provider = ReplayProvider::recording_misses(
+            &path,
+            Box::new(LiveProvider {
+                calls: Arc::clone(&calls),
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(provider.chat("hi", "m", 0.0).await.unwrap(), "live: hi");
+        assert_eq!(provider.chat("hi", "m", 0.0).await.unwrap(), "live: hi");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let replay = ReplayProvider::from_file(&path).unwrap();
+        assert_eq!(replay.chat("hi", "m", 0.0).await.unwrap(), "live: hi");
+    }
+}
@@ -1,5 +1,10 @@
-use super::traits::ChatMessage;
+use super::tokens::Tokenizer;
+use super::traits::{
+    ChatMessage, ChatOptions, ChatResponse, ContentPart, ConversationMessage, MessageContent,
+    ModelInfo, SamplingParams,
+};
 use super::Provider;
+use crate::tools::ToolSpec;
 use async_trait::async_trait;
 use std::collections::HashMap;
 
@@ -10,12 +15,71 @@ pub struct Route {
     pub model: String,
 }
 
+/// Model name that asks the router to pick a model from its registry.
+pub const AUTO_MODEL: &str = "auto";
+
+/// Tokens a registry model must have free beyond the prompt for the reply.
+const RESPONSE_RESERVE_TOKENS: usize = 1024;
+
+/// What a model can do, as declared in config; providers rarely report it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    /// Context window in tokens
+    pub context_window: usize,
+    pub vision: bool,
+    pub tools: bool,
+    /// Relative price; lower is cheaper.
+    pub cost_tier: u8,
+    /// Relative latency; lower is faster.
+    pub speed_tier: u8,
+}
+
+/// A provider + model the router may pick for [`AUTO_MODEL`] requests.
+#[derive(Debug, Clone)]
+pub struct ModelEntry {
+    pub provider_name: String,
+    pub model: String,
+    pub capabilities: ModelCapabilities,
+}
+
+/// How the router picks among the registry models able to serve a request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RoutingPolicy {
+    /// Lowest cost tier, then lowest speed tier.
+    #[default]
+    CheapestCapable,
+    /// Lowest speed tier, then lowest cost tier.
+    Fastest,
+    /// This model whenever it is capable, otherwise the cheapest capable one.
+    Pinned(String),
+}
+
+impl std::str::FromStr for RoutingPolicy {
+    type Err = String;
+
+    /// Parses `cheapest`, `fastest` or `pinned:<model>`.
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.trim() {
+            "" | "cheapest" | "cheapest-capable" => Ok(Self::CheapestCapable),
+            "fastest" => Ok(Self::Fastest),
+            other => match other.strip_prefix("pinned:") {
+                Some(model) if !model.is_empty() => Ok(Self::Pinned(model.to_string())),
+                _ => Err(format!(
+                    "Unknown routing policy \"{other}\"; expected cheapest, fastest or pinned:<model>"
+                )),
+            },
+        }
+    }
+}
+
 /// Multi-model router — routes requests to different provider+model combos
 /// based on a task hint encoded in the model parameter.
 ///
 /// The model parameter can be:
 /// - A regular model name (e.g. "anthropic/claude-sonnet-4-20250514") → uses default provider
 /// - A hint-prefixed string (e.g. "hint:reasoning") → resolves via route table
+/// - [`AUTO_MODEL`] → the registry model the [`RoutingPolicy`] prefers among
+///   those with the context size, vision and tool calling the request needs
 ///
 /// This wraps multiple pre-created providers and selects the right one per request.
 pub struct RouterProvider {
@@ -23,6 +87,9 @@ pub struct RouterProvider {
     providers: Vec<(String, Box<dyn Provider>)>,
     default_index: usize,
     default_model: String,
+    /// Registry models resolved to provider indices
+    registry: Vec<(usize, String, ModelCapabilities)>,
+    policy: RoutingPolicy,
 }
 
 impl RouterProvider {
@@ -65,7 +132,120 @@ impl RouterProvider {
             providers,
             default_index: 0,
             default_model,
+            registry: Vec::new(),
+            policy: RoutingPolicy::default(),
+        }
+    }
+
+    /// Models to choose from for [`AUTO_MODEL`] requests, and how to choose.
+    /// Entries naming an unknown provider are skipped.
+    #[must_use]
+    pub fn with_model_registry(mut self, models: Vec<ModelEntry>, policy: RoutingPolicy) -> Self {
+        self.registry = models
+            .into_iter()
+            .filter_map(|entry| {
+                let index = self
+                    .providers
+                    .iter()
+                    .position(|(name, _)| *name == entry.provider_name);
+                if index.is_none() {
+                    tracing::warn!(
+                        model = entry.model,
+                        provider = entry.provider_name,
+                        "Registry model references unknown provider, skipping"
+                    );
+                }
+                index.map(|i| (i, entry.model, entry.capabilities))
+            })
+            .collect();
+        self.policy = policy;
+        self
+    }
+
+    /// Registry model for a request sending `messages`: the policy's pick
+    /// among models that fit the prompt and offer vision and tool calling
+    /// where needed. When none fits, the largest-context model offering the
+    /// features is used; `None` when the registry is empty.
+    fn select(&self, messages: &[ChatMessage], needs_tools: bool) -> Option<(usize, String)> {
+        if self.registry.is_empty() {
+            return None;
+        }
+        let needs_vision = messages.iter().any(|m| match &m.content {
+            MessageContent::Parts(parts) => parts.iter().any(|p| {
+                matches!(
+                    p,
+                    ContentPart::ImageUrl { .. } | ContentPart::ImageBase64 { .. }
+                )
+            }),
+            MessageContent::Text(_) => false,
+        });
+        let has_features = |caps: &ModelCapabilities| {
+            (caps.vision || !needs_vision) && (caps.tools || !needs_tools)
+        };
+        let fits = |model: &str, caps: &ModelCapabilities| {
+            let tokenizer = Tokenizer::for_model(model);
+            let prompt: usize = messages.iter().map(|m| tokenizer.count_message(m)).sum();
+            prompt + RESPONSE_RESERVE_TOKENS <= caps.context_window
+        };
+
+        let capable: Vec<_> = self
+            .registry
+            .iter()
+            .filter(|(_, model, caps)| has_features(caps) && fits(model, caps))
+            .collect();
+        let cheapest = || {
+            capable
+                .iter()
+                .min_by_key(|(_, _, caps)| (caps.cost_tier, caps.speed_tier))
+                .copied()
+        };
+        let chosen = match &self.policy {
+            RoutingPolicy::CheapestCapable => cheapest(),
+            RoutingPolicy::Fastest => capable
+                .iter()
+                .min_by_key(|(_, _, caps)| (caps.speed_tier, caps.cost_tier))
+                .copied(),
+            RoutingPolicy::Pinned(pinned) => capable
+                .iter()
+                .find(|(_, model, _)| model == pinned)
+                .copied()
+                .or_else(cheapest),
+        };
+
+        let (index, model, _) = chosen.or_else(|| {
+            tracing::warn!("No registry model can serve this request; using the largest");
+            // Prefer models with the needed features, then the largest.
+            self.registry
+                .iter()
+                .max_by_key(|(_, _, caps)| (has_features(caps), caps.context_window))
+        })?;
+        Some((*index, model.clone()))
+    }
+
+    /// Like [`Self::resolve`], but picks from the registry for [`AUTO_MODEL`].
+    fn route(&self, model: &str, messages: &[ChatMessage], needs_tools: bool) -> (usize, String) {
+        if model == AUTO_MODEL {
+            if let Some(selected) = self.select(messages, needs_tools) {
+                return selected;
+            }
         }
+        self.resolve(model)
+    }
+
+    /// [`Self::route`] for a single-turn request.
+    fn route_single(
+        &self,
+        model: &str,
+        system_prompt: Option<&str>,
+        message: &str,
+    ) -> (usize, String) {
+        if model != AUTO_MODEL {
+            return self.resolve(model);
+        }
+        let mut messages: Vec<ChatMessage> =
+            system_prompt.map(ChatMessage::system).into_iter().collect();
+        messages.push(ChatMessage::user(message));
+        self.route(model, &messages, false)
     }
 
     /// Resolve a model parameter to a (provider, `actual_model`) pair.
@@ -98,7 +278,7 @@ impl Provider for RouterProvider {
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
-        let (provider_idx, resolved_model) = self.resolve(model);
+        let (provider_idx, resolved_model) = self.route_single(model, system_prompt, message);
 
         let (provider_name, provider) = &self.providers[provider_idx];
         tracing::info!(
@@ -112,19 +292,124 @@ impl Provider for RouterProvider {
             .await
     }
 
+    async fn chat_with_options(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+        options: &ChatOptions,
+    ) -> anyhow::Result<String> {
+        let (provider_idx, resolved_model) = self.route_single(model, system_prompt, message);
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_with_options(
+                system_prompt,
+                message,
+                &resolved_model,
+                temperature,
+                options,
+            )
+            .await
+    }
+
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let (provider_idx, resolved_model) = self.route_single(model, system_prompt, message);
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_with_params(system_prompt, message, &resolved_model, params)
+            .await
+    }
+
     async fn chat_with_history(
         &self,
         messages: &[ChatMessage],
         model: &str,
         temperature: f64,
     ) -> anyhow::Result<String> {
-        let (provider_idx, resolved_model) = self.resolve(model);
+        let (provider_idx, resolved_model) = self.route(model, messages, false);
         let (_, provider) = &self.providers[provider_idx];
         provider
             .chat_with_history(messages, &resolved_model, temperature)
             .await
     }
 
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        let (provider_idx, resolved_model) = self.route(model, messages, false);
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_with_history_params(messages, &resolved_model, params)
+            .await
+    }
+
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
+        let (provider_idx, resolved_model) = self.route(model, messages, false);
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_response(messages, &resolved_model, params)
+            .await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+        chunks: &tokio::sync::mpsc::Sender<String>,
+    ) -> anyhow::Result<String> {
+        let (provider_idx, resolved_model) = self.route(model, messages, false);
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_stream(messages, &resolved_model, temperature, chunks)
+            .await
+    }
+
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<ChatResponse> {
+        let (provider_idx, resolved_model) = if model == AUTO_MODEL {
+            let history = ConversationMessage::to_chat_messages(messages);
+            self.route(model, &history, !tools.is_empty())
+        } else {
+            self.resolve(model)
+        };
+        let (_, provider) = &self.providers[provider_idx];
+        provider
+            .chat_with_tools(messages, tools, &resolved_model, temperature)
+            .await
+    }
+
+    async fn embed(&self, texts: &[String], model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        let (provider_idx, resolved_model) = self.resolve(model);
+        let (_, provider) = &self.providers[provider_idx];
+        provider.embed(texts, &resolved_model).await
+    }
+
+    async fn list_models(&self) -> anyhow::Result<Vec<ModelInfo>> {
+        let (_, provider) = &self.providers[self.default_index];
+        provider.list_models().await
+    }
+
     async fn warmup(&self) -> anyhow::Result<()> {
         for (name, provider) in &self.providers {
             tracing::info!(provider = name, "Warming up routed provider");
@@ -355,4 +640,77 @@ mod tests {
         assert_eq!(result, "response");
         assert_eq!(mock.call_count(), 1);
     }
+
+    fn registry_model(
+        provider: &str,
+        model: &str,
+        context_window: usize,
+        vision: bool,
+        cost_tier: u8,
+        speed_tier: u8,
+    ) -> ModelEntry {
+        ModelEntry {
+            provider_name: provider.into(),
+            model: model.into(),
+            capabilities: ModelCapabilities {
+                context_window,
+                vision,
+                tools: true,
+                cost_tier,
+                speed_tier,
+            },
+        }
+    }
+
+    fn registry() -> Vec<ModelEntry> {
+        vec![
+            registry_model("cheap", "small", 4_096, false, 1, 2),
+            registry_model("fast", "quick", 32_000, false, 2, 1),
+            registry_model("big", "vision-xl", 200_000, true, 3, 3),
+        ]
+    }
+
+    #[tokio::test]
+    async fn auto_picks_the_policy_choice_among_capable_models() {
+        let providers = [("cheap", "c"), ("fast", "f"), ("big", "b")];
+        let (router, mocks) = make_router(&providers, &[]);
+        let router = router.with_model_registry(registry(), RoutingPolicy::CheapestCapable);
+
+        assert_eq!(router.chat("hi", AUTO_MODEL, 0.0).await.unwrap(), "c");
+        assert_eq!(mocks[0].last_model(), "small");
+
+        // Too long for the small model's 4k window.
+        let long = "word ".repeat(4_000);
+        assert_eq!(router.chat(&long, AUTO_MODEL, 0.0).await.unwrap(), "f");
+
+        // Only the big model takes images.
+        let image = [ChatMessage::user_parts(vec![ContentPart::ImageUrl {
+            url: "https://example.com/cat.png".into(),
+            detail: None,
+        }])];
+        assert_eq!(router.route(AUTO_MODEL, &image, false).1, "vision-xl");
+
+        // Explicit models bypass the registry.
+        router.chat("hi", "my-model", 0.0).await.unwrap();
+        assert_eq!(mocks[0].last_model(), "my-model");
+    }
+
+    #[test]
+    fn fastest_and_pinned_policies() {
+        let providers = [("cheap", "c"), ("fast", "f"), ("big", "b")];
+        let hello = [ChatMessage::user("hi")];
+
+        let (router, _) = make_router(&providers, &[]);
+        let router = router.with_model_registry(registry(), RoutingPolicy::Fastest);
+        assert_eq!(router.route(AUTO_MODEL, &hello, false), (1, "quick".into()));
+
+        let (router, _) = make_router(&providers, &[]);
+        let router = router.with_model_registry(registry(), "pinned:vision-xl".parse().unwrap());
+        assert_eq!(
+            router.route(AUTO_MODEL, &hello, false),
+            (2, "vision-xl".into())
+        );
+
+        assert!("slowest".parse::<RoutingPolicy>().is_err());
+    }
 }
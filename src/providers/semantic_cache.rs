@@ -0,0 +1,171 @@
+//! Opt-in semantic layer over the exact-key response cache: prompts are
+//! embedded and a cached answer is reused for a new prompt whose embedding is
+//! close enough, so near-identical questions skip the provider entirely.
+
+use crate::memory::embeddings::EmbeddingProvider;
+use crate::memory::vector::cosine_similarity;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+struct SemanticEntry {
+    /// Everything besides the embedded prompt that must match exactly:
+    /// model, sampling params, system prompt or earlier turns.
+    scope: String,
+    embedding: Vec<f32>,
+    response: String,
+    provider: String,
+    inserted_at: Instant,
+}
+
+/// A cached answer matched by similarity.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SemanticHit {
+    pub(crate) response: String,
+    pub(crate) provider: String,
+    pub(crate) similarity: f32,
+}
+
+/// Embeds prompts with `embedder` and serves a cached answer when a prompt's
+/// cosine similarity to a cached one reaches `threshold`.
+pub struct SemanticCache {
+    embedder: Arc<dyn EmbeddingProvider>,
+    threshold: f32,
+    max_entries: usize,
+    entries: Mutex<VecDeque<SemanticEntry>>,
+}
+
+impl SemanticCache {
+    /// `threshold` is clamped to `0.0..=1.0`; around 0.95 reuses answers only
+    /// for rephrasings of the same question.
+    pub fn new(embedder: Arc<dyn EmbeddingProvider>, threshold: f32) -> Self {
+        Self {
+            embedder,
+            threshold: threshold.clamp(0.0, 1.0),
+            max_entries: 1024,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Most prompts kept; the oldest is dropped first. Lookups scan every
+    /// entry, so keep this modest.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Embedding of `prompt`; an embedder returning nothing disables the
+    /// lookup rather than matching everything.
+    pub(crate) async fn embed(&self, prompt: &str) -> anyhow::Result<Vec<f32>> {
+        let embedding = self.embedder.embed_one(prompt).await?;
+        if embedding.is_empty() {
+            anyhow::bail!("Embedder {} returned an empty vector", self.embedder.name());
+        }
+        Ok(embedding)
+    }
+
+    /// Most similar answer in `scope` at least `threshold` similar and no
+    /// older than `max_age`.
+    pub(crate) fn lookup(
+        &self,
+        scope: &str,
+        embedding: &[f32],
+        max_age: Duration,
+        now: Instant,
+    ) -> Option<SemanticHit> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .iter()
+            .filter(|entry| {
+                entry.scope == scope && now.duration_since(entry.inserted_at) <= max_age
+            })
+            .map(|entry| (entry, cosine_similarity(embedding, &entry.embedding)))
+            .filter(|(_, similarity)| *similarity >= self.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entry, similarity)| SemanticHit {
+                response: entry.response.clone(),
+                provider: entry.provider.clone(),
+                similarity,
+            })
+    }
+
+    pub(crate) fn insert(
+        &self,
+        scope: String,
+        embedding: Vec<f32>,
+        response: String,
+        provider: String,
+        now: Instant,
+    ) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        while entries.len() >= self.max_entries {
+            entries.pop_front();
+        }
+        entries.push_back(SemanticEntry {
+            scope,
+            embedding,
+            response,
+            provider,
+            inserted_at: now,
+        });
+    }
+
+    /// Drop entries whose scope starts with `prefix`, returning how many.
+    pub(crate) fn remove_prefix(&self, prefix: &str) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = entries.len();
+        entries.retain(|entry| !entry.scope.starts_with(prefix));
+        before - entries.len()
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedEmbedding;
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for FixedEmbedding {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn dimensions(&self) -> usize {
+            2
+        }
+
+        async fn embed(&self, _texts: &[&str]) -> anyhow::Result<Vec<Vec<f32>>> {
+            Ok(vec![vec![1.0, 0.0]])
+        }
+    }
+
+    #[test]
+    fn lookup_respects_threshold_scope_and_age() {
+        let cache = SemanticCache::new(Arc::new(FixedEmbedding), 0.9);
+        let now = Instant::now();
+        let ttl = Duration::from_secs(60);
+        cache.insert("m".into(), vec![1.0, 0.0], "yes".into(), "p".into(), now);
+
+        let hit = cache.lookup("m", &[0.99, 0.05], ttl, now).unwrap();
+        assert_eq!(hit.response, "yes");
+        assert!(hit.similarity > 0.9);
+
+        assert!(cache.lookup("m", &[0.5, 0.5], ttl, now).is_none());
+        assert!(cache.lookup("other", &[1.0, 0.0], ttl, now).is_none());
+        assert!(cache
+            .lookup("m", &[1.0, 0.0], ttl, now + Duration::from_secs(61))
+            .is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Token counting for prompts, so conversations can be fitted to a model's
+//! context window before they are sent instead of being refused upstream.
+//!
+//! `OpenAI` models get a tiktoken-compatible count: text is split with the
+//! pre-tokenization pattern of their encoding and each piece is charged for
+//! the merges its vocabulary typically manages. Without the vocabulary files
+//! this is an estimate, but it tracks real counts far better than a flat
+//! character ratio, which remains the fallback for other models.
+
+use super::traits::{ChatMessage, ContentPart, MessageContent};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Charged per image or audio part; providers bill these by size, so this
+/// is only a rough allowance.
+const MEDIA_PART_TOKENS: usize = 1_000;
+
+/// Role and separator tokens wrapped around every chat message.
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
+/// Token counting scheme for a model family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// `o200k_base`: GPT-4o, GPT-4.1, GPT-5 and the o-series reasoning models.
+    O200k,
+    /// `cl100k_base`: GPT-4, GPT-3.5 and the `text-embedding-3` models.
+    Cl100k,
+    /// About four characters per token, for models with unpublished tokenizers.
+    Heuristic,
+}
+
+impl Tokenizer {
+    /// Tokenizer for `model`, ignoring any `provider/` prefix.
+    pub fn for_model(model: &str) -> Self {
+        let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|p| name.starts_with(p));
+        if starts(&[
+            "gpt-4o",
+            "gpt-4.1",
+            "gpt-4.5",
+            "gpt-5",
+            "o1",
+            "o3",
+            "o4",
+            "chatgpt-4o",
+        ]) {
+            Self::O200k
+        } else if starts(&["gpt-4", "gpt-3.5", "text-embedding-3", "text-embedding-ada"]) {
+            Self::Cl100k
+        } else {
+            Self::Heuristic
+        }
+    }
+
+    /// Tokens in `text`.
+    pub fn count(self, text: &str) -> usize {
+        match self {
+            Self::Heuristic => text.chars().count().div_ceil(4),
+            Self::O200k | Self::Cl100k => pre_tokenizer()
+                .find_iter(text)
+                .map(|piece| self.piece_tokens(piece.as_str()))
+                .sum(),
+        }
+    }
+
+    /// Tokens one pre-tokenized piece is likely to merge into: common words
+    /// are a single token, longer runs split every few characters, and
+    /// non-Latin text costs close to a token per character.
+    fn piece_tokens(self, piece: &str) -> usize {
+        if piece.chars().all(char::is_whitespace) {
+            return 1;
+        }
+        if piece.chars().all(|c| !c.is_alphanumeric()) {
+            return piece.trim().chars().count().div_ceil(2).max(1);
+        }
+        // The space or symbol a word starts with merges into its first token.
+        let word = piece
+            .strip_prefix(|c: char| !c.is_alphanumeric())
+            .unwrap_or(piece);
+        let (ascii, other) = word.chars().fold((0usize, 0usize), |(ascii, other), c| {
+            if c.is_ascii() {
+                (ascii + 1, other)
+            } else {
+                (ascii, other + 1)
+            }
+        });
+        let (ascii_per_token, other_tokens) = match self {
+            // o200k's larger vocabulary merges more of everything.
+            Self::O200k => (7, (other * 2).div_ceil(3)),
+            _ => (6, other),
+        };
+        (ascii.div_ceil(ascii_per_token) + other_tokens).max(1)
+    }
+
+    /// Tokens `message` occupies in a chat request, including its role
+    /// framing and a flat allowance per image or audio part.
+    pub fn count_message(self, message: &ChatMessage) -> usize {
+        let content = match &message.content {
+            MessageContent::Text(text) => self.count(text),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .map(|part| match part {
+                    ContentPart::Text(text) => self.count(text),
+                    _ => MEDIA_PART_TOKENS,
+                })
+                .sum(),
+        };
+        content + self.count(&message.role) + MESSAGE_OVERHEAD_TOKENS
+    }
+}
+
+/// The split pattern shared by `cl100k_base` and `o200k_base`, restated with
+/// the character classes the `regex` crate supports and without the
+/// lookahead that keeps trailing whitespace apart.
+fn pre_tokenizer() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"'(?i:s|t|re|ve|m|ll|d)|[^\r\n\w]?[^\W\d_]+|\d{1,3}| ?[^\s\w]+[\r\n]*|\s*[\r\n]+|\s+",
+        )
+        .expect("pre-tokenizer pattern is valid")
+    })
+}
+
+/// Context window of well-known models in tokens, or `None` when unknown.
+pub fn context_window(model: &str) -> Option<usize> {
+    const WINDOWS: &[(&str, usize)] = &[
+        ("gpt-4.1", 1_047_576),
+        ("gpt-5", 400_000),
+        ("gpt-4o", 128_000),
+        ("chatgpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("gpt-4-32k", 32_768),
+        ("gpt-4", 8_192),
+        ("gpt-3.5-turbo", 16_385),
+        ("o1-mini", 128_000),
+        ("o1", 200_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("claude", 200_000),
+        ("gemini-1.5", 1_048_576),
+        ("gemini-2", 1_048_576),
+        ("llama3.1", 131_072),
+        ("llama3.2", 131_072),
+        ("llama3", 8_192),
+        ("mistral", 32_768),
+    ];
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    WINDOWS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_tokenizers_by_model_family() {
+        assert_eq!(Tokenizer::for_model("gpt-4o-mini"), Tokenizer::O200k);
+        assert_eq!(
+            Tokenizer::for_model("openai/gpt-4-turbo"),
+            Tokenizer::Cl100k
+        );
+        assert_eq!(
+            Tokenizer::for_model("claude-sonnet-4"),
+            Tokenizer::Heuristic
+        );
+        assert_eq!(context_window("openai/gpt-4o"), Some(128_000));
+        assert_eq!(context_window("gpt-4-0613"), Some(8_192));
+        assert_eq!(context_window("my-finetune"), None);
+    }
+
+    #[test]
+    fn counts_track_tiktoken_for_plain_english() {
+        // tiktoken reports 9 tokens for this sentence in both encodings.
+        let text = "The quick brown fox jumps over the lazy dog.";
+        for tokenizer in [Tokenizer::O200k, Tokenizer::Cl100k] {
+            let count = tokenizer.count(text);
+            assert!((8..=11).contains(&count), "{tokenizer:?} counted {count}");
+        }
+        assert_eq!(Tokenizer::Heuristic.count(text), 11);
+        assert_eq!(Tokenizer::O200k.count(""), 0);
+
+        let message = ChatMessage::user(text);
+        assert!(Tokenizer::Cl100k.count_message(&message) > Tokenizer::Cl100k.count(text));
+    }
+}
@@ -1,38 +1,213 @@
+use super::error::ProviderError;
+use super::metering::Usage;
+use crate::tools::ToolSpec;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write;
 
 /// A single message in a conversation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
-    pub content: String,
+    pub content: MessageContent,
 }
 
 impl ChatMessage {
     pub fn system(content: impl Into<String>) -> Self {
         Self {
             role: "system".into(),
-            content: content.into(),
+            content: MessageContent::Text(content.into()),
         }
     }
 
     pub fn user(content: impl Into<String>) -> Self {
         Self {
             role: "user".into(),
-            content: content.into(),
+            content: MessageContent::Text(content.into()),
         }
     }
 
     pub fn assistant(content: impl Into<String>) -> Self {
         Self {
             role: "assistant".into(),
-            content: content.into(),
+            content: MessageContent::Text(content.into()),
+        }
+    }
+
+    /// User message made of text and image parts.
+    pub fn user_parts(parts: Vec<ContentPart>) -> Self {
+        Self {
+            role: "user".into(),
+            content: MessageContent::Parts(parts),
+        }
+    }
+
+    /// Text of the message; see [`MessageContent::text`].
+    pub fn text(&self) -> Cow<'_, str> {
+        self.content.text()
+    }
+}
+
+/// Message body: plain text, or a list of parts when images are attached.
+///
+/// Serializes as a bare string for `Text`, matching the text-only wire
+/// format, and as the OpenAI-compatible `content` array for `Parts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// Text parts joined with newlines; images and audio are skipped.
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Text(text) => Cow::Borrowed(text),
+            Self::Parts(parts) => Cow::Owned(
+                parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        }
+    }
+
+    /// Same content with `f` applied to every piece of text.
+    pub fn map_text(&self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            Self::Text(text) => Self::Text(f(text)),
+            Self::Parts(parts) => Self::Parts(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        ContentPart::Text(text) => ContentPart::Text(f(text)),
+                        media => media.clone(),
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+/// One part of a multi-part message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "ContentPartWire", into = "ContentPartWire")]
+pub enum ContentPart {
+    Text(String),
+    /// Image by URL (`https://` or a `data:` URI); `detail` is the
+    /// OpenAI-style resolution hint (`low`, `high`, `auto`).
+    ImageUrl {
+        url: String,
+        detail: Option<String>,
+    },
+    /// Base64-encoded image bytes, e.g. a photo received on a channel.
+    ImageBase64 {
+        media_type: String,
+        data: String,
+    },
+    /// Base64-encoded audio; `format` is the file extension (`wav`, `mp3`).
+    Audio {
+        format: String,
+        data: String,
+    },
+}
+
+impl ContentPart {
+    /// Media type and base64 payload of an image or audio part carried
+    /// inline, including images given as `data:` URIs. `None` for text and
+    /// for images that must be fetched from a URL.
+    pub fn inline_data(&self) -> Option<(Cow<'_, str>, &str)> {
+        match self {
+            Self::Text(_) => None,
+            Self::ImageUrl { url, .. } => {
+                let (media_type, data) = url.strip_prefix("data:")?.split_once(";base64,")?;
+                Some((Cow::Borrowed(media_type), data))
+            }
+            Self::ImageBase64 { media_type, data } => Some((Cow::Borrowed(media_type), data)),
+            Self::Audio { format, data } => Some((Cow::Owned(format!("audio/{format}")), data)),
+        }
+    }
+}
+
+/// OpenAI-compatible wire shape of a content part. Base64 images travel as
+/// `data:` URIs, so they read back as [`ContentPart::ImageUrl`].
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPartWire {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrlWire },
+    InputAudio { input_audio: InputAudioWire },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct InputAudioWire {
+    data: String,
+    format: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ImageUrlWire {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl From<ContentPartWire> for ContentPart {
+    fn from(wire: ContentPartWire) -> Self {
+        match wire {
+            ContentPartWire::Text { text } => Self::Text(text),
+            ContentPartWire::ImageUrl { image_url } => Self::ImageUrl {
+                url: image_url.url,
+                detail: image_url.detail,
+            },
+            ContentPartWire::InputAudio { input_audio } => Self::Audio {
+                format: input_audio.format,
+                data: input_audio.data,
+            },
+        }
+    }
+}
+
+impl From<ContentPart> for ContentPartWire {
+    fn from(part: ContentPart) -> Self {
+        match part {
+            ContentPart::Text(text) => Self::Text { text },
+            ContentPart::ImageUrl { url, detail } => Self::ImageUrl {
+                image_url: ImageUrlWire { url, detail },
+            },
+            ContentPart::ImageBase64 { media_type, data } => Self::ImageUrl {
+                image_url: ImageUrlWire {
+                    url: format!("data:{media_type};base64,{data}"),
+                    detail: None,
+                },
+            },
+            ContentPart::Audio { format, data } => Self::InputAudio {
+                input_audio: InputAudioWire { data, format },
+            },
         }
     }
 }
 
 /// A tool call requested by the LLM.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
@@ -40,12 +215,32 @@ pub struct ToolCall {
 }
 
 /// An LLM response that may contain text, tool calls, or both.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ChatResponse {
     /// Text content of the response (may be empty if only tool calls).
     pub text: Option<String>,
     /// Tool calls requested by the LLM.
     pub tool_calls: Vec<ToolCall>,
+    /// Tokens the provider billed for the call, when it reports them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    /// Model that answered as the provider names it, which may be a dated
+    /// snapshot of the requested alias.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Why generation ended (`stop`, `length`, `tool_calls`, ...), in the
+    /// provider's own vocabulary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+}
+
+impl From<String> for ChatResponse {
+    fn from(text: String) -> Self {
+        Self {
+            text: Some(text),
+            ..Self::default()
+        }
+    }
 }
 
 impl ChatResponse {
@@ -58,6 +253,12 @@ impl ChatResponse {
     pub fn text_or_empty(&self) -> &str {
         self.text.as_deref().unwrap_or("")
     }
+
+    /// The text, failing when the provider answered with nothing.
+    pub fn into_text(self, provider: &str) -> anyhow::Result<String> {
+        self.text
+            .ok_or_else(|| anyhow::anyhow!("No response from {provider}"))
+    }
 }
 
 /// A tool result to feed back to the LLM.
@@ -82,6 +283,107 @@ pub enum ConversationMessage {
     ToolResult(ToolResultMessage),
 }
 
+impl ConversationMessage {
+    /// Flatten a tool conversation into plain chat messages for providers
+    /// without native tool calling: tool calls become `<tool_call>` blocks in
+    /// the assistant's text and results become `<tool_result>` user messages.
+    pub fn to_chat_messages(messages: &[Self]) -> Vec<ChatMessage> {
+        messages
+            .iter()
+            .map(|message| match message {
+                Self::Chat(chat) => chat.clone(),
+                Self::AssistantToolCalls { text, tool_calls } => {
+                    let mut content = text.clone().unwrap_or_default();
+                    for call in tool_calls {
+                        let arguments = serde_json::from_str(&call.arguments)
+                            .unwrap_or_else(|_| serde_json::Value::String(call.arguments.clone()));
+                        let call = serde_json::json!({"name": call.name, "arguments": arguments});
+                        let _ = write!(content, "\n<tool_call>{call}</tool_call>");
+                    }
+                    ChatMessage::assistant(content.trim_start())
+                }
+                Self::ToolResult(result) => ChatMessage::user(format!(
+                    "[Tool results]\n<tool_result id=\"{}\">\n{}\n</tool_result>",
+                    result.tool_call_id, result.content
+                )),
+            })
+            .collect()
+    }
+}
+
+/// A model advertised by a provider.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Maximum context length in tokens, when the provider reports it.
+    pub context_window: Option<u32>,
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+}
+
+/// Per-call options for wrappers that add behavior around a provider call
+/// (response cache, coalescing). Plain providers ignore them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatOptions {
+    /// Never serve this call from the response cache nor store its result.
+    /// Also opts out of request coalescing, which shares results between callers.
+    pub bypass_cache: bool,
+    /// Upper bound on wall-clock time across all retries and fallbacks.
+    pub deadline: Option<std::time::Duration>,
+    /// Best-effort call: on a cache miss, make one attempt against the first
+    /// provider whose circuit admits it, with no retries, hedging or fallback.
+    pub fast_mode: bool,
+    /// Ask for a single JSON value. Providers with a native JSON mode enable
+    /// it; others rely on the prompt asking for JSON.
+    pub json_output: bool,
+}
+
+/// Sampling controls for a single completion.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SamplingParams {
+    pub temperature: f64,
+    /// Nucleus sampling cutoff; `None` leaves the provider default.
+    pub top_p: Option<f64>,
+    /// Upper bound on generated tokens; `None` leaves the provider default.
+    pub max_tokens: Option<u32>,
+    /// Sequences that end generation when produced.
+    pub stop: Vec<String>,
+    /// Seed for reproducible sampling, where the provider supports one.
+    pub seed: Option<u64>,
+}
+
+impl SamplingParams {
+    /// Params carrying only a temperature, matching the temperature-only methods.
+    pub fn new(temperature: f64) -> Self {
+        Self {
+            temperature,
+            ..Self::default()
+        }
+    }
+}
+
+/// One conversation of a [`Provider::chat_batch`] call.
+#[derive(Debug, Clone)]
+pub struct BatchRequest {
+    pub messages: Vec<ChatMessage>,
+    pub model: String,
+    pub params: SamplingParams,
+}
+
+impl BatchRequest {
+    pub fn new(
+        messages: Vec<ChatMessage>,
+        model: impl Into<String>,
+        params: SamplingParams,
+    ) -> Self {
+        Self {
+            messages,
+            model: model.into(),
+            params,
+        }
+    }
+}
+
 #[async_trait]
 pub trait Provider: Send + Sync {
     async fn chat(&self, message: &str, model: &str, temperature: f64) -> anyhow::Result<String> {
@@ -97,6 +399,33 @@ pub trait Provider: Send + Sync {
         temperature: f64,
     ) -> anyhow::Result<String>;
 
+    /// Single-turn chat with per-call options. Default implementation ignores
+    /// the options and delegates to `chat_with_system`.
+    async fn chat_with_options(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        temperature: f64,
+        _options: &ChatOptions,
+    ) -> anyhow::Result<String> {
+        self.chat_with_system(system_prompt, message, model, temperature)
+            .await
+    }
+
+    /// Single-turn chat with full sampling control. Default implementation
+    /// only honors `params.temperature` and delegates to `chat_with_system`.
+    async fn chat_with_params(
+        &self,
+        system_prompt: Option<&str>,
+        message: &str,
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_with_system(system_prompt, message, model, params.temperature)
+            .await
+    }
+
     /// Multi-turn conversation. Default implementation extracts the last user
     /// message and delegates to `chat_with_system`.
     async fn chat_with_history(
@@ -108,13 +437,110 @@ pub trait Provider: Send + Sync {
         let system = messages
             .iter()
             .find(|m| m.role == "system")
-            .map(|m| m.content.as_str());
+            .map(ChatMessage::text);
         let last_user = messages
             .iter()
             .rfind(|m| m.role == "user")
-            .map_or("", |m| m.content.as_str());
-        self.chat_with_system(system, last_user, model, temperature)
+            .map(ChatMessage::text)
+            .unwrap_or_default();
+        self.chat_with_system(system.as_deref(), &last_user, model, temperature)
+            .await
+    }
+
+    /// Multi-turn conversation whose reply is sent to `chunks` piece by piece
+    /// as it is generated; returns the whole reply. The default sends the
+    /// `chat_with_history` reply as a single chunk, as do wrappers that retry,
+    /// since a partly streamed attempt cannot be taken back.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        temperature: f64,
+        chunks: &tokio::sync::mpsc::Sender<String>,
+    ) -> anyhow::Result<String> {
+        let text = self.chat_with_history(messages, model, temperature).await?;
+        // A closed receiver only means nobody is watching anymore.
+        let _ = chunks.send(text.clone()).await;
+        Ok(text)
+    }
+
+    /// Multi-turn conversation with full sampling control. Default
+    /// implementation only honors `params.temperature` and delegates to
+    /// `chat_with_history`.
+    async fn chat_with_history_params(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<String> {
+        self.chat_with_history(messages, model, params.temperature)
+            .await
+    }
+
+    /// [`Self::chat_with_history_params`] with the usage, model and finish
+    /// reason the provider reported. The string-returning methods are
+    /// conveniences over this for providers that report them; the default
+    /// wraps `chat_with_history_params` and reports nothing.
+    async fn chat_response(
+        &self,
+        messages: &[ChatMessage],
+        model: &str,
+        params: &SamplingParams,
+    ) -> anyhow::Result<ChatResponse> {
+        self.chat_with_history_params(messages, model, params)
             .await
+            .map(ChatResponse::from)
+    }
+
+    /// Answer each of `requests`, in order, for offline work where latency
+    /// does not matter. The default runs them one after another through
+    /// `chat_with_history_params`; providers with a native batch endpoint
+    /// submit them as one job instead.
+    async fn chat_batch(&self, requests: &[BatchRequest]) -> Vec<anyhow::Result<String>> {
+        let mut results = Vec::with_capacity(requests.len());
+        for request in requests {
+            results.push(
+                self.chat_with_history_params(&request.messages, &request.model, &request.params)
+                    .await,
+            );
+        }
+        results
+    }
+
+    /// Conversation turn that may answer with tool calls. Providers with native
+    /// function calling send `tools` as schemas and return structured calls.
+    /// The default flattens `messages` (see
+    /// [`ConversationMessage::to_chat_messages`]) into `chat_with_history`
+    /// without sending the schemas, so callers must describe the tools in the
+    /// prompt and parse calls out of the returned text.
+    async fn chat_with_tools(
+        &self,
+        messages: &[ConversationMessage],
+        _tools: &[ToolSpec],
+        model: &str,
+        temperature: f64,
+    ) -> anyhow::Result<ChatResponse> {
+        let history = ConversationMessage::to_chat_messages(messages);
+        let text = self.chat_with_history(&history, model, temperature).await?;
+        Ok(ChatResponse::from(text))
+    }
+
+    /// Models this provider can serve. Default implementation reports that
+    /// listing is unsupported.
+    async fn list_models(&self) -> anyhow::Result<Vec<ModelInfo>> {
+        anyhow::bail!("Model listing is not supported by this provider")
+    }
+
+    /// Embed each of `texts` with embedding model `model`, in order. The
+    /// default refuses with a [`ProviderError::BadRequest`], so a reliable
+    /// chain moves on to a provider that can embed without retrying or
+    /// holding it against this one's circuit.
+    async fn embed(&self, _texts: &[String], _model: &str) -> anyhow::Result<Vec<Vec<f32>>> {
+        Err(ProviderError::BadRequest {
+            status: 400,
+            message: "Embeddings are not supported by this provider".into(),
+        }
+        .into())
     }
 
     /// Warm up the HTTP connection pool (TLS handshake, DNS, HTTP/2 setup).
@@ -132,7 +558,7 @@ mod tests {
     fn chat_message_constructors() {
         let sys = ChatMessage::system("Be helpful");
         assert_eq!(sys.role, "system");
-        assert_eq!(sys.content, "Be helpful");
+        assert_eq!(sys.text(), "Be helpful");
 
         let user = ChatMessage::user("Hello");
         assert_eq!(user.role, "user");
@@ -142,11 +568,84 @@ mod tests {
     }
 
     #[test]
-    fn chat_response_helpers() {
-        let empty = ChatResponse {
-            text: None,
-            tool_calls: vec![],
+    fn text_only_message_keeps_string_wire_format() {
+        let json = serde_json::to_string(&ChatMessage::user("hi")).unwrap();
+        assert_eq!(json, r#"{"role":"user","content":"hi"}"#);
+
+        let parsed: ChatMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.content, MessageContent::Text("hi".into()));
+    }
+
+    #[test]
+    fn mixed_text_and_image_message_serializes_as_content_array() {
+        let msg = ChatMessage::user_parts(vec![
+            ContentPart::Text("what is this?".into()),
+            ContentPart::ImageUrl {
+                url: "https://example.com/cat.png".into(),
+                detail: None,
+            },
+        ]);
+
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "what is this?"},
+                    {"type": "image_url", "image_url": {"url": "https://example.com/cat.png"}}
+                ]
+            })
+        );
+        let parsed: ChatMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.content, msg.content);
+        assert_eq!(parsed.text(), "what is this?");
+    }
+
+    #[test]
+    fn inline_media_serializes_in_openai_format() {
+        let photo = ContentPart::ImageBase64 {
+            media_type: "image/jpeg".into(),
+            data: "/9j/4AAQ".into(),
+        };
+        let voice = ContentPart::Audio {
+            format: "wav".into(),
+            data: "UklGRg==".into(),
         };
+        let json =
+            serde_json::to_value(ChatMessage::user_parts(vec![photo.clone(), voice.clone()]))
+                .unwrap();
+        assert_eq!(
+            json["content"],
+            serde_json::json!([
+                {"type": "image_url", "image_url": {"url": "data:image/jpeg;base64,/9j/4AAQ"}},
+                {"type": "input_audio", "input_audio": {"data": "UklGRg==", "format": "wav"}}
+            ])
+        );
+
+        let parsed: ChatMessage = serde_json::from_value(json).unwrap();
+        let MessageContent::Parts(parts) = parsed.content else {
+            panic!("expected parts");
+        };
+        assert_eq!(parts[0].inline_data(), photo.inline_data());
+        assert_eq!(parts[1], voice);
+        assert_eq!(
+            voice.inline_data(),
+            Some((Cow::Borrowed("audio/wav"), "UklGRg=="))
+        );
+        assert_eq!(
+            ContentPart::ImageUrl {
+                url: "https://example.com/cat.png".into(),
+                detail: None
+            }
+            .inline_data(),
+            None
+        );
+    }
+
+    #[test]
+    fn chat_response_helpers() {
+        let empty = ChatResponse::default();
         assert!(!empty.has_tool_calls());
         assert_eq!(empty.text_or_empty(), "");
 
@@ -157,6 +656,7 @@ mod tests {
                 name: "shell".into(),
                 arguments: "{}".into(),
             }],
+            ..ChatResponse::default()
         };
         assert!(with_tools.has_tool_calls());
         assert_eq!(with_tools.text_or_empty(), "Let me check");
@@ -187,4 +687,33 @@ mod tests {
         let json = serde_json::to_string(&tool_result).unwrap();
         assert!(json.contains("\"type\":\"ToolResult\""));
     }
+
+    #[test]
+    fn tool_conversation_flattens_for_text_only_providers() {
+        let messages = vec![
+            ConversationMessage::Chat(ChatMessage::user("list files")),
+            ConversationMessage::AssistantToolCalls {
+                text: None,
+                tool_calls: vec![ToolCall {
+                    id: "call_1".into(),
+                    name: "shell".into(),
+                    arguments: r#"{"command":"ls"}"#.into(),
+                }],
+            },
+            ConversationMessage::ToolResult(ToolResultMessage {
+                tool_call_id: "call_1".into(),
+                content: "a.txt".into(),
+            }),
+        ];
+        let flat = ConversationMessage::to_chat_messages(&messages);
+        assert_eq!(flat[1].role, "assistant");
+        assert_eq!(
+            flat[1].text(),
+            r#"<tool_call>{"arguments":{"command":"ls"},"name":"shell"}</tool_call>"#
+        );
+        assert_eq!(flat[2].role, "user");
+        assert!(flat[2]
+            .text()
+            .contains("<tool_result id=\"call_1\">\na.txt\n"));
+    }
 }
@@ -0,0 +1,354 @@
+//! Retry with exponential backoff, shared by providers, channels and tools.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Decides whether a failed attempt may be retried.
+pub type RetryClassifier = Arc<dyn Fn(&anyhow::Error) -> bool + Send + Sync>;
+
+/// How the wait before each retry is derived from the exponential backoff.
+///
+/// The jittered strategies randomize every wait so that many clients failing
+/// at once do not retry in lockstep against the same upstream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackoffStrategy {
+    /// Wait the backoff itself, plus the policy's additive jitter if any.
+    #[default]
+    Exponential,
+    /// Wait a random time between zero and the backoff.
+    FullJitter,
+    /// Wait half the backoff plus a random time up to the other half.
+    EqualJitter,
+    /// Wait a random time between the base backoff and three times the
+    /// previous wait, capped; grows without synchronizing clients.
+    DecorrelatedJitter,
+}
+
+impl std::str::FromStr for BackoffStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "exponential" | "none" => Ok(Self::Exponential),
+            "full" | "full_jitter" => Ok(Self::FullJitter),
+            "equal" | "equal_jitter" => Ok(Self::EqualJitter),
+            "decorrelated" | "decorrelated_jitter" => Ok(Self::DecorrelatedJitter),
+            other => anyhow::bail!("Unknown backoff strategy: {other}"),
+        }
+    }
+}
+
+/// Uniformly random value in `0..=max`.
+fn random_up_to(max: u64) -> u64 {
+    uuid::Uuid::new_v4().as_u64_pair().0 % max.saturating_add(1)
+}
+
+/// How many times to retry an operation, how long to wait in between, and
+/// which errors are worth retrying.
+///
+/// The backoff starts at `base_backoff_ms`, grows by `backoff_multiplier` per
+/// retry and never exceeds `backoff_cap_ms`. With jitter, each wait adds a
+/// random extra of up to `jitter` times the backoff. The [`BackoffStrategy`]
+/// decides how the actual wait is drawn from the backoff.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    strategy: BackoffStrategy,
+    base_backoff_ms: u64,
+    backoff_multiplier: f64,
+    backoff_cap_ms: u64,
+    jitter: f64,
+    is_retryable: RetryClassifier,
+}
+
+impl std::fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("base_backoff_ms", &self.base_backoff_ms)
+            .field("backoff_multiplier", &self.backoff_multiplier)
+            .field("backoff_cap_ms", &self.backoff_cap_ms)
+            .field("jitter", &self.jitter)
+            .field("strategy", &self.strategy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    /// `max_retries` retries after the first attempt, doubling from
+    /// `base_backoff_ms` up to 10s, without jitter, retrying every error.
+    pub fn new(max_retries: u32, base_backoff_ms: u64) -> Self {
+        Self {
+            max_retries,
+            strategy: BackoffStrategy::default(),
+            base_backoff_ms,
+            backoff_multiplier: 2.0,
+            backoff_cap_ms: 10_000.max(base_backoff_ms),
+            jitter: 0.0,
+            is_retryable: Arc::new(|_| true),
+        }
+    }
+
+    /// Growth factor per retry; values below 1.0 (or non-finite) are ignored.
+    pub fn with_backoff_multiplier(mut self, multiplier: f64) -> Self {
+        if multiplier.is_finite() && multiplier >= 1.0 {
+            self.backoff_multiplier = multiplier;
+        }
+        self
+    }
+
+    /// Longest single wait; never below the base backoff.
+    pub fn with_backoff_cap_ms(mut self, cap_ms: u64) -> Self {
+        self.backoff_cap_ms = cap_ms.max(self.base_backoff_ms);
+        self
+    }
+
+    /// Random extra wait of up to `jitter` (clamped to 0.0..=1.0) times the backoff.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = if jitter.is_finite() {
+            jitter.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: BackoffStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Only retry errors for which `classifier` returns true.
+    pub fn with_classifier(
+        mut self,
+        classifier: impl Fn(&anyhow::Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.is_retryable = Arc::new(classifier);
+        self
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    pub fn base_backoff_ms(&self) -> u64 {
+        self.base_backoff_ms
+    }
+
+    pub fn backoff_multiplier(&self) -> f64 {
+        self.backoff_multiplier
+    }
+
+    pub fn backoff_cap_ms(&self) -> u64 {
+        self.backoff_cap_ms
+    }
+
+    pub fn strategy(&self) -> BackoffStrategy {
+        self.strategy
+    }
+
+    pub fn is_retryable(&self, err: &anyhow::Error) -> bool {
+        (self.is_retryable)(err)
+    }
+
+    /// Backoff before the retry after one that waited `backoff_ms`.
+    pub fn next_backoff_ms(&self, backoff_ms: u64) -> u64 {
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let next = (backoff_ms as f64 * self.backoff_multiplier) as u64;
+        next.min(self.backoff_cap_ms)
+    }
+
+    /// How long to actually sleep for a backoff of `backoff_ms`, jitter included.
+    pub fn delay(&self, backoff_ms: u64) -> Duration {
+        if self.jitter <= 0.0 {
+            return Duration::from_millis(backoff_ms);
+        }
+        #[allow(
+            clippy::cast_precision_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss
+        )]
+        let max_extra = (backoff_ms as f64 * self.jitter) as u64;
+        Duration::from_millis(backoff_ms.saturating_add(random_up_to(max_extra)))
+    }
+
+    /// Wait before the next retry under the configured strategy, advancing
+    /// `backoff_ms` (start it at [`Self::base_backoff_ms`]) for the one after.
+    pub fn next_delay(&self, backoff_ms: &mut u64) -> Duration {
+        let current = *backoff_ms;
+        let sleep_ms = match self.strategy {
+            BackoffStrategy::Exponential => {
+                *backoff_ms = self.next_backoff_ms(current);
+                return self.delay(current);
+            }
+            BackoffStrategy::FullJitter => random_up_to(current),
+            BackoffStrategy::EqualJitter => current / 2 + random_up_to(current - current / 2),
+            BackoffStrategy::DecorrelatedJitter => {
+                let upper = current.saturating_mul(3).min(self.backoff_cap_ms);
+                let lower = self.base_backoff_ms.min(upper);
+                let sleep_ms = lower + random_up_to(upper - lower);
+                // The next window grows from this wait, not from a schedule.
+                *backoff_ms = sleep_ms.max(self.base_backoff_ms);
+                return Duration::from_millis(sleep_ms);
+            }
+        };
+        *backoff_ms = self.next_backoff_ms(current);
+        Duration::from_millis(sleep_ms)
+    }
+
+    /// Run `op` until it succeeds, fails with a non-retryable error, or the
+    /// retries are spent. Returns the last error on failure.
+    ///
+    /// Callers that interleave fallbacks or retry budgets between attempts,
+    /// like the provider chain, step [`Self::next_delay`] themselves.
+    pub async fn execute<F, Fut, T>(&self, op: F) -> anyhow::Result<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut backoff_ms = self.base_backoff_ms;
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.max_retries || !self.is_retryable(&e) => return Err(e),
+                Err(e) => {
+                    attempt += 1;
+                    let sleep = self.next_delay(&mut backoff_ms);
+                    tracing::debug!(
+                        attempt,
+                        max_retries = self.max_retries,
+                        sleep_ms = sleep.as_millis(),
+                        "Operation failed, retrying: {e}"
+                    );
+                    tokio::time::sleep(sleep).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` calls with `error`, then returns the call count.
+    fn flaky(calls: &AtomicU32, failures: u32, error: &str) -> anyhow::Result<u32> {
+        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+        if call <= failures {
+            anyhow::bail!("{error}");
+        }
+        Ok(call)
+    }
+
+    #[tokio::test]
+    async fn succeeds_on_first_attempt() {
+        let calls = AtomicU32::new(0);
+        let policy = RetryPolicy::new(3, 1);
+        let result = policy
+            .execute(|| async { flaky(&calls, 0, "boom") })
+            .await
+            .unwrap();
+        assert_eq!(result, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_then_succeeds() {
+        let calls = AtomicU32::new(0);
+        let policy = RetryPolicy::new(3, 1);
+        let result = policy
+            .execute(|| async { flaky(&calls, 2, "boom") })
+            .await
+            .unwrap();
+        assert_eq!(result, 3);
+    }
+
+    #[tokio::test]
+    async fn exhaustion_returns_last_error() {
+        let calls = AtomicU32::new(0);
+        let policy = RetryPolicy::new(2, 1);
+        let err = policy
+            .execute(|| async { flaky(&calls, u32::MAX, "still down") })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "still down");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_errors_stop_immediately() {
+        let calls = AtomicU32::new(0);
+        let policy =
+            RetryPolicy::new(5, 1).with_classifier(|e| !e.to_string().contains("forbidden"));
+        policy
+            .execute(|| async { flaky(&calls, u32::MAX, "403 forbidden") })
+            .await
+            .unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn backoff_grows_by_multiplier_up_to_cap() {
+        let policy = RetryPolicy::new(5, 100)
+            .with_backoff_multiplier(1.5)
+            .with_backoff_cap_ms(300);
+        let mut backoff_ms = policy.base_backoff_ms();
+        let mut sequence = vec![backoff_ms];
+        for _ in 0..4 {
+            backoff_ms = policy.next_backoff_ms(backoff_ms);
+            sequence.push(backoff_ms);
+        }
+        assert_eq!(sequence, vec![100, 150, 225, 300, 300]);
+    }
+
+    #[test]
+    fn jittered_strategies_stay_within_their_windows() {
+        let policy = |strategy| {
+            RetryPolicy::new(5, 100)
+                .with_backoff_cap_ms(1_000)
+                .with_strategy(strategy)
+        };
+        for _ in 0..100 {
+            let full = policy(BackoffStrategy::FullJitter);
+            let mut backoff_ms = 400;
+            assert!(full.next_delay(&mut backoff_ms).as_millis() <= 400);
+            assert_eq!(backoff_ms, 800);
+
+            let equal = policy(BackoffStrategy::EqualJitter);
+            let mut backoff_ms = 400;
+            let sleep = equal.next_delay(&mut backoff_ms).as_millis();
+            assert!((200..=400).contains(&sleep), "{sleep}");
+
+            let decorrelated = policy(BackoffStrategy::DecorrelatedJitter);
+            let mut backoff_ms = 500;
+            let sleep = decorrelated.next_delay(&mut backoff_ms).as_millis();
+            assert!((100..=1_000).contains(&sleep), "{sleep}");
+            assert_eq!(u128::from(backoff_ms), sleep.max(100));
+        }
+        assert_eq!(
+            "decorrelated-jitter".parse::<BackoffStrategy>().unwrap(),
+            BackoffStrategy::DecorrelatedJitter
+        );
+        assert!("sometimes".parse::<BackoffStrategy>().is_err());
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = RetryPolicy::new(1, 100).with_jitter(0.5);
+        for _ in 0..100 {
+            let delay = policy.delay(100);
+            assert!((100..=150).contains(&delay.as_millis()), "{delay:?}");
+        }
+        assert_eq!(
+            RetryPolicy::new(1, 100).delay(100),
+            Duration::from_millis(100)
+        );
+    }
+}
@@ -183,7 +183,7 @@ fn normalize_path(path: &Path) -> PathBuf {
 
 impl SecurityPolicy {
     /// Classify command risk. Any high-risk segment marks the whole command high.
-    #[allow(clippy::too_many_lines)]
+    #[allow(clippy::too_many_lines, clippy::unused_self)]
     pub fn command_risk_level(&self, command: &str) -> CommandRiskLevel {
         let mut normalized = command.to_string();
         for sep in ["&&", "||"] {
@@ -470,8 +470,7 @@ impl SecurityPolicy {
 
             if !roots.iter().any(|root| normalized.starts_with(root)) {
                 return Err(format!(
-                    "Filesystem path outside allowlist for shell execution: {}",
-                    trimmed
+                    "Filesystem path outside allowlist for shell execution: {trimmed}"
                 ));
             }
         }
@@ -121,6 +121,7 @@ impl Evaluator {
     // -- Dimension scorers --------------------------------------------------
 
     /// Compatibility: favour Rust repos; penalise unknown languages.
+    #[allow(clippy::unused_self)]
     fn score_compatibility(&self, c: &ScoutResult) -> f64 {
         match c.language.as_deref() {
             Some("Rust") => 1.0,
@@ -131,6 +132,7 @@ impl Evaluator {
     }
 
     /// Quality: based on star count (log scale, capped at 1.0).
+    #[allow(clippy::unused_self, clippy::cast_precision_loss)]
     fn score_quality(&self, c: &ScoutResult) -> f64 {
         // log2(stars + 1) / 10, capped at 1.0
         let raw = ((c.stars as f64) + 1.0).log2() / 10.0;
@@ -138,6 +140,7 @@ impl Evaluator {
     }
 
     /// Security: license presence + bad-pattern check.
+    #[allow(clippy::unused_self)]
     fn score_security(&self, c: &ScoutResult) -> f64 {
         let mut score: f64 = 0.5;
 
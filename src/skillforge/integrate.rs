@@ -53,6 +53,7 @@ impl Integrator {
 
     // -- Generators ---------------------------------------------------------
 
+    #[allow(clippy::unused_self)]
     fn generate_toml(&self, c: &ScoutResult) -> String {
         let lang = c.language.as_deref().unwrap_or("unknown");
         let updated = c
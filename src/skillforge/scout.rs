@@ -106,9 +106,8 @@ impl GitHubScout {
 
     /// Parse the GitHub search/repositories JSON response.
     fn parse_items(body: &serde_json::Value) -> Vec<ScoutResult> {
-        let items = match body.get("items").and_then(|v| v.as_array()) {
-            Some(arr) => arr,
-            None => return vec![],
+        let Some(items) = body.get("items").and_then(|v| v.as_array()) else {
+            return vec![];
         };
 
         items
@@ -4,7 +4,7 @@
 //! It supports semantic element selection, accessibility snapshots, and JSON output
 //! for efficient LLM integration.
 
-use super::traits::{Tool, ToolResult};
+use super::traits::{ResourceClass, Tool, ToolResult};
 use crate::security::SecurityPolicy;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -354,12 +354,14 @@ impl BrowserTool {
                 success: true,
                 output,
                 error: None,
+                error_kind: None,
             })
         } else {
             Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: resp.error,
+                error_kind: None,
             })
         }
     }
@@ -462,6 +464,10 @@ impl Tool for BrowserTool {
         })
     }
 
+    fn resource_class(&self) -> ResourceClass {
+        ResourceClass::Heavy
+    }
+
     async fn execute(&self, args: Value) -> anyhow::Result<ToolResult> {
         // Security checks
         if !self.security.can_act() {
@@ -469,6 +475,7 @@ impl Tool for BrowserTool {
                 success: false,
                 output: String::new(),
                 error: Some("Action blocked: autonomy is read-only".into()),
+                error_kind: None,
             });
         }
 
@@ -477,6 +484,7 @@ impl Tool for BrowserTool {
                 success: false,
                 output: String::new(),
                 error: Some("Action blocked: rate limit exceeded".into()),
+                error_kind: None,
             });
         }
 
@@ -489,6 +497,7 @@ impl Tool for BrowserTool {
                     "agent-browser CLI not found. Install with: npm install -g agent-browser"
                         .into(),
                 ),
+                error_kind: None,
             });
         }
 
@@ -650,6 +659,7 @@ impl Tool for BrowserTool {
                     success: false,
                     output: String::new(),
                     error: Some(format!("Unknown action: {action_str}")),
+                    error_kind: None,
                 });
             }
         };
@@ -87,6 +87,7 @@ impl Tool for BrowserOpenTool {
                 success: false,
                 output: String::new(),
                 error: Some("Action blocked: autonomy is read-only".into()),
+                error_kind: None,
             });
         }
 
@@ -95,6 +96,7 @@ impl Tool for BrowserOpenTool {
                 success: false,
                 output: String::new(),
                 error: Some("Action blocked: rate limit exceeded".into()),
+                error_kind: None,
             });
         }
 
@@ -105,6 +107,7 @@ impl Tool for BrowserOpenTool {
                     success: false,
                     output: String::new(),
                     error: Some(e.to_string()),
+                    error_kind: None,
                 })
             }
         };
@@ -114,11 +117,13 @@ impl Tool for BrowserOpenTool {
                 success: true,
                 output: format!("Opened in Brave: {url}"),
                 error: None,
+                error_kind: None,
             }),
             Err(e) => Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Failed to open Brave Browser: {e}")),
+                error_kind: None,
             }),
         }
     }
@@ -0,0 +1,204 @@
+use super::traits::{ResourceClass, Tool, ToolResult};
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    result: ToolResult,
+    inserted_at: Instant,
+}
+
+/// Wrapper that memoizes successful results of a pure tool.
+///
+/// Results are keyed by a hash of the tool name and its canonical JSON
+/// arguments and expire after `ttl`. When the cache is full the oldest entry
+/// is evicted, matching the provider response cache. Only wrap tools whose
+/// output depends solely on their arguments.
+pub struct CachingTool {
+    inner: Box<dyn Tool>,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl CachingTool {
+    pub fn new(inner: Box<dyn Tool>, ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            inner,
+            ttl,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cache_key(&self, args: &serde_json::Value) -> String {
+        // `serde_json::Map` is a `BTreeMap` here, so serialization sorts object
+        // keys and equal arguments always produce the same string.
+        let canonical_args = serde_json::to_string(args).unwrap_or_default();
+        let digest = Sha256::digest(format!("{}|{canonical_args}", self.inner.name()));
+        hex::encode(digest)
+    }
+
+    fn cache_get(&self, key: &str) -> Option<ToolResult> {
+        if self.ttl.is_zero() || self.max_entries == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|_, v| now.duration_since(v.inserted_at) <= self.ttl);
+        entries.get(key).map(|entry| entry.result.clone())
+    }
+
+    fn cache_put(&self, key: String, result: ToolResult) {
+        if self.ttl.is_zero() || self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.insert(
+            key,
+            CacheEntry {
+                result,
+                inserted_at: Instant::now(),
+            },
+        );
+
+        if entries.len() > self.max_entries {
+            let mut by_age: Vec<(String, Instant)> = entries
+                .iter()
+                .map(|(k, v)| (k.clone(), v.inserted_at))
+                .collect();
+            by_age.sort_by_key(|(_, ts)| *ts);
+            let to_remove = entries.len() - self.max_entries;
+            for (key, _) in by_age.into_iter().take(to_remove) {
+                entries.remove(&key);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for CachingTool {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &str {
+        self.inner.description()
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        self.inner.parameters_schema()
+    }
+
+    fn resource_class(&self) -> ResourceClass {
+        self.inner.resource_class()
+    }
+
+    async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult> {
+        let key = self.cache_key(&args);
+        if let Some(hit) = self.cache_get(&key) {
+            tracing::debug!(tool = self.inner.name(), "Tool result cache hit");
+            return Ok(hit);
+        }
+
+        let result = self.inner.execute(args).await?;
+        if result.success {
+            self.cache_put(key, result.clone());
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Doubles `n`; fails when `n` is negative. Counts executions.
+    struct DoublerTool {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Tool for DoublerTool {
+        fn name(&self) -> &str {
+            "doubler"
+        }
+
+        fn description(&self) -> &str {
+            "Doubles a number"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            json!({"type": "object"})
+        }
+
+        async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let n = args["n"].as_i64().unwrap_or_default();
+            Ok(ToolResult {
+                success: n >= 0,
+                output: (n * 2).to_string(),
+                error: None,
+                error_kind: None,
+            })
+        }
+    }
+
+    fn caching(calls: &Arc<AtomicUsize>, max_entries: usize) -> CachingTool {
+        CachingTool::new(
+            Box::new(DoublerTool {
+                calls: Arc::clone(calls),
+            }),
+            Duration::from_secs(300),
+            max_entries,
+        )
+    }
+
+    #[tokio::test]
+    async fn identical_call_is_served_from_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tool = caching(&calls, 16);
+
+        let a = tool.execute(json!({"n": 21, "unit": "x"})).await.unwrap();
+        // Same arguments in a different key order hit the same entry.
+        let b = tool.execute(json!({"unit": "x", "n": 21})).await.unwrap();
+
+        assert_eq!(a.output, "42");
+        assert_eq!(b.output, "42");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        tool.execute(json!({"n": 5})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_results_are_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tool = caching(&calls, 16);
+
+        tool.execute(json!({"n": -1})).await.unwrap();
+        tool.execute(json!({"n": -1})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn oldest_entry_is_evicted_when_full() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tool = caching(&calls, 1);
+
+        tool.execute(json!({"n": 1})).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        tool.execute(json!({"n": 2})).await.unwrap();
+        tool.execute(json!({"n": 2})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        tool.execute(json!({"n": 1})).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
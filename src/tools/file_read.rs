@@ -52,6 +52,7 @@ impl Tool for FileReadTool {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Path not allowed by security policy: {path}")),
+                error_kind: None,
             });
         }
 
@@ -65,6 +66,7 @@ impl Tool for FileReadTool {
                     success: false,
                     output: String::new(),
                     error: Some(format!("Failed to resolve file path: {e}")),
+                    error_kind: None,
                 });
             }
         };
@@ -77,6 +79,7 @@ impl Tool for FileReadTool {
                     "Resolved path escapes workspace: {}",
                     resolved_path.display()
                 )),
+                error_kind: None,
             });
         }
 
@@ -91,6 +94,7 @@ impl Tool for FileReadTool {
                             "File too large: {} bytes (limit: {MAX_FILE_SIZE} bytes)",
                             meta.len()
                         )),
+                        error_kind: None,
                     });
                 }
             }
@@ -99,6 +103,7 @@ impl Tool for FileReadTool {
                     success: false,
                     output: String::new(),
                     error: Some(format!("Failed to read file metadata: {e}")),
+                    error_kind: None,
                 });
             }
         }
@@ -108,11 +113,13 @@ impl Tool for FileReadTool {
                 success: true,
                 output: contents,
                 error: None,
+                error_kind: None,
             }),
             Err(e) => Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Failed to read file: {e}")),
+                error_kind: None,
             }),
         }
     }
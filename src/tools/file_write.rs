@@ -59,6 +59,7 @@ impl Tool for FileWriteTool {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Path not allowed by security policy: {path}")),
+                error_kind: None,
             });
         }
 
@@ -69,6 +70,7 @@ impl Tool for FileWriteTool {
                 success: false,
                 output: String::new(),
                 error: Some("Invalid path: missing parent directory".into()),
+                error_kind: None,
             });
         };
 
@@ -83,6 +85,7 @@ impl Tool for FileWriteTool {
                     success: false,
                     output: String::new(),
                     error: Some(format!("Failed to resolve file path: {e}")),
+                    error_kind: None,
                 });
             }
         };
@@ -95,6 +98,7 @@ impl Tool for FileWriteTool {
                     "Resolved path escapes workspace: {}",
                     resolved_parent.display()
                 )),
+                error_kind: None,
             });
         }
 
@@ -103,6 +107,7 @@ impl Tool for FileWriteTool {
                 success: false,
                 output: String::new(),
                 error: Some("Invalid path: missing file name".into()),
+                error_kind: None,
             });
         };
 
@@ -118,6 +123,7 @@ impl Tool for FileWriteTool {
                         "Refusing to write through symlink: {}",
                         resolved_target.display()
                     )),
+                    error_kind: None,
                 });
             }
         }
@@ -127,11 +133,13 @@ impl Tool for FileWriteTool {
                 success: true,
                 output: format!("Written {} bytes to {path}", content.len()),
                 error: None,
+                error_kind: None,
             }),
             Err(e) => Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Failed to write file: {e}")),
+                error_kind: None,
             }),
         }
     }
@@ -166,6 +166,7 @@ impl Tool for ImageInfoTool {
                 error: Some(format!(
                     "Path not allowed: {path_str} (must be within workspace)"
                 )),
+                error_kind: None,
             });
         }
 
@@ -174,6 +175,7 @@ impl Tool for ImageInfoTool {
                 success: false,
                 output: String::new(),
                 error: Some(format!("File not found: {path_str}")),
+                error_kind: None,
             });
         }
 
@@ -190,6 +192,7 @@ impl Tool for ImageInfoTool {
                 error: Some(format!(
                     "Image too large: {file_size} bytes (max {MAX_IMAGE_BYTES} bytes)"
                 )),
+                error_kind: None,
             });
         }
 
@@ -224,6 +227,7 @@ impl Tool for ImageInfoTool {
             success: true,
             output,
             error: None,
+            error_kind: None,
         })
     }
 }
@@ -1,5 +1,6 @@
 pub mod browser;
 pub mod browser_open;
+pub mod caching;
 pub mod composio;
 pub mod file_read;
 pub mod file_write;
@@ -7,12 +8,16 @@ pub mod image_info;
 pub mod memory_forget;
 pub mod memory_recall;
 pub mod memory_store;
+pub mod process;
+pub mod registry;
 pub mod screenshot;
 pub mod shell;
 pub mod traits;
 
 pub use browser::BrowserTool;
 pub use browser_open::BrowserOpenTool;
+#[allow(unused_imports)]
+pub use caching::CachingTool;
 pub use composio::ComposioTool;
 pub use file_read::FileReadTool;
 pub use file_write::FileWriteTool;
@@ -20,11 +25,13 @@ pub use image_info::ImageInfoTool;
 pub use memory_forget::MemoryForgetTool;
 pub use memory_recall::MemoryRecallTool;
 pub use memory_store::MemoryStoreTool;
+#[allow(unused_imports)]
+pub use registry::ToolRegistry;
 pub use screenshot::ScreenshotTool;
 pub use shell::ShellTool;
 pub use traits::Tool;
 #[allow(unused_imports)]
-pub use traits::{ToolResult, ToolSpec};
+pub use traits::{ResourceClass, ToolChunk, ToolErrorKind, ToolResult, ToolSpec, ToolStream};
 
 use crate::memory::Memory;
 use crate::runtime::{NativeRuntime, RuntimeAdapter};
@@ -225,6 +232,7 @@ mod tests {
             success: true,
             output: "hello".into(),
             error: None,
+            error_kind: None,
         };
         let json = serde_json::to_string(&result).unwrap();
         let parsed: ToolResult = serde_json::from_str(&json).unwrap();
@@ -239,6 +247,7 @@ mod tests {
             success: false,
             output: String::new(),
             error: Some("boom".into()),
+            error_kind: None,
         };
         let json = serde_json::to_string(&result).unwrap();
         let parsed: ToolResult = serde_json::from_str(&json).unwrap();
@@ -0,0 +1,111 @@
+use super::traits::{ToolChunk, ToolStream};
+use std::process::{Output, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+
+/// Run `cmd` to completion like `Command::output`, but forward each stdout and
+/// stderr line to `tx` as soon as the child writes it.
+///
+/// The full captured output is still returned. A closed receiver does not
+/// stop the process; chunks are then simply dropped. The child is killed if
+/// the returned future is dropped (e.g. by a timeout).
+pub async fn output_streaming(
+    mut cmd: Command,
+    tx: &mpsc::Sender<ToolChunk>,
+) -> std::io::Result<Output> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn()?;
+    let mut stdout = piped_reader(child.stdout.take())?;
+    let mut stderr = piped_reader(child.stderr.take())?;
+
+    let (mut stdout_buf, mut stderr_buf) = (Vec::new(), Vec::new());
+    let (mut stdout_line, mut stderr_line) = (Vec::new(), Vec::new());
+    let (mut stdout_open, mut stderr_open) = (true, true);
+
+    // `read_until` keeps partially read bytes in the line buffer when the
+    // other branch wins, so no output is lost across iterations.
+    loop {
+        tokio::select! {
+            read = stdout.read_until(b'\n', &mut stdout_line), if stdout_open => {
+                if read? == 0 {
+                    stdout_open = false;
+                } else {
+                    forward_line(tx, ToolStream::Stdout, &mut stdout_line, &mut stdout_buf).await;
+                }
+            }
+            read = stderr.read_until(b'\n', &mut stderr_line), if stderr_open => {
+                if read? == 0 {
+                    stderr_open = false;
+                } else {
+                    forward_line(tx, ToolStream::Stderr, &mut stderr_line, &mut stderr_buf).await;
+                }
+            }
+            else => break,
+        }
+    }
+
+    let status = child.wait().await?;
+    Ok(Output {
+        status,
+        stdout: stdout_buf,
+        stderr: stderr_buf,
+    })
+}
+
+fn piped_reader<R: AsyncRead + Unpin>(pipe: Option<R>) -> std::io::Result<BufReader<R>> {
+    pipe.map(BufReader::new)
+        .ok_or_else(|| std::io::Error::other("child pipe was not captured"))
+}
+
+async fn forward_line(
+    tx: &mpsc::Sender<ToolChunk>,
+    stream: ToolStream,
+    line: &mut Vec<u8>,
+    captured: &mut Vec<u8>,
+) {
+    let _ = tx
+        .send(ToolChunk {
+            stream,
+            text: String::from_utf8_lossy(line).into_owned(),
+        })
+        .await;
+    captured.append(line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn captures_both_streams_and_forwards_lines() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("echo out1; echo err1 >&2; echo out2; exit 3");
+        let (tx, mut rx) = mpsc::channel(16);
+
+        let output = output_streaming(cmd, &tx).await.unwrap();
+        drop(tx);
+
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "out1\nout2\n");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "err1\n");
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            chunks.push(chunk);
+        }
+        let stdout: Vec<&str> = chunks
+            .iter()
+            .filter(|c| c.stream == ToolStream::Stdout)
+            .map(|c| c.text.as_str())
+            .collect();
+        assert_eq!(stdout, vec!["out1\n", "out2\n"]);
+        assert!(chunks
+            .iter()
+            .any(|c| c.stream == ToolStream::Stderr && c.text == "err1\n"));
+    }
+}
@@ -0,0 +1,215 @@
+use super::traits::{ResourceClass, Tool, ToolResult};
+use futures_util::future::join_all;
+use tokio::sync::Semaphore;
+
+/// Default cap on concurrently running `Heavy` tools.
+const HEAVY_CONCURRENCY: usize = 2;
+
+/// Concurrency cap for `class` from `CRABCLAW_TOOL_<CLASS>_CONCURRENCY`;
+/// `0` lifts the cap.
+fn concurrency_limit_from_env(class: ResourceClass) -> Option<usize> {
+    let (var, default) = match class {
+        ResourceClass::Light => ("CRABCLAW_TOOL_LIGHT_CONCURRENCY", None),
+        ResourceClass::Heavy => ("CRABCLAW_TOOL_HEAVY_CONCURRENCY", Some(HEAVY_CONCURRENCY)),
+    };
+    match std::env::var(var)
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None => default,
+    }
+}
+
+/// The tools available to an agent, plus per-[`ResourceClass`] semaphores that
+/// bound how many invocations of each class run at once.
+///
+/// By default at most two `Heavy` tools (shell-outs, browser automation) run
+/// concurrently and `Light` tools are unbounded; override with
+/// `CRABCLAW_TOOL_HEAVY_CONCURRENCY` / `CRABCLAW_TOOL_LIGHT_CONCURRENCY` or
+/// [`ToolRegistry::with_concurrency_limit`].
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+    light: Option<Semaphore>,
+    heavy: Option<Semaphore>,
+}
+
+impl ToolRegistry {
+    pub fn new(tools: Vec<Box<dyn Tool>>) -> Self {
+        let semaphore = |class| concurrency_limit_from_env(class).map(Semaphore::new);
+        Self {
+            tools,
+            light: semaphore(ResourceClass::Light),
+            heavy: semaphore(ResourceClass::Heavy),
+        }
+    }
+
+    /// Allow at most `limit` concurrent executions of `class`; `None` removes
+    /// the cap. A limit of zero is treated as one.
+    pub fn with_concurrency_limit(mut self, class: ResourceClass, limit: Option<usize>) -> Self {
+        let semaphore = limit.map(|limit| Semaphore::new(limit.max(1)));
+        match class {
+            ResourceClass::Light => self.light = semaphore,
+            ResourceClass::Heavy => self.heavy = semaphore,
+        }
+        self
+    }
+
+    pub fn tools(&self) -> &[Box<dyn Tool>] {
+        &self.tools
+    }
+
+    pub fn find(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == name)
+            .map(std::convert::AsRef::as_ref)
+    }
+
+    fn limit(&self, class: ResourceClass) -> Option<&Semaphore> {
+        match class {
+            ResourceClass::Light => self.light.as_ref(),
+            ResourceClass::Heavy => self.heavy.as_ref(),
+        }
+    }
+
+    /// Run tool `name`, waiting for a slot in its resource class first.
+    pub async fn dispatch(
+        &self,
+        name: &str,
+        args: serde_json::Value,
+    ) -> anyhow::Result<ToolResult> {
+        let tool = self
+            .find(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown tool: {name}"))?;
+        // The semaphores are never closed, so acquiring cannot fail.
+        let _permit = match self.limit(tool.resource_class()) {
+            Some(limit) => Some(limit.acquire().await?),
+            None => None,
+        };
+        tool.execute(args).await
+    }
+
+    /// Run every `(name, args)` call concurrently, subject to the per-class
+    /// limits. Results are returned in call order.
+    pub async fn dispatch_many(
+        &self,
+        calls: &[(String, serde_json::Value)],
+    ) -> Vec<anyhow::Result<ToolResult>> {
+        join_all(
+            calls
+                .iter()
+                .map(|(name, args)| self.dispatch(name, args.clone())),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Tracks how many executions of one class overlap.
+    #[derive(Default)]
+    struct Gauge {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    struct SleepyTool {
+        name: &'static str,
+        class: ResourceClass,
+        gauge: Arc<Gauge>,
+    }
+
+    #[async_trait]
+    impl Tool for SleepyTool {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "Sleeps briefly"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        fn resource_class(&self) -> ResourceClass {
+            self.class
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> anyhow::Result<ToolResult> {
+            let running = self.gauge.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.gauge.peak.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            self.gauge.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ToolResult {
+                success: true,
+                output: self.name.into(),
+                error: None,
+                error_kind: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn heavy_tools_are_capped_while_light_tools_run_freely() {
+        let heavy = Arc::new(Gauge::default());
+        let light = Arc::new(Gauge::default());
+        let registry = ToolRegistry::new(vec![
+            Box::new(SleepyTool {
+                name: "build",
+                class: ResourceClass::Heavy,
+                gauge: Arc::clone(&heavy),
+            }),
+            Box::new(SleepyTool {
+                name: "lookup",
+                class: ResourceClass::Light,
+                gauge: Arc::clone(&light),
+            }),
+        ])
+        .with_concurrency_limit(ResourceClass::Heavy, Some(2))
+        .with_concurrency_limit(ResourceClass::Light, None);
+
+        let calls: Vec<(String, serde_json::Value)> = (0..6)
+            .flat_map(|_| ["build", "lookup"])
+            .map(|name| (name.to_string(), serde_json::json!({})))
+            .collect();
+        let results = registry.dispatch_many(&calls).await;
+
+        assert_eq!(results.len(), 12);
+        for ((name, _), result) in calls.iter().zip(&results) {
+            assert_eq!(&result.as_ref().unwrap().output, name);
+        }
+        assert_eq!(heavy.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(light.peak.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn unknown_tools_fail_without_affecting_others() {
+        let registry = ToolRegistry::new(vec![Box::new(SleepyTool {
+            name: "lookup",
+            class: ResourceClass::Light,
+            gauge: Arc::default(),
+        })]);
+        let results = registry
+            .dispatch_many(&[
+                ("missing".into(), serde_json::json!({})),
+                ("lookup".into(), serde_json::json!({})),
+            ])
+            .await;
+        assert!(results[0]
+            .as_ref()
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown tool: missing"));
+        assert!(results[1].as_ref().unwrap().success);
+    }
+}
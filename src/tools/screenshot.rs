@@ -1,4 +1,4 @@
-use super::traits::{Tool, ToolResult};
+use super::traits::{ResourceClass, Tool, ToolResult};
 use crate::security::SecurityPolicy;
 use async_trait::async_trait;
 use serde_json::json;
@@ -76,6 +76,7 @@ impl ScreenshotTool {
                 success: false,
                 output: String::new(),
                 error: Some("Screenshot not supported on this platform".into()),
+                error_kind: None,
             });
         };
 
@@ -111,12 +112,14 @@ impl ScreenshotTool {
                                 "No screenshot tool found. Install gnome-screenshot, scrot, or ImageMagick."
                                     .into(),
                             ),
+                            error_kind: None,
                         });
                     }
                     return Ok(ToolResult {
                         success: false,
                         output: String::new(),
                         error: Some(format!("Screenshot command failed: {stderr}")),
+                        error_kind: None,
                     });
                 }
 
@@ -126,6 +129,7 @@ impl ScreenshotTool {
                 success: false,
                 output: String::new(),
                 error: Some(format!("Failed to execute screenshot command: {e}")),
+                error_kind: None,
             }),
             Err(_) => Ok(ToolResult {
                 success: false,
@@ -133,6 +137,7 @@ impl ScreenshotTool {
                 error: Some(format!(
                     "Screenshot timed out after {SCREENSHOT_TIMEOUT_SECS}s"
                 )),
+                error_kind: None,
             }),
         }
     }
@@ -151,6 +156,7 @@ impl ScreenshotTool {
                         meta.len(),
                     ),
                     error: None,
+                    error_kind: None,
                 });
             }
         }
@@ -188,12 +194,14 @@ impl ScreenshotTool {
                     success: true,
                     output: output_msg,
                     error: None,
+                    error_kind: None,
                 })
             }
             Err(e) => Ok(ToolResult {
                 success: false,
                 output: format!("Screenshot saved to: {}", output_path.display()),
                 error: Some(format!("Failed to read screenshot file: {e}")),
+                error_kind: None,
             }),
         }
     }
@@ -225,12 +233,17 @@ impl Tool for ScreenshotTool {
         })
     }
 
+    fn resource_class(&self) -> ResourceClass {
+        ResourceClass::Heavy
+    }
+
     async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult> {
         if !self.security.can_act() {
             return Ok(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some("Action blocked: autonomy is read-only".into()),
+                error_kind: None,
             });
         }
         self.capture(args).await
@@ -1,9 +1,11 @@
-use super::traits::{Tool, ToolResult};
+use super::process::output_streaming;
+use super::traits::{ResourceClass, Tool, ToolChunk, ToolErrorKind, ToolResult};
 use crate::runtime::RuntimeAdapter;
 use crate::security::SecurityPolicy;
 use async_trait::async_trait;
 use serde_json::json;
 use std::path::PathBuf;
+use std::process::Output;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -47,63 +49,30 @@ impl ShellTool {
     pub fn new(security: Arc<SecurityPolicy>, runtime: Arc<dyn RuntimeAdapter>) -> Self {
         Self { security, runtime }
     }
-}
-
-#[async_trait]
-impl Tool for ShellTool {
-    fn name(&self) -> &str {
-        "shell"
-    }
-
-    fn description(&self) -> &str {
-        "Execute a shell command in the workspace directory"
-    }
-
-    fn parameters_schema(&self) -> serde_json::Value {
-        json!({
-            "type": "object",
-            "properties": {
-                "command": {
-                    "type": "string",
-                    "description": "The shell command to execute"
-                },
-                "approved": {
-                    "type": "boolean",
-                    "description": "Set true to explicitly approve medium/high-risk commands in supervised mode",
-                    "default": false
-                }
-            },
-            "required": ["command"]
-        })
-    }
-
-    async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult> {
-        let command = args
-            .get("command")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))?;
-        let approved = args
-            .get("approved")
-            .and_then(serde_json::Value::as_bool)
-            .unwrap_or(false);
 
+    /// Run the security checks for `command` and build the sandboxed process.
+    /// A rejected command comes back as the `ToolResult` to report.
+    fn prepare_command(
+        &self,
+        command: &str,
+        approved: bool,
+    ) -> Result<tokio::process::Command, ToolResult> {
         if self.security.is_rate_limited() {
-            return Ok(ToolResult {
+            return Err(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some("Rate limit exceeded: too many actions in the last hour".into()),
+                error_kind: Some(ToolErrorKind::Other),
             });
         }
 
-        match self.security.validate_command_execution(command, approved) {
-            Ok(_) => {}
-            Err(reason) => {
-                return Ok(ToolResult {
-                    success: false,
-                    output: String::new(),
-                    error: Some(reason),
-                });
-            }
+        if let Err(reason) = self.security.validate_command_execution(command, approved) {
+            return Err(ToolResult {
+                success: false,
+                output: String::new(),
+                error: Some(reason),
+                error_kind: Some(ToolErrorKind::PermissionDenied),
+            });
         }
 
         let fs_allowlist = fs_allowlist_roots();
@@ -111,37 +80,35 @@ impl Tool for ShellTool {
             .security
             .validate_command_fs_allowlist(command, &fs_allowlist)
         {
-            return Ok(ToolResult {
+            return Err(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some(reason),
+                error_kind: Some(ToolErrorKind::PermissionDenied),
             });
         }
 
         if !self.security.record_action() {
-            return Ok(ToolResult {
+            return Err(ToolResult {
                 success: false,
                 output: String::new(),
                 error: Some("Rate limit exceeded: action budget exhausted".into()),
+                error_kind: Some(ToolErrorKind::Other),
             });
         }
 
         // Execute with timeout to prevent hanging commands.
         // Clear the environment to prevent leaking API keys and other secrets
         // (CWE-200), then re-add only safe, functional variables.
-        let mut cmd = match self
+        let mut cmd = self
             .runtime
             .build_shell_command(command, &self.security.workspace_dir)
-        {
-            Ok(cmd) => cmd,
-            Err(e) => {
-                return Ok(ToolResult {
-                    success: false,
-                    output: String::new(),
-                    error: Some(format!("Failed to build runtime command: {e}")),
-                });
-            }
-        };
+            .map_err(|e| ToolResult {
+                success: false,
+                output: String::new(),
+                error: Some(format!("Failed to build runtime command: {e}")),
+                error_kind: Some(ToolErrorKind::ExecutionFailed),
+            })?;
         cmd.env_clear();
 
         for var in SAFE_ENV_VARS {
@@ -149,48 +116,132 @@ impl Tool for ShellTool {
                 cmd.env(var, val);
             }
         }
+        Ok(cmd)
+    }
+}
+
+fn parse_args(args: &serde_json::Value) -> anyhow::Result<(&str, bool)> {
+    let command = args
+        .get("command")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing 'command' parameter"))?;
+    let approved = args
+        .get("approved")
+        .and_then(serde_json::Value::as_bool)
+        .unwrap_or(false);
+    Ok((command, approved))
+}
+
+#[async_trait]
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Execute a shell command in the workspace directory"
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The shell command to execute"
+                },
+                "approved": {
+                    "type": "boolean",
+                    "description": "Set true to explicitly approve medium/high-risk commands in supervised mode",
+                    "default": false
+                }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn resource_class(&self) -> ResourceClass {
+        ResourceClass::Heavy
+    }
+
+    async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult> {
+        let (command, approved) = parse_args(&args)?;
+        let mut cmd = match self.prepare_command(command, approved) {
+            Ok(cmd) => cmd,
+            Err(rejected) => return Ok(rejected),
+        };
 
         let timeout_secs = shell_timeout_secs();
         let result = tokio::time::timeout(Duration::from_secs(timeout_secs), cmd.output()).await;
+        Ok(command_result(result, timeout_secs))
+    }
+
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        tx: tokio::sync::mpsc::Sender<ToolChunk>,
+    ) -> anyhow::Result<ToolResult> {
+        let (command, approved) = parse_args(&args)?;
+        let cmd = match self.prepare_command(command, approved) {
+            Ok(cmd) => cmd,
+            Err(rejected) => return Ok(rejected),
+        };
 
-        match result {
-            Ok(Ok(output)) => {
-                let mut stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let timeout_secs = shell_timeout_secs();
+        let result = tokio::time::timeout(
+            Duration::from_secs(timeout_secs),
+            output_streaming(cmd, &tx),
+        )
+        .await;
+        Ok(command_result(result, timeout_secs))
+    }
+}
 
-                // Truncate output to prevent OOM
-                if stdout.len() > MAX_OUTPUT_BYTES {
-                    stdout.truncate(stdout.floor_char_boundary(MAX_OUTPUT_BYTES));
-                    stdout.push_str("\n... [output truncated at 1MB]");
-                }
-                if stderr.len() > MAX_OUTPUT_BYTES {
-                    stderr.truncate(stderr.floor_char_boundary(MAX_OUTPUT_BYTES));
-                    stderr.push_str("\n... [stderr truncated at 1MB]");
-                }
+/// Turn a (possibly timed out) process run into the tool's result.
+fn command_result(
+    result: Result<std::io::Result<Output>, tokio::time::error::Elapsed>,
+    timeout_secs: u64,
+) -> ToolResult {
+    match result {
+        Ok(Ok(output)) => {
+            let mut stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let mut stderr = String::from_utf8_lossy(&output.stderr).to_string();
+
+            // Truncate output to prevent OOM
+            if stdout.len() > MAX_OUTPUT_BYTES {
+                stdout.truncate(stdout.floor_char_boundary(MAX_OUTPUT_BYTES));
+                stdout.push_str("\n... [output truncated at 1MB]");
+            }
+            if stderr.len() > MAX_OUTPUT_BYTES {
+                stderr.truncate(stderr.floor_char_boundary(MAX_OUTPUT_BYTES));
+                stderr.push_str("\n... [stderr truncated at 1MB]");
+            }
 
-                Ok(ToolResult {
-                    success: output.status.success(),
-                    output: stdout,
-                    error: if stderr.is_empty() {
-                        None
-                    } else {
-                        Some(stderr)
-                    },
-                })
+            ToolResult {
+                success: output.status.success(),
+                output: stdout,
+                error: if stderr.is_empty() {
+                    None
+                } else {
+                    Some(stderr)
+                },
+                error_kind: ToolErrorKind::from_exit_status(output.status),
             }
-            Ok(Err(e)) => Ok(ToolResult {
-                success: false,
-                output: String::new(),
-                error: Some(format!("Failed to execute command: {e}")),
-            }),
-            Err(_) => Ok(ToolResult {
-                success: false,
-                output: String::new(),
-                error: Some(format!(
-                    "Command timed out after {timeout_secs}s and was killed"
-                )),
-            }),
         }
+        Ok(Err(e)) => ToolResult {
+            success: false,
+            output: String::new(),
+            error: Some(format!("Failed to execute command: {e}")),
+            error_kind: Some(ToolErrorKind::ExecutionFailed),
+        },
+        Err(_) => ToolResult {
+            success: false,
+            output: String::new(),
+            error: Some(format!(
+                "Command timed out after {timeout_secs}s and was killed"
+            )),
+            error_kind: Some(ToolErrorKind::Timeout),
+        },
     }
 }
 
@@ -420,4 +471,34 @@ mod tests {
             .unwrap_or("")
             .contains("outside allowlist"));
     }
+
+    #[tokio::test]
+    async fn shell_streams_lines_as_they_are_printed() {
+        let security = Arc::new(SecurityPolicy {
+            autonomy: AutonomyLevel::Full,
+            workspace_dir: std::env::temp_dir(),
+            allowed_commands: vec!["echo".into(), "sleep".into()],
+            ..SecurityPolicy::default()
+        });
+        let tool = ShellTool::new(security, test_runtime());
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+
+        let started = std::time::Instant::now();
+        let run = tokio::spawn(async move {
+            tool.execute_streaming(json!({"command": "echo first; sleep 1; echo second"}), tx)
+                .await
+        });
+
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.text, "first\n");
+        // The first line arrives well before the command finishes sleeping.
+        assert!(started.elapsed() < Duration::from_millis(800));
+        assert!(!run.is_finished());
+
+        let second = rx.recv().await.unwrap();
+        assert_eq!(second.text, "second\n");
+        let result = run.await.unwrap().unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "first\nsecond\n");
+    }
 }
@@ -1,12 +1,75 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+/// Category of a tool failure, so callers can branch without parsing `error`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolErrorKind {
+    InvalidArgs,
+    Timeout,
+    NotFound,
+    PermissionDenied,
+    ExecutionFailed,
+    Other,
+}
+
+impl ToolErrorKind {
+    /// Classify a finished process: killed by a signal counts as `Timeout`
+    /// (tools kill overrunning commands), a nonzero exit as `ExecutionFailed`.
+    pub fn from_exit_status(status: std::process::ExitStatus) -> Option<Self> {
+        if status.success() {
+            return None;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if status.signal().is_some() {
+                return Some(Self::Timeout);
+            }
+        }
+        Some(Self::ExecutionFailed)
+    }
+
+    /// Whether running the same call again may succeed.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Timeout)
+    }
+}
+
 /// Result of a tool execution
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolResult {
     pub success: bool,
     pub output: String,
     pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ToolErrorKind>,
+}
+
+/// Output stream a `ToolChunk` was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolStream {
+    Stdout,
+    Stderr,
+}
+
+/// Incremental piece of tool output, emitted while the tool is still running
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolChunk {
+    pub stream: ToolStream,
+    pub text: String,
+}
+
+/// How much of the machine a tool invocation may use, so concurrent dispatch
+/// can cap expensive tools separately from cheap ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceClass {
+    /// In-process work or small file I/O
+    Light,
+    /// Spawns processes or drives external programs (shell, browser)
+    Heavy,
 }
 
 /// Description of a tool for the LLM
@@ -32,6 +95,31 @@ pub trait Tool: Send + Sync {
     /// Execute the tool with given arguments
     async fn execute(&self, args: serde_json::Value) -> anyhow::Result<ToolResult>;
 
+    /// Execute the tool, sending output to `tx` as it is produced. The final
+    /// `ToolResult` is still returned once the tool finishes.
+    ///
+    /// The default runs `execute` and sends its whole output as one chunk;
+    /// process-backed tools override this to stream line by line.
+    async fn execute_streaming(
+        &self,
+        args: serde_json::Value,
+        tx: tokio::sync::mpsc::Sender<ToolChunk>,
+    ) -> anyhow::Result<ToolResult> {
+        let result = self.execute(args).await?;
+        let _ = tx
+            .send(ToolChunk {
+                stream: ToolStream::Stdout,
+                text: result.output.clone(),
+            })
+            .await;
+        Ok(result)
+    }
+
+    /// Resource class used to bound concurrent executions of this tool
+    fn resource_class(&self) -> ResourceClass {
+        ResourceClass::Light
+    }
+
     /// Get the full spec for LLM registration
     fn spec(&self) -> ToolSpec {
         ToolSpec {
@@ -41,3 +129,90 @@ pub trait Tool: Send + Sync {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(script: &str) -> std::process::ExitStatus {
+        std::process::Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .status()
+            .unwrap()
+    }
+
+    #[test]
+    fn exit_status_maps_to_error_kind() {
+        assert_eq!(ToolErrorKind::from_exit_status(run("exit 0")), None);
+        assert_eq!(
+            ToolErrorKind::from_exit_status(run("exit 3")),
+            Some(ToolErrorKind::ExecutionFailed)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_kill_maps_to_timeout() {
+        let kind = ToolErrorKind::from_exit_status(run("kill -9 $$"));
+        assert_eq!(kind, Some(ToolErrorKind::Timeout));
+        assert!(kind.unwrap().is_retryable());
+    }
+
+    #[test]
+    fn error_kind_is_optional_in_json() {
+        let legacy: ToolResult =
+            serde_json::from_str(r#"{"success":false,"output":"","error":"boom"}"#).unwrap();
+        assert_eq!(legacy.error_kind, None);
+
+        let result = ToolResult {
+            success: false,
+            output: String::new(),
+            error: Some("denied".into()),
+            error_kind: Some(ToolErrorKind::PermissionDenied),
+        };
+        let json = serde_json::to_string(&result).unwrap();
+        assert!(json.contains(r#""error_kind":"permission_denied""#));
+    }
+
+    struct FixedTool;
+
+    #[async_trait]
+    impl Tool for FixedTool {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn description(&self) -> &str {
+            "Returns a fixed output"
+        }
+
+        fn parameters_schema(&self) -> serde_json::Value {
+            serde_json::json!({"type": "object"})
+        }
+
+        async fn execute(&self, _args: serde_json::Value) -> anyhow::Result<ToolResult> {
+            Ok(ToolResult {
+                success: true,
+                output: "done".into(),
+                error: None,
+                error_kind: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn default_streaming_sends_one_final_chunk() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        let result = FixedTool
+            .execute_streaming(serde_json::json!({}), tx)
+            .await
+            .unwrap();
+
+        assert_eq!(result.output, "done");
+        let chunk = rx.recv().await.unwrap();
+        assert_eq!(chunk.stream, ToolStream::Stdout);
+        assert_eq!(chunk.text, "done");
+        assert!(rx.recv().await.is_none());
+    }
+}
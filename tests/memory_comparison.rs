@@ -300,7 +300,7 @@ async fn compare_upsert() {
     let md_count = md.count().await.unwrap();
 
     let sq_entry = sq.get("pref").await.unwrap();
-    let md_results = md.recall("loves Rust", 5).await.unwrap();
+    let md_entry = md.get("pref").await.unwrap();
 
     println!("\n============================================================");
     println!("UPSERT (store same key twice):");
@@ -308,15 +308,16 @@ async fn compare_upsert() {
         "  SQLite:   count={sq_count}, latest=\"{}\"",
         sq_entry.as_ref().map_or("none", |e| &e.content)
     );
-    println!("  Markdown: count={md_count} (append-only, both entries kept)");
-    println!("    Can still find latest: {}", !md_results.is_empty());
+    println!(
+        "  Markdown: count={md_count}, latest=\"{}\"",
+        md_entry.as_ref().map_or("none", |e| &e.content)
+    );
 
-    // SQLite: upsert replaces, count stays at 1
+    // Both backends upsert by key: count stays at 1, latest content wins
     assert_eq!(sq_count, 1);
     assert_eq!(sq_entry.unwrap().content, "loves Rust");
-
-    // Markdown: append-only, count increases
-    assert!(md_count >= 2, "Markdown should keep both entries");
+    assert_eq!(md_count, 1);
+    assert_eq!(md_entry.unwrap().content, "loves Rust");
 }
 
 // ── Test 6: Forget / delete capability ─────────────────────────
@@ -346,20 +347,16 @@ async fn compare_forget() {
         sq.count().await.unwrap()
     );
     println!(
-        "  Markdown: {} (append-only by design)",
-        if md_forgot {
-            "✅ Deleted"
-        } else {
-            "⚠️  Cannot delete (audit trail)"
-        },
+        "  Markdown: {} (count={})",
+        if md_forgot { "✅ Deleted" } else { "❌ Kept" },
+        md.count().await.unwrap()
     );
 
-    // SQLite can delete
+    // Both backends delete by key
     assert!(sq_forgot);
     assert_eq!(sq.count().await.unwrap(), 0);
-
-    // Markdown cannot delete (by design)
-    assert!(!md_forgot);
+    assert!(md_forgot);
+    assert_eq!(md.count().await.unwrap(), 0);
 }
 
 // ── Test 7: Category filtering ─────────────────────────────────
@@ -426,7 +423,86 @@ async fn compare_category_filter() {
     assert_eq!(sq_conv.len(), 1);
     assert_eq!(sq_all.len(), 4);
 
-    // Markdown: categories determined by file location
-    assert!(!md_core.is_empty());
-    assert!(!md_all.is_empty());
+    // Markdown: categories carried in front matter
+    assert_eq!(md_core.len(), 2);
+    assert_eq!(md_daily.len(), 1);
+    assert_eq!(md_all.len(), 3);
+}
+
+// ── Test 8: Trait-level parity ─────────────────────────────────
+
+async fn assert_memory_contract(mem: &dyn Memory) {
+    let name = mem.name();
+
+    // store + get
+    mem.store("lang", "User prefers Rust", MemoryCategory::Core)
+        .await
+        .unwrap();
+    mem.store("editor", "Uses Helix for editing", MemoryCategory::Daily)
+        .await
+        .unwrap();
+    mem.store(
+        "custom",
+        "Tracks the deploy checklist",
+        MemoryCategory::Custom("ops".into()),
+    )
+    .await
+    .unwrap();
+    let lang = mem.get("lang").await.unwrap().expect(name);
+    assert_eq!(lang.key, "lang", "{name}");
+    assert_eq!(lang.content, "User prefers Rust", "{name}");
+    assert_eq!(lang.category, MemoryCategory::Core, "{name}");
+    assert!(mem.get("missing").await.unwrap().is_none(), "{name}");
+
+    // upsert
+    mem.store("lang", "User loves Rust", MemoryCategory::Core)
+        .await
+        .unwrap();
+    assert_eq!(mem.count().await.unwrap(), 3, "{name}");
+    assert_eq!(
+        mem.get("lang").await.unwrap().unwrap().content,
+        "User loves Rust",
+        "{name}"
+    );
+
+    // recall
+    let hits = mem.recall("Rust", 10).await.unwrap();
+    assert_eq!(hits.len(), 1, "{name}");
+    assert_eq!(hits[0].key, "lang", "{name}");
+    assert!(hits[0].score.is_some(), "{name}");
+    assert!(
+        mem.recall("javascript", 10).await.unwrap().is_empty(),
+        "{name}"
+    );
+    assert!(mem.recall("", 10).await.unwrap().is_empty(), "{name}");
+    assert!(mem.recall("Rust", 0).await.unwrap().is_empty(), "{name}");
+
+    // list
+    assert_eq!(mem.list(None).await.unwrap().len(), 3, "{name}");
+    assert_eq!(
+        mem.list(Some(&MemoryCategory::Custom("ops".into())))
+            .await
+            .unwrap()
+            .len(),
+        1,
+        "{name}"
+    );
+
+    // forget
+    assert!(mem.forget("lang").await.unwrap(), "{name}");
+    assert!(!mem.forget("lang").await.unwrap(), "{name}");
+    assert!(mem.get("lang").await.unwrap().is_none(), "{name}");
+    assert!(mem.recall("Rust", 10).await.unwrap().is_empty(), "{name}");
+    assert_eq!(mem.count().await.unwrap(), 2, "{name}");
+
+    assert!(mem.health_check().await, "{name}");
+}
+
+#[tokio::test]
+async fn backends_satisfy_same_memory_contract() {
+    let tmp_sq = TempDir::new().unwrap();
+    let tmp_md = TempDir::new().unwrap();
+
+    assert_memory_contract(&sqlite_backend(tmp_sq.path())).await;
+    assert_memory_contract(&markdown_backend(tmp_md.path())).await;
 }